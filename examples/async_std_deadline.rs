@@ -0,0 +1,31 @@
+use async_std::task::{block_on, sleep, spawn};
+use kyansel::{async_std::cancel_after, CancellableResult};
+use std::time::Duration;
+
+fn main() {
+    block_on(async {
+        let mut handles = Vec::new();
+
+        for i in 0..10u64 {
+            //simulate work taking a variable amount of time
+            let work = async move {
+                sleep(Duration::from_millis(i * 10)).await;
+                i
+            };
+
+            //give up on anything slower than 50ms
+            handles.push(spawn(cancel_after(work, Duration::from_millis(50))));
+        }
+
+        let (mut finished, mut cancelled) = (Vec::new(), 0);
+        for handle in handles {
+            match handle.await {
+                CancellableResult::Finished(i) => finished.push(i),
+                CancellableResult::Cancelled(()) => cancelled += 1,
+            }
+        }
+
+        println!("Finished: {:?}", finished);
+        println!("Cancelled: {}", cancelled);
+    });
+}
@@ -0,0 +1,31 @@
+use async_io::Timer;
+use kyansel::{async_io::cancel_after, token::CancellationToken, FutureCancellable};
+use std::time::Duration;
+
+fn main() {
+    smol::block_on(async {
+        let shutdown = CancellationToken::new();
+
+        let tasks: Vec<_> = (0..10u64)
+            .map(|i| {
+                //simulate work taking a variable amount of time
+                let work = async move {
+                    Timer::after(Duration::from_millis(i * 10)).await;
+                    i
+                };
+
+                //give up on anything slower than 70ms, or when shutting down
+                let work = cancel_after(work, Duration::from_millis(70));
+                smol::spawn(work.cancel_with(shutdown.cancelled()))
+            })
+            .collect();
+
+        //shut down before the deadline
+        Timer::after(Duration::from_millis(35)).await;
+        shutdown.cancel();
+
+        for task in tasks {
+            println!("{:?}", task.await);
+        }
+    });
+}
@@ -0,0 +1,103 @@
+//! Procedural macros for [kyansel](https://docs.rs/kyansel)
+//!
+//!Use them through kyansel with the `macros` feature, the generated code refers to it
+
+use proc_macro::TokenStream;
+use quote::{format_ident, quote};
+use syn::{
+    parse::{Parse, ParseStream},
+    parse_macro_input, parse_quote,
+    visit_mut::{self, VisitMut},
+    Expr, FnArg, Ident, ItemFn, ReturnType, Token,
+};
+
+struct Args {
+    token: Ident,
+}
+
+impl Parse for Args {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        if input.is_empty() {
+            return Ok(Self { token: format_ident!("token") });
+        }
+
+        let key: Ident = input.parse()?;
+        if key != "token" {
+            return Err(syn::Error::new(key.span(), "expected `token = <ident>`"));
+        }
+        input.parse::<Token![=]>()?;
+
+        Ok(Self { token: input.parse()? })
+    }
+}
+
+//appends a checkpoint to every `.await` of the function body
+struct Checkpoints<'a> {
+    token: &'a Ident,
+}
+
+impl VisitMut for Checkpoints<'_> {
+    fn visit_expr_mut(&mut self, expr: &mut Expr) {
+        match expr {
+            //they run on their own, the token isn't theirs to check
+            Expr::Async(_) | Expr::Closure(_) => return,
+            _ => visit_mut::visit_expr_mut(self, expr),
+        }
+
+        if let Expr::Await(_) = expr {
+            let token = self.token;
+            *expr = parse_quote! {{
+                let __kyansel_output = #expr;
+                #token.checkpoint().await;
+                __kyansel_output
+            }};
+        }
+    }
+
+    //nested items have their own bodies
+    fn visit_item_mut(&mut self, _: &mut syn::Item) {}
+}
+
+///Make an `async fn` cancellable by a `CancellationToken`
+///
+///The function gains a trailing `token: kyansel::token::CancellationToken` parameter,
+/// renamed with `#[cancellable(token = name)]`, and returns a
+/// `kyansel::CancellableResult<T, ()>` instead of `T`.
+///
+///Every `.await` in the body is followed by a checkpoint on the token,
+/// so the function is cancelled at the next await point once the token is cancelled,
+/// even if the awaited futures complete right away.
+///Closures, async blocks and nested items are left untouched.
+#[proc_macro_attribute]
+pub fn cancellable(args: TokenStream, item: TokenStream) -> TokenStream {
+    let Args { token } = parse_macro_input!(args as Args);
+    let mut item = parse_macro_input!(item as ItemFn);
+
+    if item.sig.asyncness.is_none() {
+        let span = item.sig.fn_token.span;
+        return syn::Error::new(span, "only `async fn` can be made cancellable")
+            .to_compile_error()
+            .into();
+    }
+
+    Checkpoints { token: &token }.visit_block_mut(&mut item.block);
+
+    let output = match &item.sig.output {
+        ReturnType::Default => quote!(()),
+        ReturnType::Type(_, ty) => quote!(#ty),
+    };
+
+    let param: FnArg = parse_quote!(#token: ::kyansel::token::CancellationToken);
+    item.sig.inputs.push(param);
+    item.sig.output = parse_quote!(-> ::kyansel::CancellableResult<#output, ()>);
+
+    let body = &item.block;
+    item.block = parse_quote! {{
+        let __kyansel_stopper = #token.cancelled();
+        let __kyansel_body = ::kyansel::__private::output::<#output, _>(async move #body);
+
+        ::kyansel::FutureCancellable::cancel_with(__kyansel_body, __kyansel_stopper).await
+    }};
+
+    quote!(#item).into()
+}
@@ -0,0 +1,266 @@
+//! `Arbitrary` implementations for property-based testing
+//!
+//! # Example
+//! ```rust
+//! # #[cfg(feature = "proptest")]
+//! # {
+//!  use kyansel::CancellableResult;
+//!  use proptest::prelude::*;
+//!
+//!  proptest!(|(result: CancellableResult<u8, String>)| {
+//!      prop_assert_eq!(result.is_cancelled(), result.clone().cancelled().is_some());
+//!  });
+//! # }
+//! ```
+
+use super::{
+    grace::Escalated, race::Hedged, stall::Stalled, timer::TimeoutOrCancelled,
+    watchdog::Starved, CancellableResult,
+};
+
+#[cfg(feature = "futures_01")]
+use super::futures_01::CancellableError;
+
+#[cfg(feature = "proptest")]
+mod proptest_impls {
+    use super::*;
+    use proptest::{
+        arbitrary::{any, any_with, Arbitrary},
+        strategy::{BoxedStrategy, LazyJust, Strategy},
+    };
+
+    impl<T, S> Arbitrary for CancellableResult<T, S>
+    where
+        T: Arbitrary + 'static,
+        S: Arbitrary + 'static,
+    {
+        type Parameters = (T::Parameters, S::Parameters);
+        type Strategy = BoxedStrategy<Self>;
+
+        fn arbitrary_with((t, s): Self::Parameters) -> Self::Strategy {
+            proptest::prop_oneof![
+                any_with::<T>(t).prop_map(Self::Finished),
+                any_with::<S>(s).prop_map(Self::Cancelled),
+            ]
+            .boxed()
+        }
+    }
+
+    impl<T, S> Arbitrary for TimeoutOrCancelled<T, S>
+    where
+        T: Arbitrary + 'static,
+        S: Arbitrary + 'static,
+    {
+        type Parameters = (T::Parameters, S::Parameters);
+        type Strategy = BoxedStrategy<Self>;
+
+        fn arbitrary_with((t, s): Self::Parameters) -> Self::Strategy {
+            proptest::prop_oneof![
+                any_with::<T>(t).prop_map(Self::Finished),
+                LazyJust::new(|| Self::TimedOut),
+                any_with::<S>(s).prop_map(Self::Cancelled),
+            ]
+            .boxed()
+        }
+    }
+
+    #[cfg(feature = "futures_01")]
+    impl<C, E> Arbitrary for CancellableError<C, E>
+    where
+        C: Arbitrary + 'static,
+        E: Arbitrary + 'static,
+    {
+        type Parameters = (C::Parameters, E::Parameters);
+        type Strategy = BoxedStrategy<Self>;
+
+        fn arbitrary_with((c, e): Self::Parameters) -> Self::Strategy {
+            proptest::prop_oneof![
+                any_with::<C>(c).prop_map(Self::Cancelled),
+                any_with::<E>(e).prop_map(Self::Errored),
+            ]
+            .boxed()
+        }
+    }
+
+    impl<T> Arbitrary for Hedged<T>
+    where
+        T: Arbitrary + 'static,
+    {
+        type Parameters = T::Parameters;
+        type Strategy = BoxedStrategy<Self>;
+
+        fn arbitrary_with(t: Self::Parameters) -> Self::Strategy {
+            (any::<bool>(), any_with::<T>(t))
+                .prop_map(|(backup, t)| if backup { Self::Backup(t) } else { Self::Primary(t) })
+                .boxed()
+        }
+    }
+
+    impl<T, S> Arbitrary for Escalated<T, S>
+    where
+        T: Arbitrary + 'static,
+        S: Arbitrary + 'static,
+        T::Parameters: Clone,
+        S::Parameters: Clone,
+    {
+        type Parameters = (T::Parameters, S::Parameters);
+        type Strategy = BoxedStrategy<Self>;
+
+        fn arbitrary_with((t, s): Self::Parameters) -> Self::Strategy {
+            proptest::prop_oneof![
+                any_with::<T>(t.clone()).prop_map(Self::Finished),
+                (any_with::<S>(s.clone()), any_with::<T>(t)).prop_map(|(s, t)| Self::Soft(s, t)),
+                any_with::<S>(s).prop_map(Self::Hard),
+            ]
+            .boxed()
+        }
+    }
+
+    impl Arbitrary for Stalled {
+        type Parameters = ();
+        type Strategy = BoxedStrategy<Self>;
+
+        fn arbitrary_with((): Self::Parameters) -> Self::Strategy {
+            any::<core::time::Duration>().prop_map(|idle| Self { idle }).boxed()
+        }
+    }
+
+    impl Arbitrary for Starved {
+        type Parameters = ();
+        type Strategy = BoxedStrategy<Self>;
+
+        fn arbitrary_with((): Self::Parameters) -> Self::Strategy {
+            any::<core::time::Duration>().prop_map(|interval| Self { interval }).boxed()
+        }
+    }
+}
+
+#[cfg(feature = "quickcheck")]
+mod quickcheck_impls {
+    use super::*;
+    use quickcheck::{Arbitrary, Gen};
+    use std::boxed::Box;
+
+    type Shrunk<T> = Box<dyn Iterator<Item = T>>;
+
+    impl<T, S> Arbitrary for CancellableResult<T, S>
+    where
+        T: Arbitrary,
+        S: Arbitrary,
+    {
+        fn arbitrary(g: &mut Gen) -> Self {
+            match bool::arbitrary(g) {
+                true => Self::Finished(T::arbitrary(g)),
+                false => Self::Cancelled(S::arbitrary(g)),
+            }
+        }
+
+        fn shrink(&self) -> Shrunk<Self> {
+            match self {
+                Self::Finished(t) => Box::new(t.shrink().map(Self::Finished)),
+                Self::Cancelled(s) => Box::new(s.shrink().map(Self::Cancelled)),
+            }
+        }
+    }
+
+    impl<T, S> Arbitrary for TimeoutOrCancelled<T, S>
+    where
+        T: Arbitrary,
+        S: Arbitrary,
+    {
+        fn arbitrary(g: &mut Gen) -> Self {
+            match g.choose(&[0, 1, 2]) {
+                Some(0) => Self::Finished(T::arbitrary(g)),
+                Some(1) => Self::TimedOut,
+                _ => Self::Cancelled(S::arbitrary(g)),
+            }
+        }
+
+        fn shrink(&self) -> Shrunk<Self> {
+            match self {
+                Self::Finished(t) => Box::new(t.shrink().map(Self::Finished)),
+                Self::TimedOut => quickcheck::empty_shrinker(),
+                Self::Cancelled(s) => Box::new(s.shrink().map(Self::Cancelled)),
+            }
+        }
+    }
+
+    #[cfg(feature = "futures_01")]
+    impl<C, E> Arbitrary for CancellableError<C, E>
+    where
+        C: Arbitrary,
+        E: Arbitrary,
+    {
+        fn arbitrary(g: &mut Gen) -> Self {
+            match bool::arbitrary(g) {
+                true => Self::Cancelled(C::arbitrary(g)),
+                false => Self::Errored(E::arbitrary(g)),
+            }
+        }
+
+        fn shrink(&self) -> Shrunk<Self> {
+            match self {
+                Self::Cancelled(c) => Box::new(c.shrink().map(Self::Cancelled)),
+                Self::Errored(e) => Box::new(e.shrink().map(Self::Errored)),
+            }
+        }
+    }
+
+    impl<T> Arbitrary for Hedged<T>
+    where
+        T: Arbitrary,
+    {
+        fn arbitrary(g: &mut Gen) -> Self {
+            match bool::arbitrary(g) {
+                true => Self::Backup(T::arbitrary(g)),
+                false => Self::Primary(T::arbitrary(g)),
+            }
+        }
+
+        fn shrink(&self) -> Shrunk<Self> {
+            match self {
+                Self::Primary(t) => Box::new(t.shrink().map(Self::Primary)),
+                Self::Backup(t) => Box::new(t.shrink().map(Self::Backup)),
+            }
+        }
+    }
+
+    impl<T, S> Arbitrary for Escalated<T, S>
+    where
+        T: Arbitrary,
+        S: Arbitrary,
+    {
+        fn arbitrary(g: &mut Gen) -> Self {
+            match g.choose(&[0, 1, 2]) {
+                Some(0) => Self::Finished(T::arbitrary(g)),
+                Some(1) => Self::Soft(S::arbitrary(g), T::arbitrary(g)),
+                _ => Self::Hard(S::arbitrary(g)),
+            }
+        }
+
+        fn shrink(&self) -> Shrunk<Self> {
+            match self {
+                Self::Finished(t) => Box::new(t.shrink().map(Self::Finished)),
+                Self::Soft(s, t) => {
+                    let s = s.clone();
+                    Box::new(t.shrink().map(move |t| Self::Soft(s.clone(), t)))
+                }
+                Self::Hard(s) => Box::new(s.shrink().map(Self::Hard)),
+            }
+        }
+    }
+
+    impl Arbitrary for Stalled {
+        fn arbitrary(g: &mut Gen) -> Self { Self { idle: Arbitrary::arbitrary(g) } }
+
+        fn shrink(&self) -> Shrunk<Self> { Box::new(self.idle.shrink().map(|idle| Self { idle })) }
+    }
+
+    impl Arbitrary for Starved {
+        fn arbitrary(g: &mut Gen) -> Self { Self { interval: Arbitrary::arbitrary(g) } }
+
+        fn shrink(&self) -> Shrunk<Self> {
+            Box::new(self.interval.shrink().map(|interval| Self { interval }))
+        }
+    }
+}
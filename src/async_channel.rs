@@ -0,0 +1,65 @@
+//! Stoppers for `async-channel` channels
+//!
+//!A [`ReceiverStopper`](struct.ReceiverStopper.html) completes with the first message
+//! received, or with `None` once the channel is closed,
+//! and implements [`CancelSignal`](../fast_path/trait.CancelSignal.html)
+//! so the channel can be checked without polling it.
+//!
+//! # Example
+//! ```rust
+//! # use futures::{executor::block_on, future::pending};
+//!  use kyansel::async_channel::cancel_on_recv;
+//!
+//!  let (tx, rx) = async_channel::bounded::<()>(1);
+//!  let cancellable = cancel_on_recv(pending::<()>(), rx);
+//!
+//!  //closing the channel counts as a signal too
+//!  drop(tx);
+//!  assert_eq!(block_on(cancellable).cancelled(), Some(None));
+//! ```
+
+use super::{fast_path::CancelSignal, Cancellable, FutureCancellable};
+use async_channel::Receiver;
+use core::{
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll},
+};
+use futures_core::Stream;
+use pin_project_lite::pin_project;
+
+pin_project! {
+    ///Stopper completing with the first message of an `async-channel` channel,
+    /// or `None` when the channel is closed
+    #[derive(Debug)]
+    #[must_use = "futures do nothing unless you `.await` or poll them"]
+    pub struct ReceiverStopper<T> {
+        #[pin]
+        rx: Receiver<T>,
+    }
+}
+
+impl<T> ReceiverStopper<T> {
+    ///Create a stopper receiving from `rx`
+    pub fn new(rx: Receiver<T>) -> Self { Self { rx } }
+}
+
+impl<T> Future for ReceiverStopper<T> {
+    type Output = Option<T>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
+        self.project().rx.poll_next(cx)
+    }
+}
+
+impl<T> CancelSignal for ReceiverStopper<T> {
+    fn is_cancelled(&self) -> bool { !self.rx.is_empty() || self.rx.is_closed() }
+}
+
+///Cancel `fut` when a message is received from `rx`, or when the channel is closed
+pub fn cancel_on_recv<F, T>(fut: F, rx: Receiver<T>) -> Cancellable<F, ReceiverStopper<T>>
+where
+    F: Future,
+{
+    fut.cancel_with(ReceiverStopper::new(rx))
+}
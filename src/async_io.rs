@@ -0,0 +1,45 @@
+//! Time-based cancellation backed by `async_io::Timer`
+//!
+//! `async-io` drives the timers on its own thread, so these helpers work with smol,
+//! async-executor or any other executor, without committing to a full runtime.
+//!
+//! # Example
+//! ```rust
+//! # smol::block_on(async {
+//!  use kyansel::{async_io::cancel_after, FutureCancellable};
+//! #  use futures::future::pending;
+//! #  use std::time::Duration;
+//!
+//!  let result = cancel_after(pending::<()>(), Duration::from_millis(10)).await;
+//!
+//!  //the cancelled result holds the instant the timer fired at
+//!  assert!(result.is_cancelled());
+//!
+//!  //timers are plain stoppers as well
+//!  let timer = async_io::Timer::after(Duration::from_millis(10));
+//!  let result = smol::future::ready(42).cancel_with(timer).await;
+//!
+//!  assert_eq!(result.finished(), Some(42));
+//! # });
+//! ```
+
+use super::{Cancellable, FutureCancellable};
+use async_io::Timer;
+use core::future::Future;
+use std::time::{Duration, Instant};
+
+///Cancel `fut` if it hasn't completed after `duration`
+pub fn cancel_after<F>(fut: F, duration: Duration) -> Cancellable<F, Timer>
+where
+    F: Future,
+{
+    fut.cancel_with(Timer::after(duration))
+}
+
+///Cancel `fut` if it hasn't completed by `deadline`
+pub fn cancel_at<F>(fut: F, deadline: Instant) -> Cancellable<F, Timer>
+where
+    F: Future,
+{
+    fut.cancel_with(Timer::at(deadline))
+}
@@ -0,0 +1,84 @@
+//! Time-based cancellation on the async-std runtime
+//!
+//! # Example
+//! ```rust
+//! # async_std::task::block_on(async {
+//!  use kyansel::async_std::cancel_after;
+//! #  use futures::future::pending;
+//! #  use std::time::Duration;
+//!
+//!  let result = cancel_after(pending::<()>(), Duration::from_millis(10)).await;
+//!
+//!  assert!(result.is_cancelled());
+//! # });
+//! ```
+
+use super::{timer::Timer, Cancellable, FutureCancellable};
+use core::{
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll},
+};
+use std::boxed::Box;
+use std::time::{Duration, Instant};
+
+///Stopper completing after a delay, backed by `async_std::task::sleep`
+#[must_use = "futures do nothing unless you `.await` or poll them"]
+pub struct Sleep {
+    inner: Pin<Box<dyn Future<Output = ()> + Send>>,
+}
+
+impl Sleep {
+    ///Create a stopper completing after `duration`
+    pub fn new(duration: Duration) -> Self {
+        Self { inner: Box::pin(async_std::task::sleep(duration)) }
+    }
+
+    ///Create a stopper completing at `deadline`, or right away if it's already passed
+    pub fn until(deadline: Instant) -> Self {
+        Self::new(deadline.saturating_duration_since(Instant::now()))
+    }
+}
+
+impl Future for Sleep {
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
+        self.get_mut().inner.as_mut().poll(cx)
+    }
+}
+
+impl core::fmt::Debug for Sleep {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        f.debug_struct("Sleep").finish()
+    }
+}
+
+///[`Timer`](../timer/trait.Timer.html) backed by `async_std::task::sleep`
+#[derive(Debug, Default, Clone, Copy)]
+pub struct AsyncStdTimer;
+
+impl Timer for AsyncStdTimer {
+    type Instant = Instant;
+    type Sleep = Sleep;
+
+    fn sleep(&self, duration: Duration) -> Self::Sleep { Sleep::new(duration) }
+
+    fn sleep_until(&self, deadline: Self::Instant) -> Self::Sleep { Sleep::until(deadline) }
+}
+
+///Cancel `fut` if it hasn't completed after `duration`
+pub fn cancel_after<F>(fut: F, duration: Duration) -> Cancellable<F, Sleep>
+where
+    F: Future,
+{
+    fut.cancel_with(Sleep::new(duration))
+}
+
+///Cancel `fut` if it hasn't completed by `deadline`
+pub fn cancel_at<F>(fut: F, deadline: Instant) -> Cancellable<F, Sleep>
+where
+    F: Future,
+{
+    fut.cancel_with(Sleep::until(deadline))
+}
@@ -0,0 +1,30 @@
+//! Attribute macros
+//!
+//!Only available with the `macros` feature.
+//!They live in their own module since they would clash with the macros of the same name
+//! at the crate root, like [`cancellable!`](../macro.cancellable.html)
+
+///Make an `async fn` cancellable by a token, checking it after every `.await`
+///
+/// # Example
+/// ```rust
+/// # use futures::{executor::block_on, future::ready};
+///  use kyansel::{attr::cancellable, token::CancellationToken};
+///
+///  #[cancellable]
+///  async fn crunch(n: u64) -> u64 {
+///      let mut sum = 0;
+///      for i in 0..n {
+///          sum += ready(i).await;
+///      }
+///      sum
+///  }
+///
+///  assert_eq!(block_on(crunch(10, CancellationToken::new())).finished(), Some(45));
+///
+///  //every `.await` completes right away, the checkpoints stop the loop
+///  let token = CancellationToken::new();
+///  token.cancel();
+///  assert!(block_on(crunch(u64::MAX, token)).is_cancelled());
+/// ```
+pub use kyansel_macros::cancellable;
@@ -0,0 +1,179 @@
+//! Bounded concurrency where the newest work wins
+//!
+//!Thumbnailers, previewers and speculative fetchers only care about the latest requests:
+//! a [`BoundedPool`](struct.BoundedPool.html) holds up to a fixed number of in-flight futures,
+//! and submitting work beyond that cancels the oldest in-flight work to make room,
+//! or the one with the lowest priority when submitted with
+//! [`submit_with_priority`](struct.BoundedPool.html#method.submit_with_priority).
+//!
+//!The pool is a `Stream` of `(ticket, result)` pairs, with the ticket returned when the work was
+//! submitted, and evicted work shows up as
+//! [`CancellableResult::Cancelled`](../enum.CancellableResult.html#variant.Cancelled)
+//!
+//! # Example
+//! ```rust
+//! # use futures::{executor::block_on, future::{pending, ready, Either}, StreamExt};
+//!  use kyansel::{bounded::BoundedPool, CancellableResult};
+//!
+//!  let mut previews = BoundedPool::new(2);
+//!
+//!  //the user scrolls past three images
+//!  let first = previews.submit(Either::Left(pending()));
+//!  let second = previews.submit(Either::Right(ready("second")));
+//!  let third = previews.submit(Either::Right(ready("third")));
+//!
+//!  let mut results = block_on(previews.collect::<Vec<_>>());
+//!  results.sort_by_key(|(ticket, _)| *ticket);
+//!
+//!  assert_eq!(results, vec![
+//!      (first, CancellableResult::Cancelled(())),
+//!      (second, CancellableResult::Finished("second")),
+//!      (third, CancellableResult::Finished("third")),
+//!  ]);
+//! ```
+
+use super::{
+    tasks::{Task, Tasks},
+    CancellableResult,
+};
+use core::{
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll},
+};
+use futures_core::Stream;
+
+///Pool of up to a fixed number of in-flight futures, where new work evicts the oldest
+/// or lowest priority work
+#[must_use = "streams do nothing unless polled"]
+pub struct BoundedPool<F>
+where
+    F: Future,
+{
+    capacity: usize,
+    next_ticket: u64,
+    //keyed by priority then ticket, so the lowest is the one to evict
+    tasks: Tasks<(u32, u64), F>,
+}
+
+impl<F> BoundedPool<F>
+where
+    F: Future,
+{
+    ///Create an empty pool holding up to `capacity` in-flight futures
+    ///
+    /// # Panics
+    ///Panics if `capacity` is 0
+    pub fn new(capacity: usize) -> Self {
+        assert!(capacity > 0, "BoundedPool capacity must be at least 1");
+        Self { capacity, next_ticket: 0, tasks: Tasks::new() }
+    }
+
+    ///Submit work, evicting the oldest in-flight work if the pool is full
+    ///
+    ///Returns the ticket the result will be yielded with
+    pub fn submit(&mut self, fut: F) -> u64 { self.submit_with_priority(0, fut) }
+
+    ///Submit work with `priority`, evicting the in-flight work with the lowest priority
+    /// if the pool is full, the oldest one among equals
+    ///
+    ///The new work is always started, even if its priority is lower than the work evicted
+    ///
+    ///Returns the ticket the result will be yielded with
+    ///
+    /// # Example
+    /// ```rust
+    /// # use futures::{executor::block_on, future::{pending, ready, Either}, StreamExt};
+    ///  use kyansel::{bounded::BoundedPool, CancellableResult};
+    ///
+    ///  let mut fetches = BoundedPool::new(2);
+    ///
+    ///  let visible = fetches.submit_with_priority(10, Either::Right(ready("visible")));
+    ///  let speculative = fetches.submit_with_priority(1, Either::Left(pending()));
+    ///  let hovered = fetches.submit_with_priority(5, Either::Right(ready("hovered")));
+    ///
+    ///  let mut results = block_on(fetches.collect::<Vec<_>>());
+    ///  results.sort_by_key(|(ticket, _)| *ticket);
+    ///
+    ///  assert_eq!(results, vec![
+    ///      (visible, CancellableResult::Finished("visible")),
+    ///      (speculative, CancellableResult::Cancelled(())),
+    ///      (hovered, CancellableResult::Finished("hovered")),
+    ///  ]);
+    /// ```
+    pub fn submit_with_priority(&mut self, priority: u32, fut: F) -> u64 {
+        if self.in_flight() >= self.capacity {
+            let evicted =
+                self.tasks.iter().filter(|task| !task.is_cancelled()).min_by_key(|task| task.data);
+
+            if let Some(task) = evicted {
+                task.cancel();
+            }
+        }
+
+        let ticket = self.next_ticket;
+        self.next_ticket += 1;
+
+        self.tasks.push(Task::new((priority, ticket), fut));
+
+        ticket
+    }
+
+    ///Cancel the work submitted with `ticket`, returns false if it's not in the pool
+    pub fn cancel(&mut self, ticket: u64) -> bool {
+        match self.tasks.iter().find(|task| task.data.1 == ticket) {
+            Some(task) => {
+                task.cancel();
+                true
+            }
+            None => false,
+        }
+    }
+
+    ///Cancel all the in-flight work
+    pub fn cancel_all(&mut self) {
+        for task in self.tasks.iter() {
+            task.cancel();
+        }
+    }
+
+    ///Maximum number of in-flight futures
+    pub fn capacity(&self) -> usize { self.capacity }
+
+    ///Number of in-flight futures, not counting the evicted ones not yet yielded
+    pub fn in_flight(&self) -> usize {
+        self.tasks.iter().filter(|task| !task.is_cancelled()).count()
+    }
+
+    ///Number of futures in the pool, including the evicted ones not yet yielded
+    pub fn len(&self) -> usize { self.tasks.len() }
+
+    ///Check if the pool has no futures left
+    pub fn is_empty(&self) -> bool { self.tasks.is_empty() }
+}
+
+impl<F> Stream for BoundedPool<F>
+where
+    F: Future,
+{
+    type Item = (u64, CancellableResult<F::Output, ()>);
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+
+        this.tasks.poll_next(cx).map(|next| next.map(|((_, ticket), result)| (ticket, result)))
+    }
+}
+
+impl<F> core::fmt::Debug for BoundedPool<F>
+where
+    F: Future,
+{
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        f.debug_struct("BoundedPool")
+            .field("capacity", &self.capacity)
+            .field("in_flight", &self.in_flight())
+            .field("len", &self.len())
+            .finish()
+    }
+}
@@ -0,0 +1,176 @@
+//! Amortizing the cost of the stopper over many polls
+//!
+//!For futures in hot loops, where a cancellation taking effect a few wakeups late is acceptable,
+//! [`Cancellable::check_every`](../struct.Cancellable.html#method.check_every) only polls the
+//! stopper once every few polls of the inner future.
+//!
+//!The stopper still gets its own waker, so if it wakes the task while the inner future
+//! is idle it's polled right away, and the cancellation isn't delayed indefinitely.
+//!
+//! # Example
+//! ```rust
+//! # use futures::{executor::block_on, future::poll_fn};
+//!  use kyansel::FutureCancellable;
+//!  use std::{cell::Cell, task::Poll};
+//!
+//!  let (polls, stopper_polls) = (Cell::new(0), Cell::new(0));
+//!
+//!  //wakes itself 1000 times before finishing
+//!  let busy = poll_fn(|cx| {
+//!      polls.set(polls.get() + 1);
+//!      if polls.get() == 1000 {
+//!          return Poll::Ready(());
+//!      }
+//!      cx.waker().wake_by_ref();
+//!      Poll::Pending
+//!  });
+//!
+//!  let stopper = poll_fn(|_| {
+//!      stopper_polls.set(stopper_polls.get() + 1);
+//!      Poll::<()>::Pending
+//!  });
+//!
+//!  assert!(!block_on(busy.cancel_with(stopper).check_every(100)).is_cancelled());
+//!  assert_eq!(stopper_polls.get(), 10);
+//! ```
+
+use super::{wake::WakeFlag, Cancellable, CancellableResult};
+use core::{
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll, Waker},
+};
+use pin_project_lite::pin_project;
+use std::sync::Arc;
+
+pin_project! {
+    ///[`Cancellable`](../struct.Cancellable.html) polling its stopper once every few polls
+    ///
+    ///Created with [`Cancellable::check_every`](../struct.Cancellable.html#method.check_every)
+    #[must_use = "futures do nothing unless you `.await` or poll them"]
+    pub struct Budgeted<F, S>
+    where
+        F: Future,
+        S: Future,
+    {
+        #[pin]
+        cancellable: Cancellable<F, S>,
+        every: u32,
+        until_check: u32,
+        stopper_woken: Arc<WakeFlag>,
+        stopper_waker: Waker,
+    }
+}
+
+impl<F, S> Future for Budgeted<F, S>
+where
+    F: Future,
+    S: Future,
+{
+    type Output = CancellableResult<F::Output, S::Output>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
+        let this = self.project();
+        #[cfg(any(feature = "log", feature = "tracing"))]
+        let name = this.cancellable.name;
+        let projection = this.cancellable.project();
+
+        //always poll inner future first
+        if let Poll::Ready(t) = projection.inner.poll(cx) {
+            #[cfg(any(feature = "log", feature = "tracing"))]
+            super::trace_named(name, "finished");
+
+            return Poll::Ready(CancellableResult::Finished(t));
+        }
+
+        //the stopper keeps its forwarding waker, which must always wake the current task,
+        // even if the task moved since the stopper was last polled
+        this.stopper_woken.register(cx.waker());
+
+        if !this.stopper_woken.take() && *this.until_check > 0 {
+            *this.until_check -= 1;
+            return Poll::Pending;
+        }
+        *this.until_check = *this.every - 1;
+
+        match projection.stopper.poll(&mut Context::from_waker(this.stopper_waker)) {
+            Poll::Ready(s) => {
+                #[cfg(any(feature = "log", feature = "tracing"))]
+                super::trace_named(name, "cancelled");
+
+                Poll::Ready(CancellableResult::Cancelled(s))
+            }
+            Poll::Pending => Poll::Pending,
+        }
+    }
+}
+
+impl<F, S> core::fmt::Debug for Budgeted<F, S>
+where
+    F: Future + core::fmt::Debug,
+    S: Future + core::fmt::Debug,
+{
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        f.debug_struct("Budgeted")
+            .field("cancellable", &self.cancellable)
+            .field("every", &self.every)
+            .finish()
+    }
+}
+
+impl<F, S> Cancellable<F, S>
+where
+    F: Future,
+    S: Future,
+{
+    ///Poll the stopper only once every `polls` polls of the inner future,
+    /// or when the stopper wakes the task
+    ///
+    ///See the [`budget`](budget/index.html) module
+    ///
+    ///The stopper always wakes the waker of the latest poll, even between checks
+    ///
+    /// # Example
+    /// ```rust
+    /// # use futures::{channel::oneshot, future::pending, task::{noop_waker_ref, waker, ArcWake}};
+    ///  use kyansel::FutureCancellable;
+    ///  use std::{
+    ///      future::Future,
+    ///      sync::{atomic::{AtomicBool, Ordering}, Arc},
+    ///      task::Context,
+    ///  };
+    ///
+    ///  struct Flag(AtomicBool);
+    ///
+    ///  impl ArcWake for Flag {
+    ///      fn wake_by_ref(flag: &Arc<Self>) { flag.0.store(true, Ordering::SeqCst) }
+    ///  }
+    ///
+    ///  let (tx, rx) = oneshot::channel::<()>();
+    ///  let mut fut = Box::pin(pending::<()>().cancel_with(rx).check_every(100));
+    ///  assert!(fut.as_mut().poll(&mut Context::from_waker(noop_waker_ref())).is_pending());
+    ///
+    ///  //the task moved to another executor, before the stopper is due for a check
+    ///  let flag = Arc::new(Flag(AtomicBool::new(false)));
+    ///  let moved = waker(flag.clone());
+    ///  assert!(fut.as_mut().poll(&mut Context::from_waker(&moved)).is_pending());
+    ///
+    ///  tx.send(()).unwrap();
+    ///  assert!(flag.0.load(Ordering::SeqCst));
+    /// ```
+    ///
+    /// # Panics
+    ///Panics if `polls` is 0
+    pub fn check_every(self, polls: u32) -> Budgeted<F, S> {
+        assert!(polls > 0, "the stopper must be checked at least every poll");
+        let stopper_woken = Arc::new(WakeFlag::default());
+
+        Budgeted {
+            cancellable: self,
+            every: polls,
+            until_check: 0,
+            stopper_waker: Waker::from(stopper_woken.clone()),
+            stopper_woken,
+        }
+    }
+}
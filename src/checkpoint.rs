@@ -0,0 +1,208 @@
+//! Cooperative checkpoints for CPU-heavy futures
+//!
+//!A future is only cancelled between polls, so one that crunches numbers for seconds
+//! without reaching an `.await` that returns `Pending` can't be cancelled until it's done.
+//!
+//![`cancel_with_checkpoints`](fn.cancel_with_checkpoints.html) hands the future a
+//! [`Checkpoint`](struct.Checkpoint.html) to await in its hot loops:
+//! awaiting it is free until the future has been running for the configured time,
+//! at which point it yields once, letting the stopper be checked and other tasks run.
+//!
+//!Only continuous running time counts: the budget restarts every time the future is polled,
+//! so time spent waiting on other `.await`s doesn't make the next checkpoint yield.
+//!
+//! # Example
+//! ```rust
+//! # use futures::{executor::block_on, future::ready};
+//!  use kyansel::checkpoint::cancel_with_checkpoints;
+//! #  use std::time::Duration;
+//!
+//!  let crunch = |mut checkpoint: kyansel::checkpoint::Checkpoint| async move {
+//!      let mut sum = 0u64;
+//!      for i in 0.. {
+//!          sum = sum.wrapping_add(i);
+//!          checkpoint.check().await;
+//!      }
+//!      sum
+//!  };
+//!
+//!  //the loop never ends, but it yields every millisecond
+//!  let result = cancel_with_checkpoints(crunch, ready(()), Duration::from_millis(1));
+//!
+//!  assert!(block_on(result).is_cancelled());
+//! ```
+
+use super::{Cancellable, FutureCancellable};
+use core::{
+    future::Future,
+    pin::Pin,
+    sync::atomic::{AtomicU64, Ordering},
+    task::{Context, Poll},
+    time::Duration,
+};
+use pin_project_lite::pin_project;
+use std::{sync::Arc, time::Instant};
+
+//when the running time started, as nanoseconds since `origin`
+#[derive(Debug)]
+struct Clock {
+    origin: Instant,
+    resumed: AtomicU64,
+}
+
+impl Clock {
+    fn restart(&self) {
+        let now = self.origin.elapsed().as_nanos().min(u64::MAX as u128) as u64;
+        self.resumed.store(now, Ordering::Relaxed);
+    }
+
+    fn running(&self) -> Duration {
+        let resumed = Duration::from_nanos(self.resumed.load(Ordering::Relaxed));
+        self.origin.elapsed().saturating_sub(resumed)
+    }
+}
+
+///Budget of continuous running time, checked cooperatively
+///
+///Created by [`cancel_with_checkpoints`](fn.cancel_with_checkpoints.html),
+/// or with [`Checkpoint::new`](struct.Checkpoint.html#method.new) to yield periodically
+/// under any other cancellation mechanism.
+///
+///Clones share the same budget
+#[derive(Debug, Clone)]
+pub struct Checkpoint {
+    every: Duration,
+    clock: Arc<Clock>,
+}
+
+impl Checkpoint {
+    ///Create a checkpoint yielding after `every` of continuous running time
+    ///
+    ///The budget only restarts when the checkpoint yields, unless the future awaiting it
+    /// is wrapped with [`resuming`](struct.Checkpoint.html#method.resuming)
+    pub fn new(every: Duration) -> Self {
+        let clock = Clock { origin: Instant::now(), resumed: AtomicU64::new(0) };
+        Self { every, clock: Arc::new(clock) }
+    }
+
+    ///Wrap `fut`, restarting the budget every time it's polled,
+    /// so time spent waiting on something else doesn't count
+    ///
+    ///[`cancel_with_checkpoints`](fn.cancel_with_checkpoints.html) does this already
+    pub fn resuming<F>(&self, fut: F) -> Resuming<F> {
+        Resuming { inner: fut, clock: self.clock.clone() }
+    }
+
+    ///Retrieve a future yielding once if the budget ran out, and completing right away otherwise
+    pub fn check(&mut self) -> Check<'_> { Check { checkpoint: self, yielded: false } }
+}
+
+///Future returned by [`Checkpoint::check`](struct.Checkpoint.html#method.check)
+#[derive(Debug)]
+#[must_use = "futures do nothing unless you `.await` or poll them"]
+pub struct Check<'a> {
+    checkpoint: &'a mut Checkpoint,
+    yielded: bool,
+}
+
+impl Future for Check<'_> {
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
+        let this = self.get_mut();
+
+        if this.yielded {
+            //the budget restarts once we're polled again
+            this.checkpoint.clock.restart();
+            return Poll::Ready(());
+        }
+
+        if this.checkpoint.clock.running() < this.checkpoint.every {
+            return Poll::Ready(());
+        }
+
+        this.yielded = true;
+        cx.waker().wake_by_ref();
+        Poll::Pending
+    }
+}
+
+pin_project! {
+    ///Future for the [`Checkpoint::resuming`](struct.Checkpoint.html#method.resuming) combinator
+    #[must_use = "futures do nothing unless you `.await` or poll them"]
+    pub struct Resuming<F> {
+        #[pin]
+        inner: F,
+        clock: Arc<Clock>,
+    }
+}
+
+impl<F> Future for Resuming<F>
+where
+    F: Future,
+{
+    type Output = F::Output;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
+        let this = self.project();
+
+        this.clock.restart();
+        this.inner.poll(cx)
+    }
+}
+
+impl<F> core::fmt::Debug for Resuming<F> {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        f.debug_struct("Resuming").field("running", &self.clock.running()).finish()
+    }
+}
+
+///Cancel the future created by `make_fut` if `stopper` completes,
+/// handing it a [`Checkpoint`](struct.Checkpoint.html) yielding after `every` of running time
+///
+///The future is only cancelled if it awaits the checkpoint often enough.
+///The budget restarts every time the future is polled
+///
+/// # Example
+/// ```rust
+/// # use futures::{channel::oneshot, executor::block_on, future::poll_fn};
+///  use kyansel::checkpoint::{cancel_with_checkpoints, Checkpoint};
+///  use std::{cell::Cell, task::Poll, thread, time::Duration};
+///
+///  let stopper_polls = Cell::new(0);
+///  let stopper = poll_fn(|_| {
+///      stopper_polls.set(stopper_polls.get() + 1);
+///      Poll::<()>::Pending
+///  });
+///
+///  let work = |mut checkpoint: Checkpoint| async move {
+///      let (tx, rx) = oneshot::channel();
+///      thread::spawn(move || {
+///          thread::sleep(Duration::from_millis(20));
+///          tx.send(42)
+///      });
+///      let reply = rx.await.unwrap();
+///
+///      //the wait isn't running time, so this doesn't yield
+///      checkpoint.check().await;
+///      reply
+///  };
+///
+///  let result = block_on(cancel_with_checkpoints(work, stopper, Duration::from_millis(10)));
+///  assert_eq!(result.finished(), Some(42));
+///  //only checked while waiting for the reply
+///  assert_eq!(stopper_polls.get(), 1);
+/// ```
+pub fn cancel_with_checkpoints<Mk, F, S>(
+    make_fut: Mk,
+    stopper: S,
+    every: Duration,
+) -> Cancellable<Resuming<F>, S>
+where
+    Mk: FnOnce(Checkpoint) -> F,
+    F: Future,
+    S: Future,
+{
+    let checkpoint = Checkpoint::new(every);
+    checkpoint.resuming(make_fut(checkpoint.clone())).cancel_with(stopper)
+}
@@ -0,0 +1,209 @@
+//! Emulating async drop
+//!
+//!Rust has no async drop: a future dropped halfway can't await anything to release what it
+//! holds, like closing a remote session or rolling back a transaction.
+//![`with_async_cleanup`](fn.with_async_cleanup.html) pairs a future with a cleanup future,
+//! handed to a spawn hook whenever the future is dropped before completing: when a
+//! [`Cancellable`](../struct.Cancellable.html) stopper fires, but also on a `select!` losing
+//! branch, a dropped task or a timeout of any other library.
+//!
+//!The cleanup is discarded without running if the future completes.
+//![`with_lazy_cleanup`](fn.with_lazy_cleanup.html) takes a closure building the cleanup instead,
+//! so it's only built when needed, and an async closure works too:
+//! `async || session.close().await`.
+//!
+//! # Example
+//! ```rust
+//! # use futures::{executor::block_on, future::{pending, ready}};
+//!  use kyansel::{cleanup::with_async_cleanup, FutureCancellable};
+//!  use std::cell::Cell;
+//!
+//!  let rolled_back = Cell::new(false);
+//!
+//!  let transaction = with_async_cleanup(
+//!      pending::<()>(),
+//!      async { rolled_back.set(true) },
+//!      //hand it to the executor, like with `tokio::spawn`
+//!      |cleanup| block_on(cleanup),
+//!  );
+//!
+//!  assert!(block_on(transaction.cancel_with(ready(()))).is_cancelled());
+//!  assert!(rolled_back.get());
+//! ```
+
+use core::{
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll},
+};
+use pin_project_lite::pin_project;
+
+///Source of the cleanup future of [`WithAsyncCleanup`](struct.WithAsyncCleanup.html)
+///
+///Every future resolving to `()` is its own cleanup,
+/// while [`LazyCleanup`](struct.LazyCleanup.html) builds it only when needed
+pub trait IntoCleanup {
+    ///The cleanup future handed to the spawn hook
+    type Cleanup: Future<Output = ()>;
+
+    ///Retrieve the cleanup future
+    fn into_cleanup(self) -> Self::Cleanup;
+}
+
+impl<C> IntoCleanup for C
+where
+    C: Future<Output = ()>,
+{
+    type Cleanup = C;
+
+    fn into_cleanup(self) -> Self::Cleanup { self }
+}
+
+///Cleanup built by a closure, created by [`with_lazy_cleanup`](fn.with_lazy_cleanup.html)
+#[derive(Debug, Clone, Copy)]
+pub struct LazyCleanup<Mk>(Mk);
+
+impl<Mk, C> IntoCleanup for LazyCleanup<Mk>
+where
+    Mk: FnOnce() -> C,
+    C: Future<Output = ()>,
+{
+    type Cleanup = C;
+
+    fn into_cleanup(self) -> Self::Cleanup { (self.0)() }
+}
+
+pin_project! {
+    ///Future for the [`with_async_cleanup`](fn.with_async_cleanup.html)
+    /// and [`with_lazy_cleanup`](fn.with_lazy_cleanup.html) combinators
+    ///
+    ///If dropped before completing, the cleanup future is handed to the spawn hook
+    #[must_use = "futures do nothing unless you `.await` or poll them"]
+    pub struct WithAsyncCleanup<F, C, Sp>
+    where
+        C: IntoCleanup,
+        Sp: FnOnce(C::Cleanup),
+    {
+        #[pin]
+        inner: F,
+        cleanup: Option<(C, Sp)>,
+    }
+
+    impl<F, C, Sp> PinnedDrop for WithAsyncCleanup<F, C, Sp>
+    where
+        C: IntoCleanup,
+        Sp: FnOnce(C::Cleanup),
+    {
+        fn drop(this: Pin<&mut Self>) {
+            if let Some((cleanup, spawn)) = this.project().cleanup.take() {
+                spawn(cleanup.into_cleanup());
+            }
+        }
+    }
+}
+
+impl<F, C, Sp> WithAsyncCleanup<F, C, Sp>
+where
+    C: IntoCleanup,
+    Sp: FnOnce(C::Cleanup),
+{
+    ///Discard the cleanup, so it won't run even if the future is dropped before completing
+    pub fn disarm(self: Pin<&mut Self>) {
+        *self.project().cleanup = None;
+    }
+
+    ///Check if the cleanup would run if the future was dropped now
+    pub fn is_armed(&self) -> bool { self.cleanup.is_some() }
+}
+
+impl<F, C, Sp> Future for WithAsyncCleanup<F, C, Sp>
+where
+    F: Future,
+    C: IntoCleanup,
+    Sp: FnOnce(C::Cleanup),
+{
+    type Output = F::Output;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
+        let this = self.project();
+
+        match this.inner.poll(cx) {
+            Poll::Ready(t) => {
+                *this.cleanup = None;
+                Poll::Ready(t)
+            }
+            Poll::Pending => Poll::Pending,
+        }
+    }
+}
+
+impl<F, C, Sp> core::fmt::Debug for WithAsyncCleanup<F, C, Sp>
+where
+    F: core::fmt::Debug,
+    C: IntoCleanup,
+    Sp: FnOnce(C::Cleanup),
+{
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        f.debug_struct("WithAsyncCleanup")
+            .field("inner", &self.inner)
+            .field("armed", &self.is_armed())
+            .finish()
+    }
+}
+
+///Run `fut`, handing `cleanup` to `spawn` if it's dropped before completing
+///
+///See the [`cleanup`](index.html) module
+pub fn with_async_cleanup<F, C, Sp>(fut: F, cleanup: C, spawn: Sp) -> WithAsyncCleanup<F, C, Sp>
+where
+    F: Future,
+    C: Future<Output = ()>,
+    Sp: FnOnce(C),
+{
+    WithAsyncCleanup { inner: fut, cleanup: Some((cleanup, spawn)) }
+}
+
+///Run `fut`, building the cleanup with `make_cleanup` and handing it to `spawn`
+/// if it's dropped before completing
+///
+///Like [`with_async_cleanup`](fn.with_async_cleanup.html), but nothing is built
+/// unless the cleanup runs
+///
+/// # Example
+/// ```rust
+/// # use futures::{executor::block_on, future::{pending, ready}};
+///  use kyansel::{cleanup::with_lazy_cleanup, FutureCancellable};
+///  use std::cell::Cell;
+///
+///  let closed = Cell::new(0);
+///
+///  let session = with_lazy_cleanup(
+///      pending::<()>(),
+///      async || closed.set(closed.get() + 1),
+///      |cleanup| block_on(cleanup),
+///  );
+///  assert!(block_on(session.cancel_with(ready(()))).is_cancelled());
+///
+///  //the cleanup isn't even built when the future completes
+///  let session = with_lazy_cleanup(
+///      ready(42),
+///      async || unreachable!(),
+///      |cleanup| block_on(cleanup),
+///  );
+///  assert_eq!(block_on(session.cancel_with(pending::<()>())).finished(), Some(42));
+///
+///  assert_eq!(closed.get(), 1);
+/// ```
+pub fn with_lazy_cleanup<F, Mk, C, Sp>(
+    fut: F,
+    make_cleanup: Mk,
+    spawn: Sp,
+) -> WithAsyncCleanup<F, LazyCleanup<Mk>, Sp>
+where
+    F: Future,
+    Mk: FnOnce() -> C,
+    C: Future<Output = ()>,
+    Sp: FnOnce(C),
+{
+    WithAsyncCleanup { inner: fut, cleanup: Some((LazyCleanup(make_cleanup), spawn)) }
+}
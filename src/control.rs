@@ -0,0 +1,273 @@
+//! Cancelling jobs remotely over a control socket
+//!
+//!A [`KillSwitch`](struct.KillSwitch.html) maps names to
+//! [`CancellationToken`](../token/struct.CancellationToken.html)s, and listens on a TCP or Unix
+//! socket for commands cancelling them, so operators can stop a long running job in production
+//! without restarting the process.
+//!
+//!The protocol is line based, each command getting a one line reply:
+//! - `cancel <name>` cancels the token registered as `name`, replying `ok`, or `unknown` if there's
+//!   no such token
+//! - `auth <credential>` authenticates the connection, replying `ok` or `denied`
+//!
+//!With an [`authenticate`](struct.KillSwitch.html#method.authenticate) hook, `cancel` is
+//! `denied` until the connection authenticated. Without one anybody able to connect can cancel
+//! the jobs, so the socket should only be reachable by the operators
+//!
+//!The listener runs on its own thread, and each connection is handled on a new thread
+//!
+//! # Example
+//! ```rust
+//!  use kyansel::{control::KillSwitch, token::CancellationToken};
+//!  use std::{io::{BufRead, BufReader, Write}, net::TcpStream};
+//!
+//!  let switch = KillSwitch::new().authenticate(|credential| credential == "hunter2");
+//!  let listener = switch.listen_tcp("127.0.0.1:0").unwrap();
+//!
+//!  let token = CancellationToken::new();
+//!  let _registration = switch.register("reindex", &token);
+//!
+//!  //what an operator would do, with `nc` for example
+//!  let mut stream = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+//!  let mut replies = BufReader::new(stream.try_clone().unwrap()).lines();
+//!
+//!  stream.write_all(b"cancel reindex\n").unwrap();
+//!  assert_eq!(replies.next().unwrap().unwrap(), "denied");
+//!
+//!  stream.write_all(b"auth hunter2\ncancel reindex\n").unwrap();
+//!  assert_eq!(replies.next().unwrap().unwrap(), "ok");
+//!  assert_eq!(replies.next().unwrap().unwrap(), "ok");
+//!
+//!  assert!(token.is_cancelled());
+//! ```
+
+use super::token::CancellationToken;
+use core::sync::atomic::{AtomicBool, Ordering};
+use std::{
+    collections::HashMap,
+    io::{self, BufRead, BufReader, Read, Write},
+    net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs},
+    string::String,
+    sync::{Arc, Mutex},
+    thread,
+};
+#[cfg(unix)]
+use std::{
+    os::unix::net::{UnixListener, UnixStream},
+    path::{Path, PathBuf},
+};
+
+type Authenticate = Arc<dyn Fn(&str) -> bool + Send + Sync>;
+
+#[derive(Default)]
+struct Shared {
+    tokens: Mutex<HashMap<String, CancellationToken>>,
+    authenticate: Mutex<Option<Authenticate>>,
+}
+
+///Registry of named tokens, cancelled by the commands received on its control sockets
+///
+///The switch is cheap to clone, all clones share the same tokens
+#[derive(Clone, Default)]
+pub struct KillSwitch {
+    shared: Arc<Shared>,
+}
+
+impl KillSwitch {
+    ///Create a switch without tokens, accepting commands from any connection
+    pub fn new() -> Self { Self::default() }
+
+    ///Require the connections to authenticate with a credential accepted by `hook`
+    ///
+    ///The connections already open are not affected
+    pub fn authenticate<A>(self, hook: A) -> Self
+    where
+        A: Fn(&str) -> bool + Send + Sync + 'static,
+    {
+        *self.shared.authenticate.lock().unwrap() = Some(Arc::new(hook));
+        self
+    }
+
+    ///Register `token` as `name`, replacing the token previously registered with the same name
+    ///
+    ///The token is unregistered when the returned [`Registration`](struct.Registration.html)
+    /// is dropped
+    pub fn register(&self, name: impl Into<String>, token: &CancellationToken) -> Registration {
+        let name = name.into();
+        self.shared.tokens.lock().unwrap().insert(name.clone(), token.clone());
+
+        Registration { shared: self.shared.clone(), name, token: token.clone() }
+    }
+
+    ///Cancel the token registered as `name`, returns if there was one
+    pub fn cancel(&self, name: &str) -> bool {
+        match self.shared.tokens.lock().unwrap().get(name) {
+            Some(token) => {
+                token.cancel();
+                true
+            }
+            None => false,
+        }
+    }
+
+    ///Listen for commands on a TCP socket bound to `addr`
+    pub fn listen_tcp(&self, addr: impl ToSocketAddrs) -> io::Result<ControlListener> {
+        let listener = TcpListener::bind(addr)?;
+        let addr = listener.local_addr()?;
+        let stop = Arc::new(AtomicBool::new(false));
+
+        let switch = self.clone();
+        let stopped = stop.clone();
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                if stopped.load(Ordering::Acquire) {
+                    break;
+                }
+                if let Ok(stream) = stream {
+                    let switch = switch.clone();
+                    thread::spawn(move || handle(&switch, &stream));
+                }
+            }
+        });
+
+        Ok(ControlListener { stop, addr: Addr::Tcp(addr) })
+    }
+
+    ///Listen for commands on a Unix socket bound to `path`
+    ///
+    ///The socket file is not removed when the listener is closed
+    #[cfg(unix)]
+    pub fn listen_unix(&self, path: impl AsRef<Path>) -> io::Result<ControlListener> {
+        let listener = UnixListener::bind(path.as_ref())?;
+        let stop = Arc::new(AtomicBool::new(false));
+
+        let switch = self.clone();
+        let stopped = stop.clone();
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                if stopped.load(Ordering::Acquire) {
+                    break;
+                }
+                if let Ok(stream) = stream {
+                    let switch = switch.clone();
+                    thread::spawn(move || handle(&switch, &stream));
+                }
+            }
+        });
+
+        Ok(ControlListener { stop, addr: Addr::Unix(path.as_ref().to_path_buf()) })
+    }
+}
+
+impl core::fmt::Debug for KillSwitch {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        let tokens = self.shared.tokens.lock().unwrap();
+        f.debug_struct("KillSwitch")
+            .field("tokens", &tokens.keys().collect::<std::vec::Vec<_>>())
+            .field("authenticated", &self.shared.authenticate.lock().unwrap().is_some())
+            .finish()
+    }
+}
+
+//serve the commands of a connection until it's closed
+fn handle<'a, S>(switch: &KillSwitch, stream: &'a S)
+where
+    &'a S: Read + Write,
+{
+    let authenticate = switch.shared.authenticate.lock().unwrap().clone();
+    let mut authenticated = authenticate.is_none();
+    let mut writer = stream;
+
+    for line in BufReader::new(stream).lines() {
+        let line = match line {
+            Ok(line) => line,
+            Err(_) => return,
+        };
+
+        let reply = match line.trim().split_once(' ') {
+            Some(("auth", credential)) => {
+                authenticated = authenticate.as_ref().is_none_or(|hook| hook(credential));
+                if authenticated { "ok" } else { "denied" }
+            }
+            Some(("cancel", _)) if !authenticated => "denied",
+            Some(("cancel", name)) if switch.cancel(name) => "ok",
+            Some(("cancel", _)) => "unknown",
+            _ => "error: unknown command",
+        };
+
+        if writeln!(writer, "{}", reply).is_err() {
+            return;
+        }
+    }
+}
+
+///Token registered on a [`KillSwitch`](struct.KillSwitch.html), unregistered when dropped
+#[must_use = "the token is unregistered when the registration is dropped"]
+pub struct Registration {
+    shared: Arc<Shared>,
+    name: String,
+    token: CancellationToken,
+}
+
+impl Registration {
+    ///Retrieve the name the token is registered as
+    pub fn name(&self) -> &str { &self.name }
+}
+
+impl Drop for Registration {
+    fn drop(&mut self) {
+        let mut tokens = self.shared.tokens.lock().unwrap();
+        //the name might have been registered again since
+        if tokens.get(&self.name).is_some_and(|token| token.same_as(&self.token)) {
+            tokens.remove(&self.name);
+        }
+    }
+}
+
+impl core::fmt::Debug for Registration {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        f.debug_struct("Registration").field("name", &self.name).finish()
+    }
+}
+
+#[derive(Debug)]
+enum Addr {
+    Tcp(SocketAddr),
+    #[cfg(unix)]
+    Unix(PathBuf),
+}
+
+///Control socket started by [`KillSwitch::listen_tcp`](struct.KillSwitch.html#method.listen_tcp)
+/// or [`KillSwitch::listen_unix`](struct.KillSwitch.html#method.listen_unix)
+///
+///The socket stops accepting connections when the listener is dropped,
+/// the connections already open are still served
+#[derive(Debug)]
+pub struct ControlListener {
+    stop: Arc<AtomicBool>,
+    addr: Addr,
+}
+
+impl ControlListener {
+    ///Retrieve the address of a TCP socket, `None` for Unix sockets
+    pub fn local_addr(&self) -> Option<SocketAddr> {
+        match &self.addr {
+            Addr::Tcp(addr) => Some(*addr),
+            #[cfg(unix)]
+            Addr::Unix(_) => None,
+        }
+    }
+}
+
+impl Drop for ControlListener {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Release);
+
+        //wake the thread blocked accepting connections, so it sees it has to stop
+        let _ = match &self.addr {
+            Addr::Tcp(addr) => TcpStream::connect(addr).map(drop),
+            #[cfg(unix)]
+            Addr::Unix(path) => UnixStream::connect(path).map(drop),
+        };
+    }
+}
@@ -0,0 +1,88 @@
+//! Cancelling a token on Ctrl-C, with `ctrlc`
+//!
+//!Programs whose main loop is synchronous, running cancellable async work on the side,
+//! don't have a runtime to await a signal on.
+//![`cancel_on_ctrlc`](fn.cancel_on_ctrlc.html) registers a `ctrlc` handler cancelling a
+//! [`CancellationToken`](../token/struct.CancellationToken.html), which the main loop can check
+//! with `is_cancelled` and the async work can await.
+//!
+//![`run_main`](fn.run_main.html) is the entry point for async programs without a runtime of their
+//! own: it blocks on the program's root future, cancelling it on Ctrl-C or SIGTERM.
+//!
+//!`ctrlc` allows a single handler per process, so only one of these can be called, once
+//!
+//! # Example
+//! ```rust,no_run
+//!  use kyansel::{ctrlc::cancel_on_ctrlc, token::CancellationToken, FutureCancellable};
+//! #  use futures::future::pending;
+//!
+//!  let token = CancellationToken::new();
+//!  cancel_on_ctrlc(&token).unwrap();
+//!
+//!  let background = std::thread::spawn({
+//!      let token = token.clone();
+//!      move || futures::executor::block_on(pending::<()>().cancel_with(token.cancelled()))
+//!  });
+//!
+//!  while !token.is_cancelled() {
+//!      //synchronous work
+//! #    std::thread::sleep(std::time::Duration::from_millis(10));
+//!  }
+//!
+//!  assert!(background.join().unwrap().is_cancelled());
+//! ```
+
+use super::{sync::cancel_block_on, token::CancellationToken, CancellableResult};
+use core::future::Future;
+
+pub use ctrlc::Error;
+
+///Cancel `token` when Ctrl-C is pressed
+///
+///On Unix this handles SIGINT, SIGTERM and SIGHUP
+///
+/// # Errors
+///Fails if a handler was already registered, by this function or with `ctrlc` directly,
+/// or if the handler could not be registered
+pub fn cancel_on_ctrlc(token: &CancellationToken) -> Result<(), Error> {
+    let token = token.clone();
+    ctrlc::set_handler(move || token.cancel())
+}
+
+///Run the root future of the program on the current thread, cancelling it on Ctrl-C
+///
+///`make_fut` is given the root token of the program, cancelled on Ctrl-C right before the
+/// future is, so the work spawned elsewhere can be tied to it.
+///`CancellableResult` can be returned from `main`, exiting with code 130 when cancelled
+///
+/// # Example
+/// ```rust,no_run
+///  use kyansel::{ctrlc::{run_main, Error}, CancellableResult};
+/// #  use futures::future::pending;
+///
+///  fn main() -> Result<CancellableResult<(), ()>, Error> {
+///      run_main(|_token| async {
+///          //the program
+/// #        pending::<()>().await
+///      })
+///  }
+/// ```
+///
+/// # Errors
+///Fails if the Ctrl-C handler could not be registered, like
+/// [`cancel_on_ctrlc`](fn.cancel_on_ctrlc.html)
+pub fn run_main<Mk, F>(make_fut: Mk) -> Result<CancellableResult<F::Output, ()>, Error>
+where
+    Mk: FnOnce(CancellationToken) -> F,
+    F: Future,
+{
+    let token = CancellationToken::new();
+    let (handle, blocking) = cancel_block_on(make_fut(token.clone()));
+
+    ctrlc::set_handler(move || {
+        token.cancel();
+        handle.cancel();
+    })?;
+
+    Ok(blocking.wait())
+}
@@ -0,0 +1,275 @@
+//! Stop waiting for a future without stopping its work
+//!
+//![`Cancellable::detach`](../struct.Cancellable.html#method.detach) resolves as soon as the
+//! stopper completes, like a plain [`Cancellable`](../struct.Cancellable.html), but instead of
+//! dropping the inner future it hands it back as a [`Detached`](struct.Detached.html) future,
+//! to be spawned on any runtime and run to completion in the background.
+//!
+//![`Detached::on_complete`](struct.Detached.html#method.on_complete) attaches a callback
+//! notified with the output of the work once it's done.
+//!
+//![`Cancellable::linger`](../struct.Cancellable.html#method.linger) bounds the work left in
+//! the background: the inner future is handed to a spawner racing a delay, and is dropped
+//! if it doesn't complete within it, so nearly-finished work isn't wasted
+//! but abandoned work doesn't pile up.
+//!
+//! # Example
+//! ```rust
+//! # #[tokio::main]
+//! # async fn main() {
+//! #    use futures::future::ready;
+//!  use kyansel::{map::CancellableMapExt, FutureCancellable};
+//! #    use std::time::Duration;
+//!  use tokio::{sync::oneshot, time::sleep};
+//!
+//!  let (tx, rx) = oneshot::channel();
+//!  let upload = async {
+//!      sleep(Duration::from_millis(10)).await;
+//!      "uploaded"
+//!  };
+//!
+//!  //the caller stops waiting, the upload keeps going in the background
+//!  let result = upload
+//!      .cancel_with(ready("caller went away"))
+//!      .detach()
+//!      .map_cancelled(|(reason, work)| {
+//!          tokio::spawn(work.on_complete(move |done: &&str| tx.send(*done).unwrap()));
+//!          reason
+//!      })
+//!      .await;
+//!
+//!  assert_eq!(result.cancelled(), Some("caller went away"));
+//!  assert_eq!(rx.await.unwrap(), "uploaded");
+//! # }
+//! ```
+//!
+//! ```rust
+//! # use futures::{executor::block_on, future::{pending, ready}};
+//!  use kyansel::{timer::ManualTimer, FutureCancellable};
+//! #  use std::time::Duration;
+//!
+//!  let timer = ManualTimer::new();
+//!  let mut background = None;
+//!
+//!  //the spawner of any runtime goes here
+//!  let result = pending::<()>()
+//!      .cancel_with(ready("caller went away"))
+//!      .linger(timer.clone(), Duration::from_secs(5), |work| background = Some(work));
+//!
+//!  assert_eq!(block_on(result).cancelled(), Some("caller went away"));
+//!
+//!  //the work never completes, so it's dropped once it lingered for 5 seconds
+//!  timer.advance(Duration::from_secs(5));
+//!  assert!(block_on(background.unwrap()).is_cancelled());
+//! ```
+
+use super::{timer::Timer, Cancellable, CancellableResult, FutureCancellable};
+use core::{
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll},
+    time::Duration,
+};
+use pin_project_lite::pin_project;
+use std::boxed::Box;
+
+pin_project! {
+    ///[`Cancellable`](../struct.Cancellable.html) handing back its inner future when cancelled
+    ///
+    ///Created with [`Cancellable::detach`](../struct.Cancellable.html#method.detach)
+    #[must_use = "futures do nothing unless you `.await` or poll them"]
+    pub struct Detach<F, S> {
+        //boxed, so it can be handed back after being polled
+        inner: Option<Pin<Box<F>>>,
+        #[pin]
+        stopper: S,
+        name: Option<&'static str>,
+    }
+}
+
+impl<F, S> Future for Detach<F, S>
+where
+    F: Future,
+    S: Future,
+{
+    type Output = CancellableResult<F::Output, (S::Output, Detached<F>)>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
+        let this = self.project();
+        let inner = this.inner.as_mut().expect("Detach polled after completion");
+
+        //always poll inner future first
+        if let Poll::Ready(t) = inner.as_mut().poll(cx) {
+            #[cfg(any(feature = "log", feature = "tracing"))]
+            super::trace_named(*this.name, "finished");
+
+            *this.inner = None;
+            return Poll::Ready(CancellableResult::Finished(t));
+        }
+
+        match this.stopper.poll(cx) {
+            Poll::Ready(s) => {
+                #[cfg(any(feature = "log", feature = "tracing"))]
+                super::trace_named(*this.name, "cancelled");
+
+                let inner = this.inner.take().expect("inner future already detached");
+                let detached = Detached { inner, on_complete: None };
+                Poll::Ready(CancellableResult::Cancelled((s, detached)))
+            }
+            Poll::Pending => Poll::Pending,
+        }
+    }
+}
+
+impl<F, S> core::fmt::Debug for Detach<F, S> {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        f.debug_struct("Detach").field("name", &self.name).finish()
+    }
+}
+
+pin_project! {
+    ///Inner future of a cancelled [`Detach`](struct.Detach.html), to be run to completion
+    ///
+    ///It's a plain future resolving to the output of the work,
+    /// which can be spawned on any runtime
+    #[must_use = "futures do nothing unless you `.await` or poll them"]
+    pub struct Detached<F, N = fn(&<F as Future>::Output)>
+    where
+        F: Future,
+    {
+        inner: Pin<Box<F>>,
+        on_complete: Option<N>,
+    }
+}
+
+impl<F, N> Detached<F, N>
+where
+    F: Future,
+{
+    ///Invoke `hook` with the output of the work once it completes
+    pub fn on_complete<N2>(self, hook: N2) -> Detached<F, N2>
+    where
+        N2: FnOnce(&F::Output),
+    {
+        Detached { inner: self.inner, on_complete: Some(hook) }
+    }
+}
+
+impl<F, N> Future for Detached<F, N>
+where
+    F: Future,
+    N: FnOnce(&F::Output),
+{
+    type Output = F::Output;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
+        let this = self.project();
+
+        let t = match this.inner.as_mut().poll(cx) {
+            Poll::Ready(t) => t,
+            Poll::Pending => return Poll::Pending,
+        };
+
+        if let Some(on_complete) = this.on_complete.take() {
+            on_complete(&t);
+        }
+        Poll::Ready(t)
+    }
+}
+
+impl<F, N> core::fmt::Debug for Detached<F, N>
+where
+    F: Future,
+{
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        f.debug_struct("Detached").finish()
+    }
+}
+
+///Inner future of a cancelled [`Linger`](struct.Linger.html), handed to its spawner
+///
+///It completes as `Finished` if the work completes within the delay,
+/// and as `Cancelled` when the delay elapses, dropping the work
+pub type Lingering<F, T> = Cancellable<Detached<F>, <T as Timer>::Sleep>;
+
+pin_project! {
+    ///[`Cancellable`](../struct.Cancellable.html) letting its inner future linger in the
+    /// background for a bounded time once cancelled
+    ///
+    ///Created with [`Cancellable::linger`](../struct.Cancellable.html#method.linger)
+    #[must_use = "futures do nothing unless you `.await` or poll them"]
+    pub struct Linger<F, S, T, Sp> {
+        #[pin]
+        detach: Detach<F, S>,
+        timer: T,
+        duration: Duration,
+        spawn: Option<Sp>,
+    }
+}
+
+impl<F, S, T, Sp> Future for Linger<F, S, T, Sp>
+where
+    F: Future,
+    S: Future,
+    T: Timer,
+    Sp: FnOnce(Lingering<F, T>),
+{
+    type Output = CancellableResult<F::Output, S::Output>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
+        let this = self.project();
+
+        match this.detach.poll(cx) {
+            Poll::Ready(CancellableResult::Finished(t)) => {
+                Poll::Ready(CancellableResult::Finished(t))
+            }
+            Poll::Ready(CancellableResult::Cancelled((s, detached))) => {
+                let spawn = this.spawn.take().expect("Linger polled after completion");
+                spawn(detached.cancel_with(this.timer.sleep(*this.duration)));
+
+                Poll::Ready(CancellableResult::Cancelled(s))
+            }
+            Poll::Pending => Poll::Pending,
+        }
+    }
+}
+
+impl<F, S, T, Sp> core::fmt::Debug for Linger<F, S, T, Sp>
+where
+    T: core::fmt::Debug,
+{
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        f.debug_struct("Linger")
+            .field("detach", &self.detach)
+            .field("timer", &self.timer)
+            .field("duration", &self.duration)
+            .finish()
+    }
+}
+
+impl<F, S> Cancellable<F, S>
+where
+    F: Future,
+    S: Future,
+{
+    ///Resolve when the stopper completes, handing back the inner future instead of dropping it
+    ///
+    ///See the [`detach`](detach/index.html) module
+    pub fn detach(self) -> Detach<F, S> {
+        let Self { inner, stopper, name } = self;
+        Detach { inner: Some(Box::pin(inner)), stopper, name }
+    }
+
+    ///Resolve when the stopper completes, handing the inner future to `spawn`
+    /// to keep running in the background for up to `duration`, measured with `timer`
+    ///
+    ///Unlike a grace period, the caller gets the `Cancelled` result right away.
+    ///See the [`detach`](detach/index.html) module
+    pub fn linger<T, Sp>(self, timer: T, duration: Duration, spawn: Sp) -> Linger<F, S, T, Sp>
+    where
+        T: Timer,
+        Sp: FnOnce(Lingering<F, T>),
+    {
+        Linger { detach: self.detach(), timer, duration, spawn: Some(spawn) }
+    }
+}
@@ -0,0 +1,54 @@
+//! Stoppers for embassy-based firmware
+//!
+//! `Signal::wait` from `embassy_sync` and `embassy_time::Timer` are both usable as stoppers,
+//! the helpers in this module wire them up in one call.
+//!
+//! # Example
+//! ```rust
+//! # use futures::{executor::block_on, future::pending};
+//!  use embassy_sync::{blocking_mutex::raw::NoopRawMutex, signal::Signal};
+//!  use kyansel::embassy::cancel_on_signal;
+//!
+//!  let stop = Signal::<NoopRawMutex, u8>::new();
+//!  let cancellable = cancel_on_signal(pending::<()>(), &stop);
+//!
+//!  stop.signal(3);
+//!
+//!  assert_eq!(block_on(cancellable).cancelled(), Some(3));
+//! ```
+
+use super::{Cancellable, FutureCancellable};
+use core::future::Future;
+use embassy_sync::{blocking_mutex::raw::RawMutex, signal::Signal};
+use embassy_time::{Duration, Instant, Timer};
+
+///Cancel `fut` if `signal` is signalled before it completes
+///
+///Like `Signal::wait`, cancelling takes the signalled value out of the signal
+pub fn cancel_on_signal<'a, F, M, T>(
+    fut: F,
+    signal: &'a Signal<M, T>,
+) -> Cancellable<F, impl Future<Output = T> + 'a>
+where
+    F: Future,
+    M: RawMutex,
+    T: Send,
+{
+    fut.cancel_with(signal.wait())
+}
+
+///Cancel `fut` if it hasn't completed after `duration`
+pub fn cancel_after<F>(fut: F, duration: Duration) -> Cancellable<F, Timer>
+where
+    F: Future,
+{
+    fut.cancel_with(Timer::after(duration))
+}
+
+///Cancel `fut` if it hasn't completed by `deadline`
+pub fn cancel_at<F>(fut: F, deadline: Instant) -> Cancellable<F, Timer>
+where
+    F: Future,
+{
+    fut.cancel_with(Timer::at(deadline))
+}
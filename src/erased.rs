@@ -0,0 +1,67 @@
+//! Type-erased stoppers
+//!
+//!Every distinct stopper type instantiates [`Cancellable`](../struct.Cancellable.html)
+//! and everything wrapping it again, which adds up in codebases using hundreds of them.
+//![`ErasedSignal`](struct.ErasedSignal.html) boxes the stopper behind a vtable,
+//! so all the call sites opting into it share the same instantiation,
+//! at the cost of an allocation and an indirect call on every poll.
+//!
+//! # Example
+//! ```rust
+//! # use futures::{executor::block_on, future::{pending, ready, Pending}};
+//!  use kyansel::{erased::{ErasedCancellable, ErasedSignal}, FutureCancellable};
+//!
+//!  //different stoppers, same type
+//!  let jobs: Vec<ErasedCancellable<Pending<()>>> = vec![
+//!      pending().cancel_with(ErasedSignal::new(ready(()))),
+//!      pending().cancel_with(ErasedSignal::new(async {})),
+//!  ];
+//!
+//!  for job in jobs {
+//!      assert!(block_on(job).is_cancelled());
+//!  }
+//! ```
+
+use super::Cancellable;
+use core::{
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll},
+};
+use std::boxed::Box;
+
+///Boxed stopper of any type completing with `T`
+///
+///Unlike a plain `Pin<Box<dyn Future>>` it's `Debug`, and reads better in signatures
+#[must_use = "futures do nothing unless you `.await` or poll them"]
+pub struct ErasedSignal<'a, T = ()> {
+    signal: Pin<Box<dyn Future<Output = T> + Send + 'a>>,
+}
+
+impl<'a, T> ErasedSignal<'a, T> {
+    ///Erase the type of `signal`
+    pub fn new<S>(signal: S) -> Self
+    where
+        S: Future<Output = T> + Send + 'a,
+    {
+        Self { signal: Box::pin(signal) }
+    }
+}
+
+impl<T> Future for ErasedSignal<'_, T> {
+    type Output = T;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
+        self.get_mut().signal.as_mut().poll(cx)
+    }
+}
+
+impl<T> core::fmt::Debug for ErasedSignal<'_, T> {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        f.debug_struct("ErasedSignal").finish_non_exhaustive()
+    }
+}
+
+///[`Cancellable`](../struct.Cancellable.html) with an
+/// [`ErasedSignal`](struct.ErasedSignal.html) stopper
+pub type ErasedCancellable<'a, F, T = ()> = Cancellable<F, ErasedSignal<'a, T>>;
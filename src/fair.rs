@@ -0,0 +1,186 @@
+//! Polling the inner future and the stopper in a varying order
+//!
+//![`Cancellable`](../struct.Cancellable.html) always polls the inner future first,
+//! so when both are ready the inner future wins.
+//![`Cancellable::fair`](../struct.Cancellable.html#method.fair) picks which one is polled first
+//! on every poll with a [`PollOrder`](trait.PollOrder.html), so neither side is systematically
+//! favored: [`Alternate`](struct.Alternate.html) takes turns, while [`Random`](struct.Random.html)
+//! flips a coin from a seed, so tests can reproduce the order.
+//!
+//! # Example
+//! ```rust
+//! # use futures::{executor::block_on, future::ready};
+//!  use kyansel::{fair::Alternate, FutureCancellable};
+//!
+//!  let mut order = Alternate::default();
+//!
+//!  //the inner future goes first
+//!  let result = block_on(ready(42).cancel_with(ready("stop")).fair(&mut order));
+//!  assert_eq!(result.finished(), Some(42));
+//!
+//!  //then the stopper
+//!  let result = block_on(ready(42).cancel_with(ready("stop")).fair(&mut order));
+//!  assert_eq!(result.cancelled(), Some("stop"));
+//! ```
+
+use super::{rng::XorShift, Cancellable, CancellableResult};
+use core::{
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll},
+};
+use pin_project_lite::pin_project;
+
+///Decides, on every poll, whether the stopper is polled before the inner future
+pub trait PollOrder {
+    ///Check if the stopper should be polled first this time
+    fn stopper_first(&mut self) -> bool;
+}
+
+impl<O> PollOrder for &mut O
+where
+    O: PollOrder + ?Sized,
+{
+    fn stopper_first(&mut self) -> bool { (**self).stopper_first() }
+}
+
+///Order alternating between the inner future and the stopper, starting with the inner future
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Alternate {
+    stopper_next: bool,
+}
+
+impl PollOrder for Alternate {
+    fn stopper_first(&mut self) -> bool {
+        let stopper_first = self.stopper_next;
+        self.stopper_next = !stopper_first;
+        stopper_first
+    }
+}
+
+///Order picked at random, from a seeded generator
+///
+///The generator is a xorshift, fast and reproducible but in no way cryptographically secure
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Random {
+    rng: XorShift,
+}
+
+impl Random {
+    ///Create a generator from `seed`, the same seed always gives the same order
+    ///
+    /// # Example
+    /// ```rust
+    ///  use kyansel::fair::{PollOrder, Random};
+    ///
+    ///  let (mut a, mut b) = (Random::seeded(7), Random::seeded(7));
+    ///  assert!((0..64).all(|_| a.stopper_first() == b.stopper_first()));
+    ///
+    ///  //close seeds give different orders
+    ///  let (mut a, mut b) = (Random::seeded(6), Random::seeded(7));
+    ///  assert!((0..64).any(|_| a.stopper_first() != b.stopper_first()));
+    /// ```
+    pub const fn seeded(seed: u64) -> Self { Self { rng: XorShift::seeded(seed) } }
+}
+
+impl PollOrder for Random {
+    fn stopper_first(&mut self) -> bool {
+        //the high bits are the most random ones
+        self.rng.next_u64() >> 63 == 1
+    }
+}
+
+pin_project! {
+    ///[`Cancellable`](../struct.Cancellable.html) polling its futures in the order picked by `O`
+    ///
+    ///Created with [`Cancellable::fair`](../struct.Cancellable.html#method.fair)
+    #[must_use = "futures do nothing unless you `.await` or poll them"]
+    pub struct Fair<F, S, O>
+    where
+        F: Future,
+        S: Future,
+    {
+        #[pin]
+        cancellable: Cancellable<F, S>,
+        order: O,
+    }
+}
+
+impl<F, S, O> Future for Fair<F, S, O>
+where
+    F: Future,
+    S: Future,
+    O: PollOrder,
+{
+    type Output = CancellableResult<F::Output, S::Output>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
+        let this = self.project();
+        #[cfg(any(feature = "log", feature = "tracing"))]
+        let name = this.cancellable.name;
+        let mut projection = this.cancellable.project();
+
+        let stopper_first = this.order.stopper_first();
+        if stopper_first {
+            if let Poll::Ready(s) = projection.stopper.as_mut().poll(cx) {
+                #[cfg(any(feature = "log", feature = "tracing"))]
+                super::trace_named(name, "cancelled");
+
+                return Poll::Ready(CancellableResult::Cancelled(s));
+            }
+        }
+
+        if let Poll::Ready(t) = projection.inner.poll(cx) {
+            #[cfg(any(feature = "log", feature = "tracing"))]
+            super::trace_named(name, "finished");
+
+            return Poll::Ready(CancellableResult::Finished(t));
+        }
+
+        //the stopper was already polled, and was pending
+        if stopper_first {
+            return Poll::Pending;
+        }
+
+        match projection.stopper.poll(cx) {
+            Poll::Ready(s) => {
+                #[cfg(any(feature = "log", feature = "tracing"))]
+                super::trace_named(name, "cancelled");
+
+                Poll::Ready(CancellableResult::Cancelled(s))
+            }
+            Poll::Pending => Poll::Pending,
+        }
+    }
+}
+
+impl<F, S, O> core::fmt::Debug for Fair<F, S, O>
+where
+    F: Future + core::fmt::Debug,
+    S: Future + core::fmt::Debug,
+    O: core::fmt::Debug,
+{
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        f.debug_struct("Fair")
+            .field("cancellable", &self.cancellable)
+            .field("order", &self.order)
+            .finish()
+    }
+}
+
+impl<F, S> Cancellable<F, S>
+where
+    F: Future,
+    S: Future,
+{
+    ///Pick which future is polled first on every poll with `order`,
+    /// instead of always polling the inner future first
+    ///
+    ///See the [`fair`](fair/index.html) module
+    pub fn fair<O>(self, order: O) -> Fair<F, S, O>
+    where
+        O: PollOrder,
+    {
+        Fair { cancellable: self, order }
+    }
+}
@@ -0,0 +1,124 @@
+//! Flag-based stoppers checked without polling
+//!
+//!Stoppers like
+//! [`CancellationToken::cancelled`](../token/struct.CancellationToken.html#method.cancelled)
+//! are backed by a flag that's much cheaper to read than polling them,
+//! which registers the waker of the task every time.
+//!
+//!Implementing [`CancelSignal`](trait.CancelSignal.html) exposes that flag, and
+//! [`Cancellable::fast_path`](../struct.Cancellable.html#method.fast_path) checks it before
+//! polling anything, only polling the stopper to register a new waker.
+//!
+//! # Example
+//! ```rust
+//! # use futures::{executor::block_on, future::pending};
+//!  use kyansel::{token::CancellationToken, FutureCancellable};
+//!
+//!  let token = CancellationToken::new();
+//!  let cancellable = pending::<()>().cancel_with(token.cancelled()).fast_path();
+//!
+//!  token.cancel();
+//!  assert!(block_on(cancellable).is_cancelled());
+//! ```
+
+use super::{Cancellable, CancellableResult};
+use core::{
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll, Waker},
+};
+use pin_project_lite::pin_project;
+
+///Stopper whose completion can be checked without polling it
+pub trait CancelSignal: Future {
+    ///Check if the stopper would complete if polled
+    ///
+    ///Returning `false` when the stopper would complete only delays the cancellation
+    /// until it wakes the task, while returning `true` when it wouldn't makes
+    /// [`FastPath`](struct.FastPath.html) poll it every time
+    fn is_cancelled(&self) -> bool;
+}
+
+impl<T> CancelSignal for core::future::Pending<T> {
+    fn is_cancelled(&self) -> bool { false }
+}
+
+pin_project! {
+    ///[`Cancellable`](../struct.Cancellable.html) checking its stopper's flag before polling
+    ///
+    ///Created with [`Cancellable::fast_path`](../struct.Cancellable.html#method.fast_path)
+    #[must_use = "futures do nothing unless you `.await` or poll them"]
+    pub struct FastPath<F, S>
+    where
+        F: Future,
+        S: Future,
+    {
+        #[pin]
+        cancellable: Cancellable<F, S>,
+        registered: Option<Waker>,
+    }
+}
+
+impl<F, S> Future for FastPath<F, S>
+where
+    F: Future,
+    S: CancelSignal,
+{
+    type Output = CancellableResult<F::Output, S::Output>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
+        let this = self.project();
+        #[cfg(any(feature = "log", feature = "tracing"))]
+        let name = this.cancellable.name;
+        let projection = this.cancellable.project();
+
+        //unlike `Cancellable` the flag is checked before polling anything
+        if !projection.stopper.is_cancelled() {
+            if let Poll::Ready(t) = projection.inner.poll(cx) {
+                #[cfg(any(feature = "log", feature = "tracing"))]
+                super::trace_named(name, "finished");
+
+                return Poll::Ready(CancellableResult::Finished(t));
+            }
+
+            //the stopper only needs to be polled to register a new waker
+            match &this.registered {
+                Some(waker) if waker.will_wake(cx.waker()) => return Poll::Pending,
+                _ => *this.registered = Some(cx.waker().clone()),
+            }
+        }
+
+        match projection.stopper.poll(cx) {
+            Poll::Ready(s) => {
+                #[cfg(any(feature = "log", feature = "tracing"))]
+                super::trace_named(name, "cancelled");
+
+                Poll::Ready(CancellableResult::Cancelled(s))
+            }
+            Poll::Pending => Poll::Pending,
+        }
+    }
+}
+
+impl<F, S> core::fmt::Debug for FastPath<F, S>
+where
+    F: Future + core::fmt::Debug,
+    S: Future + core::fmt::Debug,
+{
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        f.debug_struct("FastPath").field("cancellable", &self.cancellable).finish()
+    }
+}
+
+impl<F, S> Cancellable<F, S>
+where
+    F: Future,
+    S: CancelSignal,
+{
+    ///Check the flag of the stopper before polling, instead of polling it every time
+    ///
+    ///See the [`fast_path`](fast_path/index.html) module
+    pub const fn fast_path(self) -> FastPath<F, S> {
+        FastPath { cancellable: self, registered: None }
+    }
+}
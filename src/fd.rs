@@ -0,0 +1,122 @@
+//! File descriptors becoming readable on cancellation
+//!
+//!C libraries and hand-written `epoll`/`poll` loops can't await a future, but they can wait
+//! on a file descriptor.
+//![`CancellationFd`](struct.CancellationFd.html) is backed by a pipe that becomes readable
+//! when its [`CancellationToken`](../token/struct.CancellationToken.html) is cancelled,
+//! and stays readable from then on, so it can be added to any readiness-based loop.
+//!
+//!Only available on Unix
+//!
+//! # Example
+//! ```rust
+//!  use kyansel::{fd::CancellationFd, token::CancellationToken};
+//!  use std::os::fd::AsRawFd;
+//!
+//!  let token = CancellationToken::new();
+//!  let fd = CancellationFd::new(&token).unwrap();
+//!
+//!  let readable = |fd: &CancellationFd| {
+//!      let mut pollfd = libc::pollfd { fd: fd.as_raw_fd(), events: libc::POLLIN, revents: 0 };
+//!      unsafe { libc::poll(&mut pollfd, 1, 0) == 1 }
+//!  };
+//!
+//!  assert!(!readable(&fd));
+//!  token.cancel();
+//!  assert!(readable(&fd));
+//! ```
+
+use super::token::{CancellationToken, WaitForCancellation};
+use core::{
+    future::Future,
+    pin::Pin,
+    task::{Context, Waker},
+};
+use std::{
+    io,
+    os::fd::{AsFd, AsRawFd, BorrowedFd, FromRawFd, OwnedFd, RawFd},
+    sync::Arc,
+    task::Wake,
+};
+
+//writes the byte making the read end readable, once woken by the token
+#[derive(Debug)]
+struct PipeWaker {
+    write: OwnedFd,
+}
+
+impl PipeWaker {
+    fn notify(&self) {
+        //a full pipe is readable already, nothing else can go wrong worth reporting
+        let _ = unsafe { libc::write(self.write.as_raw_fd(), [1u8].as_ptr().cast(), 1) };
+    }
+}
+
+impl Wake for PipeWaker {
+    fn wake(self: Arc<Self>) { self.notify() }
+
+    fn wake_by_ref(self: &Arc<Self>) { self.notify() }
+}
+
+///File descriptor becoming readable when a
+/// [`CancellationToken`](../token/struct.CancellationToken.html) is cancelled
+///
+///The descriptor is non-blocking and close-on-exec, it shouldn't be read from:
+/// it's only meant to be waited on for readability.
+///Dropping it closes the pipe and stops observing the token
+#[derive(Debug)]
+pub struct CancellationFd {
+    read: OwnedFd,
+    _cancelled: WaitForCancellation,
+}
+
+impl CancellationFd {
+    ///Create a descriptor becoming readable when `token` is cancelled,
+    /// right away if it's already cancelled
+    pub fn new(token: &CancellationToken) -> io::Result<Self> {
+        let (read, write) = pipe()?;
+        let waker = Arc::new(PipeWaker { write });
+
+        //registers the waker with the token, which wakes it once cancelled
+        let mut cancelled = token.cancelled();
+        let ready = Pin::new(&mut cancelled)
+            .poll(&mut Context::from_waker(&Waker::from(waker.clone())))
+            .is_ready();
+        if ready {
+            waker.notify();
+        }
+
+        Ok(Self { read, _cancelled: cancelled })
+    }
+}
+
+impl AsFd for CancellationFd {
+    fn as_fd(&self) -> BorrowedFd<'_> { self.read.as_fd() }
+}
+
+impl AsRawFd for CancellationFd {
+    fn as_raw_fd(&self) -> RawFd { self.read.as_raw_fd() }
+}
+
+fn pipe() -> io::Result<(OwnedFd, OwnedFd)> {
+    let mut fds = [0; 2];
+    if unsafe { libc::pipe(fds.as_mut_ptr()) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+
+    //SAFETY: both descriptors were just opened, and are owned by nobody else
+    let (read, write) = unsafe { (OwnedFd::from_raw_fd(fds[0]), OwnedFd::from_raw_fd(fds[1])) };
+
+    for fd in [&read, &write] {
+        let fd = fd.as_raw_fd();
+        let flags = unsafe { libc::fcntl(fd, libc::F_GETFL) };
+        if flags < 0
+            || unsafe { libc::fcntl(fd, libc::F_SETFL, flags | libc::O_NONBLOCK) } < 0
+            || unsafe { libc::fcntl(fd, libc::F_SETFD, libc::FD_CLOEXEC) } < 0
+        {
+            return Err(io::Error::last_os_error());
+        }
+    }
+
+    Ok((read, write))
+}
@@ -0,0 +1,106 @@
+//! C interface to cancellation tokens
+//!
+//!Hosts written in C or C++, embedding a Rust async core, can cancel its work through
+//! a [`KyanselToken`](struct.KyanselToken.html) without knowing anything about futures.
+//!The Rust side hands out handles with
+//! [`KyanselToken::into_raw`](struct.KyanselToken.html#method.into_raw),
+//! or accepts ones created by the host with [`kyansel_token_new`](fn.kyansel_token_new.html),
+//! and the host triggers and queries them with the `kyansel_token_*` functions:
+//!
+//! ```c
+//! typedef struct KyanselToken KyanselToken;
+//!
+//! KyanselToken *kyansel_token_new(void);
+//! KyanselToken *kyansel_token_clone(const KyanselToken *token);
+//! void kyansel_token_cancel(const KyanselToken *token);
+//! bool kyansel_token_is_cancelled(const KyanselToken *token);
+//! void kyansel_token_free(KyanselToken *token);
+//! ```
+//!
+//! # Example
+//! ```rust
+//!  use kyansel::{ffi::*, token::CancellationToken};
+//!
+//!  let token = CancellationToken::new();
+//!
+//!  //handed to the host, which cancels the work and releases its handle
+//!  let handle = KyanselToken::into_raw(token.clone());
+//!  unsafe {
+//!      kyansel_token_cancel(handle);
+//!      assert!(kyansel_token_is_cancelled(handle));
+//!      kyansel_token_free(handle);
+//!  }
+//!
+//!  assert!(token.is_cancelled());
+//! ```
+
+use super::token::CancellationToken;
+use std::boxed::Box;
+
+///Handle to a [`CancellationToken`](../token/struct.CancellationToken.html) owned by C code
+///
+///Always used behind a pointer, created by [`into_raw`](struct.KyanselToken.html#method.into_raw)
+/// or [`kyansel_token_new`](fn.kyansel_token_new.html)
+/// and released with [`kyansel_token_free`](fn.kyansel_token_free.html)
+#[repr(C)]
+#[derive(Debug)]
+pub struct KyanselToken {
+    token: CancellationToken,
+}
+
+impl KyanselToken {
+    ///Move `token` into a handle for C code, to be released with
+    /// [`kyansel_token_free`](fn.kyansel_token_free.html)
+    pub fn into_raw(token: CancellationToken) -> *mut Self {
+        Box::into_raw(Box::new(Self { token }))
+    }
+
+    ///Retrieve the token behind the handle
+    pub fn token(&self) -> &CancellationToken { &self.token }
+}
+
+///Create a new, uncancelled token
+///
+///The handle must be released with [`kyansel_token_free`](fn.kyansel_token_free.html)
+#[no_mangle]
+pub extern "C" fn kyansel_token_new() -> *mut KyanselToken {
+    KyanselToken::into_raw(CancellationToken::new())
+}
+
+///Create a new handle to the same token, released separately
+///
+/// # Safety
+///`token` must be a live handle
+#[no_mangle]
+pub unsafe extern "C" fn kyansel_token_clone(token: *const KyanselToken) -> *mut KyanselToken {
+    KyanselToken::into_raw((*token).token.clone())
+}
+
+///Cancel the token, waking all the futures waiting on it
+///
+/// # Safety
+///`token` must be a live handle
+#[no_mangle]
+pub unsafe extern "C" fn kyansel_token_cancel(token: *const KyanselToken) {
+    (*token).token.cancel()
+}
+
+///Check if the token was cancelled
+///
+/// # Safety
+///`token` must be a live handle
+#[no_mangle]
+pub unsafe extern "C" fn kyansel_token_is_cancelled(token: *const KyanselToken) -> bool {
+    (*token).token.is_cancelled()
+}
+
+///Release the handle, without cancelling the token
+///
+/// # Safety
+///`token` must be a live handle, or null, and isn't usable afterwards
+#[no_mangle]
+pub unsafe extern "C" fn kyansel_token_free(token: *mut KyanselToken) {
+    if !token.is_null() {
+        drop(Box::from_raw(token));
+    }
+}
@@ -0,0 +1,111 @@
+//! Merging nested cancellation layers
+//!
+//!Cancelling a [`Cancellable`](../struct.Cancellable.html) with another stopper resolves to a
+//! `CancellableResult<CancellableResult<T, S1>, S2>`, which takes two matches to handle.
+//![`CancellableResult::flatten`](../enum.CancellableResult.html#method.flatten) merges the two
+//! layers into a single [`FlatResult`](enum.FlatResult.html), telling which stopper fired,
+//! and [`Cancellable::flatten`](../struct.Cancellable.html#method.flatten) does the same for the
+//! future itself.
+//!
+//! # Example
+//! ```rust
+//! # use futures::{executor::block_on, future::{pending, ready}};
+//!  use kyansel::{flatten::FlatResult, FutureCancellable};
+//!
+//!  let request = pending::<()>()
+//!      .cancel_with(pending::<&str>())
+//!      .cancel_with(ready("shutdown"))
+//!      .flatten();
+//!
+//!  assert_eq!(block_on(request), FlatResult::CancelledOuter("shutdown"));
+//! ```
+
+use super::{Cancellable, CancellableResult};
+use core::{
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll},
+};
+use pin_project_lite::pin_project;
+
+///Result of two nested cancellables, telling which stopper fired
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum FlatResult<T, S1, S2> {
+    ///If the innermost future finished
+    Finished(T),
+
+    ///If the inner stopper fired
+    CancelledInner(S1),
+
+    ///If the outer stopper fired
+    CancelledOuter(S2),
+}
+
+impl<T, S1, S2> FlatResult<T, S1, S2> {
+    ///Check if the future was cancelled by either stopper
+    pub fn is_cancelled(&self) -> bool { !matches!(self, Self::Finished(_)) }
+
+    ///Retrieve the result of the future
+    /// if it was not cancelled
+    pub fn finished(self) -> Option<T> {
+        match self {
+            Self::Finished(t) => Some(t),
+            _ => None,
+        }
+    }
+}
+
+impl<T, S1, S2> From<CancellableResult<CancellableResult<T, S1>, S2>> for FlatResult<T, S1, S2> {
+    fn from(result: CancellableResult<CancellableResult<T, S1>, S2>) -> Self {
+        match result {
+            CancellableResult::Finished(CancellableResult::Finished(t)) => Self::Finished(t),
+            CancellableResult::Finished(CancellableResult::Cancelled(s1)) => {
+                Self::CancelledInner(s1)
+            }
+            CancellableResult::Cancelled(s2) => Self::CancelledOuter(s2),
+        }
+    }
+}
+
+impl<T, S1, S2> CancellableResult<CancellableResult<T, S1>, S2> {
+    ///Merge the two cancellation layers into one
+    ///
+    ///See the [`flatten`](flatten/index.html) module
+    pub fn flatten(self) -> FlatResult<T, S1, S2> { self.into() }
+}
+
+pin_project! {
+    ///Future for the [`flatten`](../struct.Cancellable.html#method.flatten) combinator
+    #[must_use = "futures do nothing unless you `.await` or poll them"]
+    #[derive(Debug)]
+    pub struct Flatten<Fut> {
+        #[pin]
+        inner: Fut,
+    }
+}
+
+impl<Fut, T, S1, S2> Future for Flatten<Fut>
+where
+    Fut: Future<Output = CancellableResult<CancellableResult<T, S1>, S2>>,
+{
+    type Output = FlatResult<T, S1, S2>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
+        match self.project().inner.poll(cx) {
+            Poll::Ready(result) => Poll::Ready(result.flatten()),
+            Poll::Pending => Poll::Pending,
+        }
+    }
+}
+
+impl<F, S1, S2> Cancellable<Cancellable<F, S1>, S2>
+where
+    F: Future,
+    S1: Future,
+    S2: Future,
+{
+    ///Merge the two cancellation layers of the result into one
+    ///
+    ///See the [`flatten`](flatten/index.html) module
+    pub const fn flatten(self) -> Flatten<Self> { Flatten { inner: self } }
+}
@@ -0,0 +1,68 @@
+//! Stoppers for `flume` channels
+//!
+//!A [`ReceiverStopper`](struct.ReceiverStopper.html) completes with the first message
+//! received, or with `None` once all the senders are dropped,
+//! and implements [`CancelSignal`](../fast_path/trait.CancelSignal.html)
+//! so the channel can be checked without polling it.
+//!
+//! # Example
+//! ```rust
+//! # use futures::{executor::block_on, future::pending};
+//!  use kyansel::flume::cancel_on_recv;
+//!
+//!  let (tx, rx) = flume::unbounded();
+//!  let cancellable = cancel_on_recv(pending::<()>(), rx);
+//!
+//!  tx.send("shutdown").unwrap();
+//!  assert_eq!(block_on(cancellable).cancelled(), Some(Some("shutdown")));
+//! ```
+
+use super::{fast_path::CancelSignal, Cancellable, FutureCancellable};
+use core::{
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll},
+};
+use flume::{r#async::RecvFut, Receiver};
+
+///Stopper completing with the first message of a `flume` channel,
+/// or `None` when the channel is disconnected
+///
+///`'a` bounds the lifetime of the messages, like in `flume::r#async::RecvFut`
+#[must_use = "futures do nothing unless you `.await` or poll them"]
+pub struct ReceiverStopper<'a, T> {
+    //a clone of the receiver in `recv`, to check the channel without polling
+    rx: Receiver<T>,
+    recv: RecvFut<'a, T>,
+}
+
+impl<'a, T> ReceiverStopper<'a, T> {
+    ///Create a stopper receiving from `rx`
+    pub fn new(rx: Receiver<T>) -> Self { Self { rx: rx.clone(), recv: rx.into_recv_async() } }
+}
+
+impl<T> Future for ReceiverStopper<'_, T> {
+    type Output = Option<T>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
+        Pin::new(&mut self.get_mut().recv).poll(cx).map(Result::ok)
+    }
+}
+
+impl<T> CancelSignal for ReceiverStopper<'_, T> {
+    fn is_cancelled(&self) -> bool { !self.rx.is_empty() || self.rx.is_disconnected() }
+}
+
+impl<T> core::fmt::Debug for ReceiverStopper<'_, T> {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        f.debug_struct("ReceiverStopper").field("rx", &self.rx).finish()
+    }
+}
+
+///Cancel `fut` when a message is received from `rx`, or when the channel is disconnected
+pub fn cancel_on_recv<'a, F, T>(fut: F, rx: Receiver<T>) -> Cancellable<F, ReceiverStopper<'a, T>>
+where
+    F: Future,
+{
+    fut.cancel_with(ReceiverStopper::new(rx))
+}
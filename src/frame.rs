@@ -0,0 +1,128 @@
+//! Driving cancellable futures a time slice at a time
+//!
+//!Game loops and UI frames can't block on a future, but they can give it part of each frame.
+//![`Cancellable::time_sliced`](../struct.Cancellable.html#method.time_sliced) returns a
+//! [`TimeSliced`](struct.TimeSliced.html) driver whose [`tick`](struct.TimeSliced.html#method.tick)
+//! polls the future, and so checks its stopper, at least once, then keeps polling it while it
+//! wakes itself, until it completes or its budget of wall time for the frame is spent.
+//!
+//! # Example
+//! ```rust
+//! # use futures::future::{pending, poll_fn};
+//!  use kyansel::{token::CancellationToken, FutureCancellable};
+//!  use std::{task::Poll, time::Duration};
+//!
+//!  let token = CancellationToken::new();
+//!
+//!  //loading a level, a bit at a time
+//!  let mut chunks = 0;
+//!  let loading = poll_fn(|cx| {
+//!      chunks += 1;
+//!      cx.waker().wake_by_ref();
+//!      Poll::<()>::Pending
+//!  });
+//!
+//!  let mut driver = loading.cancel_with(token.cancelled()).time_sliced(Duration::from_millis(1));
+//!
+//!  //the game loop
+//!  for frame in 0.. {
+//!      if frame == 10 {
+//!          //the player went back to the menu
+//!          token.cancel();
+//!      }
+//!
+//!      if let Poll::Ready(result) = driver.tick() {
+//!          assert!(result.is_cancelled());
+//!          break;
+//!      }
+//!  }
+//! ```
+
+use super::{wake::WakeFlag, Cancellable};
+use core::{
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll, Waker},
+    time::Duration,
+};
+use std::{boxed::Box, sync::Arc, time::Instant};
+
+///Driver polling a future for up to a budget of wall time per call to
+/// [`tick`](struct.TimeSliced.html#method.tick)
+///
+///Created with [`Cancellable::time_sliced`](../struct.Cancellable.html#method.time_sliced)
+pub struct TimeSliced<F>
+where
+    F: Future,
+{
+    fut: Option<Pin<Box<F>>>,
+    budget: Duration,
+    woken: Arc<WakeFlag>,
+    waker: Waker,
+}
+
+impl<F> TimeSliced<F>
+where
+    F: Future,
+{
+    ///Drive `fut` for up to `budget` per tick
+    pub fn new(fut: F, budget: Duration) -> Self {
+        let woken = Arc::new(WakeFlag::default());
+        Self { fut: Some(Box::pin(fut)), budget, waker: Waker::from(woken.clone()), woken }
+    }
+
+    ///Poll the future once, then again as long as it woke itself and the budget isn't spent
+    ///
+    ///A single poll can run past the budget, it's only checked between polls
+    ///
+    /// # Panics
+    ///Panics if called again after the future completed
+    pub fn tick(&mut self) -> Poll<F::Output> {
+        let fut = self.fut.as_mut().expect("TimeSliced ticked after completion");
+        let mut cx = Context::from_waker(&self.waker);
+        let start = Instant::now();
+
+        loop {
+            //wakeups during this poll mean it wants to be polled again
+            self.woken.take();
+
+            if let Poll::Ready(output) = fut.as_mut().poll(&mut cx) {
+                self.fut = None;
+                return Poll::Ready(output);
+            }
+
+            if !self.woken.take() || start.elapsed() >= self.budget {
+                return Poll::Pending;
+            }
+        }
+    }
+
+    ///Check if the future was woken since the last tick, so it has progress to make
+    pub fn is_woken(&self) -> bool { self.woken.is_woken() }
+
+    ///Check if the future completed
+    pub fn is_terminated(&self) -> bool { self.fut.is_none() }
+}
+
+impl<F> core::fmt::Debug for TimeSliced<F>
+where
+    F: Future,
+{
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        f.debug_struct("TimeSliced")
+            .field("budget", &self.budget)
+            .field("terminated", &self.is_terminated())
+            .finish()
+    }
+}
+
+impl<F, S> Cancellable<F, S>
+where
+    F: Future,
+    S: Future,
+{
+    ///Drive the future from a frame loop, for up to `budget` per tick
+    ///
+    ///See the [`frame`](frame/index.html) module
+    pub fn time_sliced(self, budget: Duration) -> TimeSliced<Self> { TimeSliced::new(self, budget) }
+}
@@ -0,0 +1,92 @@
+//! Stoppers that can give up on cancelling
+//!
+//!A stopper like a `oneshot::Receiver` completes with an error when its sender is dropped,
+//! meaning nobody is left to cancel the future.
+//![`FutureCancellable::cancel_on_ok`](../trait.FutureCancellable.html#method.cancel_on_ok)
+//! only cancels the future when the stopper completes with `Ok`,
+//! and drops the stopper when it completes with `Err`, so it's never polled again.
+//!
+//!This mirrors the [`futures_01`](../futures_01/index.html) combinator,
+//! where stoppers completing with an error are ignored.
+//!
+//! # Example
+//! ```rust
+//! # use futures::{channel::oneshot, executor::block_on, future::pending, pin_mut, poll};
+//!  use kyansel::FutureCancellable;
+//!
+//! # block_on(async {
+//!  let (tx, rx) = oneshot::channel::<()>();
+//!  let cancellable = pending::<()>().cancel_on_ok(rx);
+//!  pin_mut!(cancellable);
+//!
+//!  //nobody can cancel the future anymore
+//!  drop(tx);
+//!
+//!  assert!(poll!(cancellable.as_mut()).is_pending());
+//!  assert!(cancellable.is_fused());
+//! # });
+//! ```
+
+use super::CancellableResult;
+use core::{
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll},
+};
+use pin_project_lite::pin_project;
+
+pin_project! {
+    ///Future for the [`cancel_on_ok`](../trait.FutureCancellable.html#method.cancel_on_ok)
+    /// combinator
+    #[derive(Debug)]
+    #[must_use = "futures do nothing unless you `.await` or poll them"]
+    pub struct CancelOnOk<F, S>
+    where
+        F: Future,
+        S: Future,
+    {
+        #[pin]
+        inner: F,
+        #[pin]
+        stopper: Option<S>,
+    }
+}
+
+impl<F, S> CancelOnOk<F, S>
+where
+    F: Future,
+    S: Future,
+{
+    pub(crate) const fn new(inner: F, stopper: S) -> Self { Self { inner, stopper: Some(stopper) } }
+
+    ///Check if the stopper gave up, so the future can only finish
+    pub const fn is_fused(&self) -> bool { self.stopper.is_none() }
+}
+
+impl<F, S, T, E> Future for CancelOnOk<F, S>
+where
+    F: Future,
+    S: Future<Output = Result<T, E>>,
+{
+    type Output = CancellableResult<F::Output, T>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
+        let this = self.project();
+        let mut stopper = this.stopper;
+
+        //always poll inner future first
+        if let Poll::Ready(t) = this.inner.poll(cx) {
+            return Poll::Ready(CancellableResult::Finished(t));
+        }
+
+        match stopper.as_mut().as_pin_mut().map(|stopper| stopper.poll(cx)) {
+            Some(Poll::Ready(Ok(s))) => Poll::Ready(CancellableResult::Cancelled(s)),
+            Some(Poll::Ready(Err(_))) => {
+                //don't poll again
+                stopper.set(None);
+                Poll::Pending
+            }
+            _ => Poll::Pending,
+        }
+    }
+}
@@ -3,14 +3,14 @@
 //! # Example
 //! ```rust
 //! # fn main() {
-//! #  use futures_01::future::{Future, IntoFuture};
+//! #  use futures_01::{future::{Future, IntoFuture}, sync::oneshot};
 //!  use kyansel::futures_01::{CancellableError, FutureCancellable};
-//! #  use tokio_01::{sync::oneshot, timer::Delay, runtime::Builder};
+//! #  use tokio_01::{timer::Delay, runtime::Builder};
 //! #  fn run(fut: impl Future<Item = (), Error = ()> + Send + 'static) { Builder::new().panic_handler(|err| std::panic::resume_unwind(err)).build().unwrap().block_on(fut); }
 //!
 //!  let (tx, rx) = oneshot::channel::<()>();
 //!
-//!  let deadline = tokio::clock::now() + std::time::Duration::from_secs(1).into();
+//!  let deadline = tokio_01::clock::now() + std::time::Duration::from_secs(1).into();
 //!
 //!  //simulate a long-running future
 //!  let cancellable = Delay::new(deadline)
@@ -67,12 +67,7 @@ pub enum CancellableError<C, E> {
 
 impl<C, E> CancellableError<C, E> {
     ///Check if the future was cancelled
-    pub fn is_cancelled(&self) -> bool {
-        match self {
-            Self::Cancelled(_) => true,
-            _ => false,
-        }
-    }
+    pub fn is_cancelled(&self) -> bool { matches!(self, Self::Cancelled(_)) }
 
     ///Retrieve the error of the future
     /// if it was not cancelled and it errored
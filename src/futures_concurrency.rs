@@ -0,0 +1,88 @@
+//! Interop with `futures-concurrency`
+//!
+//![`CancelRaceExt`](trait.CancelRaceExt.html) cancels a future with a group of stoppers
+//! combined by `futures-concurrency`: the first of them to complete with
+//! [`cancel_with_any`](trait.CancelRaceExt.html#method.cancel_with_any),
+//! or the first item of a group of streams with
+//! [`cancel_with_merged`](trait.CancelRaceExt.html#method.cancel_with_merged).
+//!
+//![`Cancellable`](../struct.Cancellable.html)s are plain futures,
+//! so they can be raced and joined by `futures-concurrency` like any other.
+//!
+//! # Example
+//! ```rust
+//! # use futures::{executor::block_on, future::{pending, ready}, stream};
+//!  use kyansel::futures_concurrency::CancelRaceExt;
+//!
+//!  let raced = pending::<()>().cancel_with_any((pending(), ready("shutdown")));
+//!  assert_eq!(block_on(raced).cancelled(), Some("shutdown"));
+//!
+//!  let signals = (stream::pending(), stream::iter(["SIGTERM"]));
+//!  let merged = pending::<()>().cancel_with_merged(signals);
+//!  assert_eq!(block_on(merged).cancelled(), Some(Some("SIGTERM")));
+//! ```
+
+use super::{Cancellable, FutureCancellable};
+use core::{
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll},
+};
+use futures_concurrency::{future::Race, stream::Merge};
+use futures_core::Stream;
+use pin_project_lite::pin_project;
+
+pin_project! {
+    ///Future completing with the first item of a stream, or `None` if it ends first
+    ///
+    ///Created with [`cancel_with_merged`](trait.CancelRaceExt.html#method.cancel_with_merged)
+    #[derive(Debug)]
+    #[must_use = "futures do nothing unless you `.await` or poll them"]
+    pub struct FirstItem<St> {
+        #[pin]
+        stream: St,
+    }
+}
+
+impl<St> Future for FirstItem<St>
+where
+    St: Stream,
+{
+    type Output = Option<St::Item>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
+        self.project().stream.poll_next(cx)
+    }
+}
+
+///An extension trait for `Future` cancelling it with groups of stoppers
+///
+///All types that implement `Future` already implement `CancelRaceExt`
+pub trait CancelRaceExt: Future {
+    ///Cancel this future when the first of `stoppers` completes
+    ///
+    ///`stoppers` is anything `futures-concurrency` can race,
+    /// like tuples, arrays and vectors of futures with the same output
+    fn cancel_with_any<R>(self, stoppers: R) -> Cancellable<Self, R::Future>
+    where
+        R: Race,
+        Self: Sized,
+    {
+        self.cancel_with(stoppers.race())
+    }
+
+    ///Cancel this future when the first of `signals` yields an item,
+    /// or when all of them end
+    ///
+    ///`signals` is anything `futures-concurrency` can merge,
+    /// like tuples, arrays and vectors of streams with the same item
+    fn cancel_with_merged<M>(self, signals: M) -> Cancellable<Self, FirstItem<M::Stream>>
+    where
+        M: Merge,
+        Self: Sized,
+    {
+        self.cancel_with(FirstItem { stream: signals.merge() })
+    }
+}
+
+impl<T: ?Sized> CancelRaceExt for T where T: Future {}
@@ -0,0 +1,74 @@
+//! Runtime-agnostic time-based cancellation backed by `futures-timer`
+//!
+//! `futures-timer` runs its own timer thread, so these helpers work with any executor,
+//! including `futures::executor`.
+//!
+//! # Example
+//! ```rust
+//! # use futures::{executor::block_on, future::pending};
+//!  use kyansel::futures_timer::{cancel_after, cancel_with_grace};
+//! #  use std::time::Duration;
+//!
+//!  let result = block_on(cancel_after(pending::<()>(), Duration::from_millis(10)));
+//!  assert!(result.is_cancelled());
+//!
+//!  //the stopper fires right away, but the work gets 50ms to complete
+//!  let work = futures_timer::Delay::new(Duration::from_millis(10));
+//!  let result = block_on(cancel_with_grace(work, async {}, Duration::from_millis(50)));
+//!  assert!(!result.is_cancelled());
+//! ```
+
+use super::{
+    grace::{self, Grace},
+    timer::Timer,
+    Cancellable, FutureCancellable,
+};
+use core::future::Future;
+use futures_timer::Delay;
+use std::time::{Duration, Instant};
+
+///[`Timer`](../timer/trait.Timer.html) backed by `futures_timer::Delay`
+#[derive(Debug, Default, Clone, Copy)]
+pub struct FuturesTimer;
+
+impl Timer for FuturesTimer {
+    type Instant = Instant;
+    type Sleep = Delay;
+
+    fn sleep(&self, duration: Duration) -> Self::Sleep { Delay::new(duration) }
+
+    fn sleep_until(&self, deadline: Self::Instant) -> Self::Sleep {
+        Delay::new(deadline.saturating_duration_since(Instant::now()))
+    }
+}
+
+///Cancel `fut` if it hasn't completed after `duration`
+pub fn cancel_after<F>(fut: F, duration: Duration) -> Cancellable<F, Delay>
+where
+    F: Future,
+{
+    fut.cancel_with(Delay::new(duration))
+}
+
+///Cancel `fut` if it hasn't completed by `deadline`
+pub fn cancel_at<F>(fut: F, deadline: Instant) -> Cancellable<F, Delay>
+where
+    F: Future,
+{
+    cancel_after(fut, deadline.saturating_duration_since(Instant::now()))
+}
+
+///Cancel `fut` if it hasn't completed within `grace` after `stopper` completes
+///
+///See [`grace::cancel_with_grace`](../grace/fn.cancel_with_grace.html)
+pub fn cancel_with_grace<F, S>(
+    fut: F,
+    stopper: S,
+    grace: Duration,
+) -> Grace<F, S, impl FnOnce() -> Delay, Delay>
+where
+    F: Future,
+    S: Future,
+{
+    grace::cancel_with_grace(fut, stopper, move || Delay::new(grace))
+}
@@ -0,0 +1,295 @@
+//! Cancellation with a grace period
+//!
+//! With [`cancel_with_grace`](fn.cancel_with_grace.html), when the stopper completes the inner
+//! future is not cancelled right away: it keeps being polled until a grace delay elapses,
+//! so work that is about to finish isn't thrown away.
+//!
+//! The delay is created by a closure only once the stopper completes, so any runtime's timer
+//! can be used, and an async closure works too: `async || sleep(grace).await`.
+//!
+//! [`cancel_with_escalation`](fn.cancel_with_escalation.html) also tells the inner future
+//! that it should wrap up, through a [`CancellationToken`](../token/struct.CancellationToken.html),
+//! and reports whether it did so in time or had to be dropped.
+//! The future is built by a closure taking the token, `async |token| ...` included.
+//!
+//! # Example
+//! ```rust
+//! # use futures::{executor::block_on, future::{pending, ready}};
+//!  use kyansel::grace::cancel_with_grace;
+//!
+//!  //the stopper completes right away, but the work completes within the grace period
+//!  let result = block_on(cancel_with_grace(ready(42), ready("stop"), pending::<()>));
+//!  assert_eq!(result.finished(), Some(42));
+//!
+//!  //the grace period elapses right away
+//!  let result = block_on(cancel_with_grace(pending::<()>(), ready("stop"), || ready(())));
+//!  assert_eq!(result.cancelled(), Some("stop"));
+//!
+//!  //same with an async closure
+//!  let result = block_on(cancel_with_grace(pending::<()>(), ready("stop"), async || {}));
+//!  assert_eq!(result.cancelled(), Some("stop"));
+//! ```
+
+use super::{timer::Timer, token::CancellationToken, CancellableResult};
+use core::{
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll},
+    time::Duration,
+};
+use pin_project_lite::pin_project;
+
+pin_project! {
+    ///Future for the [`cancel_with_grace`](fn.cancel_with_grace.html) combinator
+    #[must_use = "futures do nothing unless you `.await` or poll them"]
+    pub struct Grace<F, S, Mk, D>
+    where
+        S: Future,
+    {
+        #[pin]
+        inner: F,
+        #[pin]
+        stopper: Option<S>,
+        stopped: Option<S::Output>,
+        make_delay: Option<Mk>,
+        #[pin]
+        delay: Option<D>,
+    }
+}
+
+impl<F, S, Mk, D> Grace<F, S, Mk, D>
+where
+    S: Future,
+{
+    ///Check if the stopper completed and the grace period started
+    pub fn in_grace_period(&self) -> bool { self.stopped.is_some() }
+}
+
+impl<F, S, Mk, D> Future for Grace<F, S, Mk, D>
+where
+    F: Future,
+    S: Future,
+    Mk: FnOnce() -> D,
+    D: Future,
+{
+    type Output = CancellableResult<F::Output, S::Output>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
+        let mut this = self.project();
+
+        //always poll inner future first
+        if let Poll::Ready(t) = this.inner.poll(cx) {
+            return Poll::Ready(CancellableResult::Finished(t));
+        }
+
+        if let Some(stopper) = this.stopper.as_mut().as_pin_mut() {
+            match stopper.poll(cx) {
+                Poll::Pending => return Poll::Pending,
+                Poll::Ready(s) => {
+                    this.stopper.set(None);
+                    *this.stopped = Some(s);
+                    let make_delay = this.make_delay.take().expect("grace period started twice");
+                    this.delay.set(Some(make_delay()));
+                }
+            }
+        }
+
+        match this.delay.as_mut().as_pin_mut().map(|delay| delay.poll(cx)) {
+            Some(Poll::Ready(_)) => {
+                this.delay.set(None);
+                let s = this.stopped.take().expect("grace period elapsed twice");
+                Poll::Ready(CancellableResult::Cancelled(s))
+            }
+            _ => Poll::Pending,
+        }
+    }
+}
+
+impl<F, S, Mk, D> core::fmt::Debug for Grace<F, S, Mk, D>
+where
+    S: Future,
+{
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        f.debug_struct("Grace").field("in_grace_period", &self.in_grace_period()).finish()
+    }
+}
+
+///Cancel `fut` once the delay created by `make_delay` completes, after `stopper` completes
+///
+///The inner future is polled during the grace period, and if it completes
+/// the result is [`Finished`](../enum.CancellableResult.html#variant.Finished)
+pub fn cancel_with_grace<F, S, Mk, D>(fut: F, stopper: S, make_delay: Mk) -> Grace<F, S, Mk, D>
+where
+    F: Future,
+    S: Future,
+    Mk: FnOnce() -> D,
+    D: Future,
+{
+    Grace {
+        inner: fut,
+        stopper: Some(stopper),
+        stopped: None,
+        make_delay: Some(make_delay),
+        delay: None,
+    }
+}
+
+///Result returned by [`Escalate`](struct.Escalate.html)
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Escalated<T, S> {
+    ///If the inner future finished before the signal completed
+    Finished(T),
+
+    ///If the inner future finished after being asked to stop
+    Soft(S, T),
+
+    ///If the inner future didn't finish in time and was dropped
+    Hard(S),
+}
+
+impl<T, S> Escalated<T, S> {
+    ///Check if the signal completed
+    pub fn is_cancelled(&self) -> bool { !matches!(self, Self::Finished(_)) }
+
+    ///Check if the inner future had to be dropped
+    pub fn is_hard(&self) -> bool { matches!(self, Self::Hard(_)) }
+
+    ///Retrieve the result of the future
+    /// if it finished, even after being asked to stop
+    pub fn finished(self) -> Option<T> {
+        match self {
+            Self::Finished(t) | Self::Soft(_, t) => Some(t),
+            Self::Hard(_) => None,
+        }
+    }
+}
+
+pin_project! {
+    ///Future for the [`cancel_with_escalation`](fn.cancel_with_escalation.html) combinator
+    #[must_use = "futures do nothing unless you `.await` or poll them"]
+    pub struct Escalate<F, S, T>
+    where
+        S: Future,
+        T: Timer,
+    {
+        #[pin]
+        inner: F,
+        #[pin]
+        signal: Option<S>,
+        stopped: Option<S::Output>,
+        token: CancellationToken,
+        timer: T,
+        hard_after: Duration,
+        #[pin]
+        sleep: Option<T::Sleep>,
+    }
+}
+
+impl<F, S, T> Future for Escalate<F, S, T>
+where
+    F: Future,
+    S: Future,
+    T: Timer,
+{
+    type Output = Escalated<F::Output, S::Output>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
+        let mut this = self.project();
+
+        loop {
+            //always poll inner future first
+            if let Poll::Ready(t) = this.inner.as_mut().poll(cx) {
+                return Poll::Ready(match this.stopped.take() {
+                    Some(s) => Escalated::Soft(s, t),
+                    None => Escalated::Finished(t),
+                });
+            }
+
+            let signal = match this.signal.as_mut().as_pin_mut() {
+                Some(signal) => signal,
+                None => break,
+            };
+
+            match signal.poll(cx) {
+                Poll::Pending => return Poll::Pending,
+                Poll::Ready(s) => {
+                    this.signal.set(None);
+                    *this.stopped = Some(s);
+                    this.sleep.set(Some(this.timer.sleep(*this.hard_after)));
+                    this.token.cancel();
+                    //give the inner future a chance to notice right away
+                }
+            }
+        }
+
+        match this.sleep.as_mut().as_pin_mut().map(|sleep| sleep.poll(cx)) {
+            Some(Poll::Ready(())) => {
+                this.sleep.set(None);
+                let s = this.stopped.take().expect("escalated twice");
+                Poll::Ready(Escalated::Hard(s))
+            }
+            _ => Poll::Pending,
+        }
+    }
+}
+
+impl<F, S, T> core::fmt::Debug for Escalate<F, S, T>
+where
+    S: Future,
+    T: Timer,
+{
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        f.debug_struct("Escalate")
+            .field("token", &self.token)
+            .field("hard_after", &self.hard_after)
+            .finish()
+    }
+}
+
+///Ask the future created by `make_fut` to stop when `soft_signal` completes,
+/// dropping it if it hasn't completed within `hard_after`, measured with `timer`
+///
+///The future is asked to stop by cancelling the token passed to `make_fut`
+///
+/// # Example
+/// ```rust
+/// # use futures::{executor::block_on, future::ready};
+///  use kyansel::{grace::{cancel_with_escalation, Escalated}, timer::ManualTimer};
+/// #  use std::time::Duration;
+///
+///  //async closures work as well as closures returning a future
+///  let work = async |token: kyansel::token::CancellationToken| {
+///      //flush what was done so far before stopping
+///      token.cancelled().await;
+///      "flushed"
+///  };
+///
+///  let timer = ManualTimer::new();
+///  let result = cancel_with_escalation(work, ready(()), timer, Duration::from_secs(5));
+///
+///  assert_eq!(block_on(result), Escalated::Soft((), "flushed"));
+/// ```
+pub fn cancel_with_escalation<Mk, F, S, T>(
+    make_fut: Mk,
+    soft_signal: S,
+    timer: T,
+    hard_after: Duration,
+) -> Escalate<F, S, T>
+where
+    Mk: FnOnce(CancellationToken) -> F,
+    F: Future,
+    S: Future,
+    T: Timer,
+{
+    let token = CancellationToken::new();
+
+    Escalate {
+        inner: make_fut(token.clone()),
+        signal: Some(soft_signal),
+        stopped: None,
+        token,
+        timer,
+        hard_after,
+        sleep: None,
+    }
+}
@@ -0,0 +1,161 @@
+//! Lifecycle hooks for [`Cancellable`](../struct.Cancellable.html)
+//!
+//![`Cancellable::with_hooks`](../struct.Cancellable.html#method.with_hooks) starts a builder
+//! for synchronous callbacks invoked when the future is polled, finishes or is cancelled,
+//! to plug in logging or accounting without wrapping the whole future.
+//!
+//! # Example
+//! ```rust
+//! # use futures::{executor::block_on, future::{pending, ready}};
+//!  use kyansel::FutureCancellable;
+//!  use std::cell::Cell;
+//!
+//!  let (polls, reason) = (Cell::new(0), Cell::new(None));
+//!
+//!  let cancellable = pending::<()>()
+//!      .cancel_with(ready("shutdown"))
+//!      .with_hooks()
+//!      .on_poll(|| polls.set(polls.get() + 1))
+//!      .on_cancel(|s| reason.set(Some(*s)));
+//!
+//!  assert!(block_on(cancellable).is_cancelled());
+//!  assert_eq!((polls.get(), reason.get()), (1, Some("shutdown")));
+//! ```
+
+use super::{Cancellable, CancellableResult};
+use core::{
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll},
+};
+use pin_project_lite::pin_project;
+
+///Callback invoked with no arguments, like [`on_poll`](struct.Hooked.html#method.on_poll)
+pub trait Hook {
+    ///Invoke the callback
+    fn call(&mut self);
+}
+
+impl<Fun> Hook for Fun
+where
+    Fun: FnMut(),
+{
+    fn call(&mut self) { self() }
+}
+
+///Callback invoked with a reference to a value,
+/// like [`on_finish`](struct.Hooked.html#method.on_finish)
+pub trait HookWith<A: ?Sized> {
+    ///Invoke the callback
+    fn call(&mut self, arg: &A);
+}
+
+impl<A: ?Sized, Fun> HookWith<A> for Fun
+where
+    Fun: FnMut(&A),
+{
+    fn call(&mut self, arg: &A) { self(arg) }
+}
+
+///Hook doing nothing, used for the hooks that aren't set
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Noop;
+
+impl Hook for Noop {
+    fn call(&mut self) {}
+}
+
+impl<A: ?Sized> HookWith<A> for Noop {
+    fn call(&mut self, _: &A) {}
+}
+
+pin_project! {
+    ///[`Cancellable`](../struct.Cancellable.html) with lifecycle hooks
+    ///
+    ///Created with [`Cancellable::with_hooks`](../struct.Cancellable.html#method.with_hooks)
+    #[derive(Debug)]
+    #[must_use = "futures do nothing unless you `.await` or poll them"]
+    pub struct Hooked<F, S, P = Noop, Fi = Noop, C = Noop>
+    where
+        F: Future,
+        S: Future,
+    {
+        #[pin]
+        cancellable: Cancellable<F, S>,
+        on_poll: P,
+        on_finish: Fi,
+        on_cancel: C,
+    }
+}
+
+impl<F, S, P, Fi, C> Hooked<F, S, P, Fi, C>
+where
+    F: Future,
+    S: Future,
+{
+    ///Invoke `hook` every time the future is polled, before polling it
+    pub fn on_poll<P2>(self, hook: P2) -> Hooked<F, S, P2, Fi, C>
+    where
+        P2: FnMut(),
+    {
+        let Self { cancellable, on_finish, on_cancel, .. } = self;
+        Hooked { cancellable, on_poll: hook, on_finish, on_cancel }
+    }
+
+    ///Invoke `hook` with the output of the inner future when it finishes
+    pub fn on_finish<Fi2>(self, hook: Fi2) -> Hooked<F, S, P, Fi2, C>
+    where
+        Fi2: FnMut(&F::Output),
+    {
+        let Self { cancellable, on_poll, on_cancel, .. } = self;
+        Hooked { cancellable, on_poll, on_finish: hook, on_cancel }
+    }
+
+    ///Invoke `hook` with the output of the stopper when the future is cancelled
+    pub fn on_cancel<C2>(self, hook: C2) -> Hooked<F, S, P, Fi, C2>
+    where
+        C2: FnMut(&S::Output),
+    {
+        let Self { cancellable, on_poll, on_finish, .. } = self;
+        Hooked { cancellable, on_poll, on_finish, on_cancel: hook }
+    }
+}
+
+impl<F, S, P, Fi, C> Future for Hooked<F, S, P, Fi, C>
+where
+    F: Future,
+    S: Future,
+    P: Hook,
+    Fi: HookWith<F::Output>,
+    C: HookWith<S::Output>,
+{
+    type Output = CancellableResult<F::Output, S::Output>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
+        let this = self.project();
+
+        this.on_poll.call();
+        let result = this.cancellable.poll(cx);
+
+        match &result {
+            Poll::Ready(CancellableResult::Finished(t)) => this.on_finish.call(t),
+            Poll::Ready(CancellableResult::Cancelled(s)) => this.on_cancel.call(s),
+            Poll::Pending => {}
+        }
+
+        result
+    }
+}
+
+impl<F, S> Cancellable<F, S>
+where
+    F: Future,
+    S: Future,
+{
+    ///Start configuring lifecycle hooks for this future
+    ///
+    ///See the [`hooks`](hooks/index.html) module
+    pub const fn with_hooks(self) -> Hooked<F, S> {
+        Hooked { cancellable: self, on_poll: Noop, on_finish: Noop, on_cancel: Noop }
+    }
+}
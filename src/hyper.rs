@@ -0,0 +1,242 @@
+//! Graceful shutdown of hyper connections
+//!
+//![`GracefulShutdown`](struct.GracefulShutdown.html) bridges a
+//! [`CancellationToken`](../token/struct.CancellationToken.html) to hyper's graceful shutdown:
+//! once the token is cancelled the accept loop stops,
+//! each [`watch`](struct.GracefulShutdown.html#method.watch)ed connection is asked to finish
+//! its in-flight requests, and the ones still running after the grace period are dropped.
+//!
+//!Each watched connection resolves to a [`ConnectionOutcome`](enum.ConnectionOutcome.html)
+//! telling how it ended.
+//!
+//! # Example
+//! ```rust
+//! # use futures::{executor::block_on, future::pending, pin_mut, poll};
+//!  use kyansel::{
+//!      hyper::{ConnectionOutcome, GracefulConnection, GracefulShutdown},
+//!      timer::ManualTimer,
+//!      token::CancellationToken,
+//!  };
+//!  use std::{future::Future, pin::Pin, task::{Context, Poll}, time::Duration};
+//!
+//!  //stands in for `hyper::server::conn::http1::Connection`, finishing once asked to
+//!  struct Conn(bool);
+//!  impl Future for Conn {
+//!      type Output = Result<(), ()>;
+//!      fn poll(self: Pin<&mut Self>, _: &mut Context) -> Poll<Self::Output> {
+//!          if self.0 { Poll::Ready(Ok(())) } else { Poll::Pending }
+//!      }
+//!  }
+//!  impl GracefulConnection for Conn {
+//!      type Error = ();
+//!      fn graceful_shutdown(mut self: Pin<&mut Self>) { self.0 = true; }
+//!  }
+//!
+//!  let token = CancellationToken::new();
+//!  let grace = Duration::from_secs(30);
+//!  let shutdown = GracefulShutdown::new(token.clone(), ManualTimer::new(), grace);
+//!
+//! # block_on(async {
+//!  let conn = shutdown.watch(Conn(false));
+//!  pin_mut!(conn);
+//!  assert!(poll!(conn.as_mut()).is_pending());
+//!
+//!  token.cancel();
+//!  assert!(shutdown.is_shutting_down());
+//!  assert_eq!(conn.await, ConnectionOutcome::Drained(Ok(())));
+//! # });
+//! ```
+
+use super::{
+    timer::Timer,
+    token::{CancellationToken, WaitForCancellation},
+};
+use core::{
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll},
+    time::Duration,
+};
+use hyper::{
+    body::{Body, Incoming},
+    rt::{Read, Write},
+    server::conn::{http1, http2},
+    service::HttpService,
+};
+use pin_project_lite::pin_project;
+use std::boxed::Box;
+
+type BoxError = Box<dyn std::error::Error + Send + Sync>;
+
+///Connection that can be asked to finish its in-flight requests and stop accepting new ones
+///
+///Implemented for hyper's http1 and http2 server connections,
+/// implement it for other connection types, like hyper-util's, to watch them too
+pub trait GracefulConnection: Future<Output = Result<(), Self::Error>> {
+    ///Error of the connection
+    type Error;
+
+    ///Start shutting down the connection gracefully
+    fn graceful_shutdown(self: Pin<&mut Self>);
+}
+
+impl<I, B, S> GracefulConnection for http1::Connection<I, S>
+where
+    S: HttpService<Incoming, ResBody = B>,
+    S::Error: Into<BoxError>,
+    I: Read + Write + Unpin + 'static,
+    B: Body + 'static,
+    B::Error: Into<BoxError>,
+{
+    type Error = hyper::Error;
+
+    fn graceful_shutdown(self: Pin<&mut Self>) { http1::Connection::graceful_shutdown(self) }
+}
+
+impl<I, B, S> GracefulConnection for http1::UpgradeableConnection<I, S>
+where
+    S: HttpService<Incoming, ResBody = B>,
+    S::Error: Into<BoxError>,
+    I: Read + Write + Unpin + Send + 'static,
+    B: Body + 'static,
+    B::Error: Into<BoxError>,
+{
+    type Error = hyper::Error;
+
+    fn graceful_shutdown(self: Pin<&mut Self>) {
+        http1::UpgradeableConnection::graceful_shutdown(self)
+    }
+}
+
+impl<I, B, S, E> GracefulConnection for http2::Connection<I, S, E>
+where
+    S: HttpService<Incoming, ResBody = B>,
+    S::Error: Into<BoxError>,
+    I: Read + Write + Unpin + 'static,
+    B: Body + 'static,
+    B::Error: Into<BoxError>,
+    E: hyper::rt::bounds::Http2ServerConnExec<S::Future, B>,
+{
+    type Error = hyper::Error;
+
+    fn graceful_shutdown(self: Pin<&mut Self>) { http2::Connection::graceful_shutdown(self) }
+}
+
+///How a [`WatchedConnection`](struct.WatchedConnection.html) ended
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum ConnectionOutcome<E> {
+    ///If the connection closed before the shutdown started
+    Closed(Result<(), E>),
+
+    ///If the connection finished its in-flight requests within the grace period
+    Drained(Result<(), E>),
+
+    ///If the connection was still running at the end of the grace period, and was dropped
+    Aborted,
+}
+
+impl<E> ConnectionOutcome<E> {
+    ///Check if the connection was dropped at the end of the grace period
+    pub fn is_aborted(&self) -> bool { matches!(self, Self::Aborted) }
+}
+
+///Shutdown coordinator for hyper connections
+///
+///Cloning it is cheap, all clones follow the same token
+#[derive(Debug, Clone)]
+pub struct GracefulShutdown<T> {
+    token: CancellationToken,
+    timer: T,
+    grace: Duration,
+}
+
+impl<T> GracefulShutdown<T>
+where
+    T: Timer + Clone,
+{
+    ///Shut connections down when `token` is cancelled,
+    /// dropping the ones still running `grace` later, measured with `timer`
+    pub fn new(token: CancellationToken, timer: T, grace: Duration) -> Self {
+        Self { token, timer, grace }
+    }
+
+    ///Check if the shutdown started, and no more connections should be accepted
+    pub fn is_shutting_down(&self) -> bool { self.token.is_cancelled() }
+
+    ///Retrieve a future completing when the shutdown starts, to stop the accept loop
+    pub fn shutting_down(&self) -> WaitForCancellation { self.token.cancelled() }
+
+    ///Drive `conn`, shutting it down gracefully when the token is cancelled
+    pub fn watch<C>(&self, conn: C) -> WatchedConnection<C, T>
+    where
+        C: GracefulConnection,
+    {
+        WatchedConnection {
+            conn,
+            shutdown: self.token.cancelled(),
+            timer: self.timer.clone(),
+            grace: self.grace,
+            deadline: None,
+        }
+    }
+}
+
+pin_project! {
+    ///Future driving a connection through a graceful shutdown
+    ///
+    ///Created with [`GracefulShutdown::watch`](struct.GracefulShutdown.html#method.watch)
+    #[must_use = "futures do nothing unless you `.await` or poll them"]
+    pub struct WatchedConnection<C, T>
+    where
+        T: Timer,
+    {
+        #[pin]
+        conn: C,
+        shutdown: WaitForCancellation,
+        timer: T,
+        grace: Duration,
+        #[pin]
+        deadline: Option<T::Sleep>,
+    }
+}
+
+impl<C, T> Future for WatchedConnection<C, T>
+where
+    C: GracefulConnection,
+    T: Timer,
+{
+    type Output = ConnectionOutcome<C::Error>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
+        let mut this = self.project();
+
+        if this.deadline.is_none() && Pin::new(this.shutdown).poll(cx).is_ready() {
+            this.conn.as_mut().graceful_shutdown();
+            this.deadline.set(Some(this.timer.sleep(*this.grace)));
+        }
+
+        if let Poll::Ready(result) = this.conn.poll(cx) {
+            return Poll::Ready(match this.deadline.is_some() {
+                true => ConnectionOutcome::Drained(result),
+                false => ConnectionOutcome::Closed(result),
+            });
+        }
+
+        match this.deadline.as_pin_mut().map(|deadline| deadline.poll(cx)) {
+            Some(Poll::Ready(())) => Poll::Ready(ConnectionOutcome::Aborted),
+            _ => Poll::Pending,
+        }
+    }
+}
+
+impl<C, T> core::fmt::Debug for WatchedConnection<C, T>
+where
+    T: Timer,
+{
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        f.debug_struct("WatchedConnection")
+            .field("grace", &self.grace)
+            .field("draining", &self.deadline.is_some())
+            .finish()
+    }
+}
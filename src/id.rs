@@ -0,0 +1,161 @@
+//! Identifiers correlating cancellables across logs
+//!
+//!A [`CancelId`](struct.CancelId.html) is unique within the process. Attaching one to a
+//! cancellable, with [`Cancellable::identified`](../struct.Cancellable.html#method.identified),
+//! returns it along with the result, and with the `tracing` feature it's recorded in the event
+//! emitted on completion, so the canceller side can log the same id it was handed.
+//!
+//![`Scope::spawn`](../scope/struct.Scope.html#method.spawn) returns the id of the spawned future
+//!
+//! # Example
+//! ```rust
+//! # use futures::{executor::block_on, future::{pending, ready}};
+//!  use kyansel::FutureCancellable;
+//!
+//!  let cancellable = pending::<()>().cancel_with(ready(())).identified();
+//!  let id = cancellable.id();
+//!
+//!  let (finished_id, result) = block_on(cancellable);
+//!  assert_eq!(finished_id, id);
+//!  assert!(result.is_cancelled());
+//! ```
+
+use super::{Cancellable, CancellableResult};
+use core::{
+    fmt,
+    future::Future,
+    num::NonZeroU64,
+    pin::Pin,
+    sync::atomic::{AtomicU64, Ordering},
+    task::{Context, Poll},
+};
+use pin_project_lite::pin_project;
+
+///Identifier of a cancellable, unique within the process
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize), serde(transparent))]
+pub struct CancelId(NonZeroU64);
+
+impl CancelId {
+    ///Create a new identifier, different from all the others created with this function
+    pub fn next() -> Self {
+        static NEXT: AtomicU64 = AtomicU64::new(1);
+
+        //a u64 doesn't overflow in the lifetime of a process
+        Self(NonZeroU64::new(NEXT.fetch_add(1, Ordering::Relaxed)).unwrap())
+    }
+
+    ///Create an identifier from a value assigned elsewhere, like a request id
+    ///
+    ///Uniqueness is then up to the caller
+    pub const fn from_raw(id: NonZeroU64) -> Self { Self(id) }
+
+    ///Retrieve the numeric value of the identifier
+    pub const fn get(self) -> u64 { self.0.get() }
+}
+
+impl fmt::Display for CancelId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result { self.0.fmt(f) }
+}
+
+pin_project! {
+    ///[`Cancellable`](../struct.Cancellable.html) returning its [`CancelId`](struct.CancelId.html)
+    /// along with its result
+    ///
+    ///Created with [`Cancellable::identified`](../struct.Cancellable.html#method.identified)
+    /// or [`Cancellable::with_id`](../struct.Cancellable.html#method.with_id)
+    #[must_use = "futures do nothing unless you `.await` or poll them"]
+    pub struct Identified<F, S>
+    where
+        F: Future,
+        S: Future,
+    {
+        #[pin]
+        cancellable: Cancellable<F, S>,
+        id: CancelId,
+    }
+}
+
+impl<F, S> Identified<F, S>
+where
+    F: Future,
+    S: Future,
+{
+    ///Retrieve the identifier of the cancellable
+    pub const fn id(&self) -> CancelId { self.id }
+}
+
+impl<F, S> Future for Identified<F, S>
+where
+    F: Future,
+    S: Future,
+{
+    type Output = (CancelId, CancellableResult<F::Output, S::Output>);
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
+        let this = self.project();
+        #[cfg(any(feature = "log", feature = "tracing"))]
+        let name = this.cancellable.name;
+        let projection = this.cancellable.project();
+
+        //same order as `Cancellable`, but tracing the id
+        let result = match projection.inner.poll(cx) {
+            Poll::Ready(t) => CancellableResult::Finished(t),
+            Poll::Pending => match projection.stopper.poll(cx) {
+                Poll::Ready(s) => CancellableResult::Cancelled(s),
+                Poll::Pending => return Poll::Pending,
+            },
+        };
+
+        #[cfg(any(feature = "log", feature = "tracing"))]
+        trace_id(*this.id, name, if result.is_cancelled() { "cancelled" } else { "finished" });
+
+        Poll::Ready((*this.id, result))
+    }
+}
+
+#[cfg(any(feature = "log", feature = "tracing"))]
+pub(crate) fn trace_id(id: CancelId, name: Option<&'static str>, outcome: &'static str) {
+    #[cfg(feature = "tracing")]
+    ::tracing::debug!(id = id.get(), name, outcome, "cancellable completed");
+
+    #[cfg(feature = "log")]
+    {
+        let level = super::log_level(outcome);
+        match name {
+            Some(name) => ::log::log!(level, "cancellable #{} {} {}", id, name, outcome),
+            None => ::log::log!(level, "cancellable #{} {}", id, outcome),
+        }
+    }
+}
+
+impl<F, S> fmt::Debug for Identified<F, S>
+where
+    F: Future + fmt::Debug,
+    S: Future + fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Identified")
+            .field("cancellable", &self.cancellable)
+            .field("id", &self.id)
+            .finish()
+    }
+}
+
+impl<F, S> Cancellable<F, S>
+where
+    F: Future,
+    S: Future,
+{
+    ///Attach a new [`CancelId`](id/struct.CancelId.html), returned along with the result
+    ///
+    ///See the [`id`](id/index.html) module
+    pub fn identified(self) -> Identified<F, S> { self.with_id(CancelId::next()) }
+
+    ///Attach `id`, returned along with the result
+    ///
+    ///See the [`id`](id/index.html) module
+    pub const fn with_id(self, id: CancelId) -> Identified<F, S> {
+        Identified { cancellable: self, id }
+    }
+}
@@ -0,0 +1,287 @@
+//! Cancelling CPU-bound iteration
+//!
+//!Code crunching through an iterator never reaches an `.await`, so it can't be cancelled
+//! and it doesn't let other tasks run until it's done.
+//![`cancellable_iter`](fn.cancellable_iter.html) turns the loop into a future, running its body
+//! for a few items at a time: in between it checks the stopper and yields to the executor.
+//!
+//!Loops that aren't driven by an iterator can use `core::iter::from_fn` or `core::iter::repeat`.
+//!
+//!Batch jobs that need a deterministic granularity can use
+//! [`cancellable_chunks`](fn.cancellable_chunks.html) instead, checking the stopper after each
+//! chunk of a slice, or [`cancellable_stream_chunks`](fn.cancellable_stream_chunks.html) for
+//! chunks coming from a stream: when cancelled they report how many chunks were processed.
+//!
+//! # Example
+//! ```rust
+//! # use futures::{executor::block_on, future::{pending, ready}};
+//!  use kyansel::iter::cancellable_iter;
+//!
+//!  let mut sum = 0u64;
+//!  let crunch = cancellable_iter(0..100_000, |i| sum += i, pending::<()>(), 1000);
+//!  assert!(!block_on(crunch).is_cancelled());
+//!  assert_eq!(sum, 4_999_950_000);
+//!
+//!  //never ends, but checks the stopper every 1000 items
+//!  let crunch = cancellable_iter(0.., |i: u64| assert!(i < 1000), ready(()), 1000);
+//!  assert!(block_on(crunch).is_cancelled());
+//! ```
+
+use super::CancellableResult;
+use core::{
+    future::Future,
+    iter::Peekable,
+    pin::Pin,
+    slice::Chunks,
+    task::{Context, Poll},
+};
+use futures_core::Stream;
+use pin_project_lite::pin_project;
+
+pin_project! {
+    ///Future for the [`cancellable_iter`](fn.cancellable_iter.html) combinator
+    #[must_use = "futures do nothing unless you `.await` or poll them"]
+    pub struct CancellableIter<I, B, S> {
+        iter: I,
+        body: B,
+        #[pin]
+        stopper: S,
+        every: usize,
+    }
+}
+
+impl<I, B, S> Future for CancellableIter<I, B, S>
+where
+    I: Iterator,
+    B: FnMut(I::Item),
+    S: Future,
+{
+    type Output = CancellableResult<(), S::Output>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
+        let this = self.project();
+
+        for _ in 0..*this.every {
+            match this.iter.next() {
+                Some(item) => (this.body)(item),
+                None => return Poll::Ready(CancellableResult::Finished(())),
+            }
+        }
+
+        match this.stopper.poll(cx) {
+            Poll::Ready(s) => Poll::Ready(CancellableResult::Cancelled(s)),
+            Poll::Pending => {
+                //let other tasks run before the next batch
+                cx.waker().wake_by_ref();
+                Poll::Pending
+            }
+        }
+    }
+}
+
+impl<I, B, S> core::fmt::Debug for CancellableIter<I, B, S>
+where
+    I: core::fmt::Debug,
+    S: core::fmt::Debug,
+{
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        f.debug_struct("CancellableIter")
+            .field("iter", &self.iter)
+            .field("stopper", &self.stopper)
+            .field("every", &self.every)
+            .finish()
+    }
+}
+
+///Run `body` on every item of `iter`, checking `stopper` and yielding to the executor
+/// every `every` items
+///
+///The iteration stops at the first check after `stopper` completes
+///
+/// # Panics
+///Panics if `every` is 0
+pub fn cancellable_iter<I, B, S>(
+    iter: I,
+    body: B,
+    stopper: S,
+    every: usize,
+) -> CancellableIter<I::IntoIter, B, S>
+where
+    I: IntoIterator,
+    B: FnMut(I::Item),
+    S: Future,
+{
+    assert!(every > 0, "the stopper must be checked at least every item");
+    CancellableIter { iter: iter.into_iter(), body, stopper, every }
+}
+
+pin_project! {
+    ///Future for the [`cancellable_chunks`](fn.cancellable_chunks.html) combinator
+    #[must_use = "futures do nothing unless you `.await` or poll them"]
+    pub struct CancellableChunks<'a, T, B, S> {
+        chunks: Peekable<Chunks<'a, T>>,
+        body: B,
+        #[pin]
+        stopper: S,
+        done: usize,
+    }
+}
+
+impl<'a, T, B, S> Future for CancellableChunks<'a, T, B, S>
+where
+    B: FnMut(&'a [T]),
+    S: Future,
+{
+    type Output = CancellableResult<usize, (usize, S::Output)>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
+        let this = self.project();
+
+        if let Some(chunk) = this.chunks.next() {
+            (this.body)(chunk);
+            *this.done += 1;
+        }
+
+        //a stopper completing during the last chunk doesn't discard the whole job
+        if this.chunks.peek().is_none() {
+            return Poll::Ready(CancellableResult::Finished(*this.done));
+        }
+
+        match this.stopper.poll(cx) {
+            Poll::Ready(s) => Poll::Ready(CancellableResult::Cancelled((*this.done, s))),
+            Poll::Pending => {
+                //let other tasks run before the next chunk
+                cx.waker().wake_by_ref();
+                Poll::Pending
+            }
+        }
+    }
+}
+
+impl<T, B, S> core::fmt::Debug for CancellableChunks<'_, T, B, S>
+where
+    T: core::fmt::Debug,
+    S: core::fmt::Debug,
+{
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        f.debug_struct("CancellableChunks")
+            .field("chunks", &self.chunks)
+            .field("stopper", &self.stopper)
+            .field("done", &self.done)
+            .finish()
+    }
+}
+
+///Run `body` on the chunks of `input`, `chunk_size` items long, checking `stopper` and yielding
+/// to the executor after each chunk
+///
+///Resolves with the number of chunks processed, along with the output of the stopper
+/// if it completed first
+///
+/// # Example
+/// ```rust
+/// # use futures::{executor::block_on, future::ready};
+///  use kyansel::{iter::cancellable_chunks, CancellableResult};
+///
+///  let rows = [1, 2, 3, 4, 5, 6, 7];
+///  let mut sum = 0;
+///
+///  let job = cancellable_chunks(&rows, 3, |chunk| sum += chunk.iter().sum::<i32>(), ready(()));
+///
+///  //the stopper is checked after the first chunk
+///  assert_eq!(block_on(job), CancellableResult::Cancelled((1, ())));
+///  assert_eq!(sum, 6);
+/// ```
+///
+/// # Panics
+///Panics if `chunk_size` is 0
+pub fn cancellable_chunks<T, B, S>(
+    input: &[T],
+    chunk_size: usize,
+    body: B,
+    stopper: S,
+) -> CancellableChunks<'_, T, B, S>
+where
+    B: FnMut(&[T]),
+    S: Future,
+{
+    CancellableChunks { chunks: input.chunks(chunk_size).peekable(), body, stopper, done: 0 }
+}
+
+pin_project! {
+    ///Future for the [`cancellable_stream_chunks`](fn.cancellable_stream_chunks.html) combinator
+    #[must_use = "futures do nothing unless you `.await` or poll them"]
+    pub struct CancellableStreamChunks<St, B, S> {
+        #[pin]
+        stream: St,
+        body: B,
+        #[pin]
+        stopper: S,
+        done: usize,
+    }
+}
+
+impl<St, B, S> Future for CancellableStreamChunks<St, B, S>
+where
+    St: Stream,
+    B: FnMut(St::Item),
+    S: Future,
+{
+    type Output = CancellableResult<usize, (usize, S::Output)>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
+        let this = self.project();
+
+        let processed = match this.stream.poll_next(cx) {
+            Poll::Ready(Some(chunk)) => {
+                (this.body)(chunk);
+                *this.done += 1;
+                true
+            }
+            Poll::Ready(None) => return Poll::Ready(CancellableResult::Finished(*this.done)),
+            Poll::Pending => false,
+        };
+
+        match this.stopper.poll(cx) {
+            Poll::Ready(s) => Poll::Ready(CancellableResult::Cancelled((*this.done, s))),
+            Poll::Pending => {
+                //let other tasks run before the next chunk
+                if processed {
+                    cx.waker().wake_by_ref();
+                }
+                Poll::Pending
+            }
+        }
+    }
+}
+
+impl<St, B, S> core::fmt::Debug for CancellableStreamChunks<St, B, S>
+where
+    St: core::fmt::Debug,
+    S: core::fmt::Debug,
+{
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        f.debug_struct("CancellableStreamChunks")
+            .field("stream", &self.stream)
+            .field("stopper", &self.stopper)
+            .field("done", &self.done)
+            .finish()
+    }
+}
+
+///Run `body` on each chunk yielded by `stream`, checking `stopper` after each chunk,
+/// and while waiting for the next one
+///
+///Resolves like [`cancellable_chunks`](fn.cancellable_chunks.html)
+pub fn cancellable_stream_chunks<St, B, S>(
+    stream: St,
+    body: B,
+    stopper: S,
+) -> CancellableStreamChunks<St, B, S>
+where
+    St: Stream,
+    B: FnMut(St::Item),
+    S: Future,
+{
+    CancellableStreamChunks { stream, body, stopper, done: 0 }
+}
@@ -0,0 +1,159 @@
+//! Latest-wins keyed work
+//!
+//! [`KeyedLatest`](struct.KeyedLatest.html) holds in-flight futures by key:
+//! inserting work for a key cancels the work already running for the same key.
+//!
+//! The container is a `Stream` of `(key, result)` pairs, where cancelled work
+//! shows up as [`CancellableResult::Cancelled`](../enum.CancellableResult.html#variant.Cancelled)
+//!
+//! # Example
+//! ```rust
+//! # use futures::{executor::block_on, future::{pending, ready, Either}, StreamExt};
+//!  use kyansel::{keyed::KeyedLatest, CancellableResult};
+//!
+//!  let mut searches = KeyedLatest::new();
+//!
+//!  //the first search will never finish, but it's superseded by the second one
+//!  searches.insert("query", Either::Left(pending()));
+//!  searches.insert("query", Either::Right(ready("results")));
+//!
+//!  let mut results = block_on(searches.collect::<Vec<_>>());
+//!  results.sort_by_key(|(_, result)| result.is_cancelled());
+//!
+//!  assert_eq!(results, vec![
+//!      ("query", CancellableResult::Finished("results")),
+//!      ("query", CancellableResult::Cancelled(())),
+//!  ]);
+//! ```
+
+use super::{
+    tasks::{Task, Tasks},
+    CancellableResult,
+};
+use core::{
+    future::Future,
+    hash::Hash,
+    pin::Pin,
+    task::{Context, Poll},
+};
+use futures_core::Stream;
+use pin_project_lite::pin_project;
+use std::collections::HashMap;
+
+pin_project! {
+    ///Container of futures where only the latest future inserted for a given key is kept running
+    #[must_use = "streams do nothing unless polled"]
+    pub struct KeyedLatest<K, F>
+    where
+        F: Future,
+    {
+        latest: HashMap<K, Task<(), F>>,
+        superseded: Tasks<K, F>,
+    }
+}
+
+impl<K, F> KeyedLatest<K, F>
+where
+    K: Hash + Eq,
+    F: Future,
+{
+    ///Create an empty container
+    pub fn new() -> Self { Self { latest: HashMap::new(), superseded: Tasks::new() } }
+
+    ///Insert work for `key`, cancelling any in-flight work for the same key
+    pub fn insert(&mut self, key: K, fut: F) {
+        self.cancel(&key);
+
+        self.latest.insert(key, Task::new((), fut));
+    }
+
+    ///Cancel the in-flight work for `key`, if any
+    ///
+    ///Returns `true` if there was work to cancel
+    pub fn cancel(&mut self, key: &K) -> bool {
+        match self.latest.remove_entry(key) {
+            Some((key, task)) => {
+                task.cancel();
+                self.superseded.push(task.with_data(key));
+                true
+            }
+            None => false,
+        }
+    }
+
+    ///Cancel all the in-flight work
+    pub fn cancel_all(&mut self) {
+        for (key, task) in self.latest.drain() {
+            task.cancel();
+            self.superseded.push(task.with_data(key));
+        }
+    }
+
+    ///Check if there is in-flight work for `key`
+    pub fn contains_key(&self, key: &K) -> bool { self.latest.contains_key(key) }
+
+    ///Number of futures in the container, including cancelled ones not yet yielded
+    pub fn len(&self) -> usize { self.latest.len() + self.superseded.len() }
+
+    ///Check if the container has no futures left
+    pub fn is_empty(&self) -> bool { self.len() == 0 }
+}
+
+impl<K, F> Default for KeyedLatest<K, F>
+where
+    K: Hash + Eq,
+    F: Future,
+{
+    fn default() -> Self { Self::new() }
+}
+
+impl<K, F> Stream for KeyedLatest<K, F>
+where
+    K: Hash + Eq,
+    F: Future,
+{
+    type Item = (K, CancellableResult<F::Output, ()>);
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+
+        if this.is_empty() {
+            return Poll::Ready(None);
+        }
+
+        if let Poll::Ready(Some(next)) = this.superseded.poll_next(cx) {
+            return Poll::Ready(Some(next));
+        }
+
+        //the ready task is taken out of the map along with its key
+        let mut result = None;
+        let ready = this
+            .latest
+            .extract_if(|_, task| match task.poll(cx) {
+                Poll::Ready(t) => {
+                    result = Some(t);
+                    true
+                }
+                Poll::Pending => false,
+            })
+            .next();
+
+        match (ready, result) {
+            (Some((key, _)), Some(result)) => Poll::Ready(Some((key, result))),
+            _ => Poll::Pending,
+        }
+    }
+}
+
+impl<K, F> core::fmt::Debug for KeyedLatest<K, F>
+where
+    K: core::fmt::Debug,
+    F: Future,
+{
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        f.debug_list()
+            .entries(self.latest.keys().map(|key| (key, false)))
+            .entries(self.superseded.iter().map(|task| (&task.data, true)))
+            .finish()
+    }
+}
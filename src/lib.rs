@@ -1,7 +1,79 @@
 //!This library adds a combinator for futures, enabling a future to be
 //! cancelled when another one has completed succesfully.
 //!
-//!Support for futures 0.1 can be enabled with the `futures_01` feature
+//!Support for futures 0.1 can be enabled with the `futures_01` feature, which requires `std`
+//!
+//!Integration with the tokio runtime can be enabled with the `tokio` feature,
+//! stoppers for OS signals with the `tokio-signal` feature,
+//! graceful cancellation of child processes with the `tokio-process` feature
+//! and graceful shutdown of TCP servers with the `tokio-net` feature.
+//!The `tokio` feature also provides a shutdown coordinator reporting how each task ended,
+//! with reports serializable through the `serde` feature.
+//!Stoppers for Unix signals that don't need tokio can be enabled with the `signal-hook` feature,
+//! and cancelling a token on Ctrl-C from synchronous programs, or running `main` under such a
+//! token, with the `ctrlc` feature.
+//!Operators can cancel named jobs over a control socket with the `control` feature
+//!
+//!Time-based cancellation on async-std can be enabled with the `async-std` feature,
+//! and for smol and other executors with the `async-io` feature.
+//!The `futures-timer` feature provides the same helpers without committing to any runtime
+//!Code that needs deadlines but shouldn't pick a runtime can use the [`timer`](timer/index.html)
+//! module instead, with a [`Timer`](timer/trait.Timer.html) implemented for each of them
+//!
+//!The combinator itself only needs `core`: disabling the default `std` feature makes the crate
+//! `no_std`, leaving out tokens, scopes and the other helpers that need an allocator.
+//!The [`cleanup`](cleanup/index.html), [`fair`](fair/index.html),
+//! [`fast_path`](fast_path/index.html), [`flatten`](flatten/index.html),
+//! [`fuse`](fuse/index.html), [`hooks`](hooks/index.html), [`iter`](iter/index.html),
+//! [`map`](map/index.html), [`op`](op/index.html) and [`retry`](retry/index.html) modules
+//! are still available, and so is [`timer`](timer/index.html) apart from `Jittered` and
+//! `ManualTimer`.
+//!Stoppers for embassy-based firmware can be enabled with the `embassy` feature,
+//! which doesn't need `std` either
+//!
+//!Operations that need a real cancellation request instead of being dropped, like io_uring ones,
+//! can hook into cancellation with the [`op`](op/index.html) module
+//!
+//!The extension traits and common types of every enabled feature can be imported at once
+//! from the [`prelude`](prelude/index.html)
+//!
+//!A C interface to cancellation tokens, for C and C++ hosts, can be enabled with the `ffi` feature,
+//! and file descriptors becoming readable on cancellation, for `epoll` loops, with the `fd` feature
+//!
+//!Mapping between asyncio and kyansel cancellation, for Rust futures driven by Python coroutines,
+//! can be enabled with the `pyo3` feature
+//!
+//!Interop with the browser's `AbortSignal` and time-based cancellation using JS timers
+//! can be enabled with the `wasm` feature
+//!
+//!Desktop apps built on `winit` can cancel background work when their windows close
+//! with the `winit` feature
+//!
+//!Utilities to test cancellation deterministically, without a runtime,
+//! can be enabled with the `testing` feature, and adapters for `futures-test`
+//! with the `futures-test` feature
+//!
+//!Spans and events for cancellable futures can be enabled with the `tracing` feature,
+//! records of named futures completing through the `log` facade with the `log` feature,
+//! counters of finished and cancelled futures with the `metrics` feature
+//! and the location and backtrace of where a token was cancelled with the `debug-cause` feature
+//!
+//!Parallel jobs on rayon, stopping when a token is cancelled,
+//! can be enabled with the `rayon` feature
+//!
+//!Stoppers combined with `futures-concurrency` can be enabled with the `futures-concurrency`
+//! feature, and channel receivers as stoppers with the `flume` and `async-channel` features
+//!
+//!Tower middleware cancelling requests can be enabled with the `tower` feature,
+//! graceful shutdown of hyper connections with the `hyper` feature,
+//! gRPC deadlines with the `tonic` feature
+//! and cancellation of outgoing reqwest requests with the `reqwest` feature
+//!
+//!The [`#[cancellable]`](attr/attr.cancellable.html) attribute, making an `async fn` cancellable
+//! by a token at every await point, can be enabled with the `macros` feature
+//!
+//!`Arbitrary` implementations for the results of the combinators, for property-based tests,
+//! can be enabled with the `proptest` and `quickcheck` features
 //!
 //! # Example
 //! ```rust
@@ -9,13 +81,12 @@
 //! # async fn main() -> Result<(), Box<dyn std::error::Error>> {
 //! #    use futures::future::{join, ready};
 //!  use kyansel::cancellable;
-//! #    use tokio::{sync::oneshot, timer::delay};
+//! #    use tokio::{sync::oneshot, time::sleep};
 //!
 //!  let (tx, rx) = oneshot::channel::<()>();
 //!     
 //!  //simulate a long future
-//!  let future =
-//!      delay(tokio::clock::now() + std::time::Duration::from_secs(1));
+//!  let future = sleep(std::time::Duration::from_secs(1));
 //!
 //!  //make it cancellable
 //!  let cancellable = cancellable(future, rx);
@@ -38,17 +109,205 @@
 //! # }
 //! ```
 
+//the crate is always `no_std`, `std` is only linked for the helpers that need it:
+//CI builds with `--no-default-features` for `thumbv7em-none-eabihf` to keep it that way
+#![no_std]
+
+#[cfg(feature = "std")]
+extern crate std;
+
 use core::{
     future::Future,
     pin::Pin,
     task::{Context, Poll},
 };
+#[cfg(feature = "std")]
+use std::boxed::Box;
+
+#[cfg(any(feature = "proptest", feature = "quickcheck"))]
+mod arbitrary;
+
+#[cfg(feature = "async-channel")]
+pub mod async_channel;
+
+#[cfg(feature = "async-io")]
+pub mod async_io;
+
+#[cfg(feature = "async-std")]
+pub mod async_std;
+
+#[cfg(feature = "macros")]
+pub mod attr;
+
+pub mod cleanup;
+
+#[cfg(feature = "control")]
+pub mod control;
+
+#[cfg(feature = "ctrlc")]
+pub mod ctrlc;
+
+#[cfg(feature = "embassy")]
+pub mod embassy;
+
+pub mod fair;
+
+pub mod fast_path;
+
+#[cfg(all(unix, feature = "fd"))]
+pub mod fd;
+
+#[cfg(feature = "ffi")]
+pub mod ffi;
+
+pub mod flatten;
+
+#[cfg(feature = "flume")]
+pub mod flume;
+
+pub mod fuse;
+
+#[cfg(feature = "futures-concurrency")]
+pub mod futures_concurrency;
 
 #[cfg(feature = "futures_01")]
 pub mod futures_01;
 
+#[cfg(feature = "futures-timer")]
+pub mod futures_timer;
+
+pub mod hooks;
+
+#[cfg(feature = "hyper")]
+pub mod hyper;
+
+pub mod iter;
+
+mod macros;
+
+pub mod map;
+
+#[cfg(feature = "metrics")]
+pub mod metrics;
+
+pub mod op;
+
+pub mod prelude;
+
 mod projection;
 
+pub mod retry;
+
+mod rng;
+
+#[cfg(feature = "std")]
+pub mod bounded;
+#[cfg(feature = "std")]
+pub mod budget;
+#[cfg(feature = "std")]
+pub mod checkpoint;
+#[cfg(feature = "std")]
+pub mod detach;
+#[cfg(feature = "std")]
+pub mod erased;
+#[cfg(feature = "std")]
+pub mod frame;
+#[cfg(feature = "std")]
+pub mod grace;
+#[cfg(feature = "std")]
+pub mod id;
+#[cfg(feature = "std")]
+pub mod keyed;
+#[cfg(feature = "std")]
+pub mod local;
+#[cfg(feature = "std")]
+pub mod pause;
+#[cfg(feature = "std")]
+pub mod pressure;
+#[cfg(feature = "std")]
+pub mod priority;
+#[cfg(feature = "std")]
+pub mod race;
+#[cfg(feature = "std")]
+pub mod scope;
+#[cfg(feature = "std")]
+pub mod singleflight;
+#[cfg(feature = "std")]
+pub mod split;
+#[cfg(feature = "std")]
+pub mod stall;
+#[cfg(feature = "std")]
+pub mod sync;
+#[cfg(feature = "std")]
+mod tasks;
+#[cfg(feature = "std")]
+pub mod token;
+#[cfg(feature = "std")]
+mod wake;
+#[cfg(feature = "std")]
+pub mod watchdog;
+
+pub mod timer;
+
+#[cfg(feature = "tokio-net")]
+pub mod net;
+
+#[cfg(feature = "tokio-process")]
+pub mod process;
+
+#[cfg(feature = "pyo3")]
+pub mod pyo3;
+
+#[cfg(feature = "rayon")]
+pub mod rayon;
+
+#[cfg(feature = "reqwest")]
+pub mod reqwest;
+
+#[cfg(feature = "tokio")]
+pub mod shutdown;
+
+#[cfg(all(unix, feature = "signal-hook"))]
+pub mod signal_hook;
+
+#[cfg(feature = "tokio-signal")]
+pub mod signals;
+
+#[cfg(feature = "testing")]
+pub mod testing;
+
+#[cfg(feature = "tokio")]
+pub mod tokio;
+
+#[cfg(feature = "tonic")]
+pub mod tonic;
+
+#[cfg(feature = "tower")]
+pub mod tower;
+
+#[cfg(feature = "tracing")]
+pub mod tracing;
+
+#[cfg(feature = "wasm")]
+pub mod wasm;
+
+#[cfg(feature = "winit")]
+pub mod winit;
+
+#[cfg(feature = "macros")]
+#[doc(hidden)]
+pub mod __private {
+    use core::future::Future;
+
+    //pins down the output of async blocks, so `?` in them infers the right error
+    pub fn output<T, F>(fut: F) -> F
+    where
+        F: Future<Output = T>,
+    {
+        fut
+    }
+}
+
 ///Future for the [`cancel_with`](trait.FutureCancellable.html#method.cancel_with) combinator,
 ///allowing a computation to be cancelled if a second computation completes succesfully.
 ///
@@ -57,7 +316,30 @@ mod projection;
 ///
 ///Created with [`FutureCancellable::cancel_with`](trait.FutureCancellable.html#method.cancel_with)
 /// or [`cancellable`](fn.cancellable.html)
-#[derive(Debug)]
+///
+/// # Layout
+///`Cancellable` adds no overhead over the two futures it holds besides padding
+/// and the optional [`name`](struct.Cancellable.html#method.named), a `&'static str`.
+///A zero-sized stopper, like `core::future::Pending`, adds nothing on top of those,
+/// and [`CancellationToken::cancelled`](token/struct.CancellationToken.html#method.cancelled)
+/// adds the size of its future: two words on 64-bit targets, three on 32-bit ones.
+///
+/// # Cloning
+///`Cancellable` is `Clone` when both futures are, so a template can be built once and cloned
+/// for each attempt, like for hedged requests or retries.
+///Cloning a future already polled clones its current state, like the futures it holds do
+///
+/// ```rust
+/// # use futures::{executor::block_on, future::ready};
+///  use kyansel::{token::CancellationToken, FutureCancellable};
+///
+///  let token = CancellationToken::new();
+///  let template = ready(42).cancel_with(token.cancelled());
+///
+///  assert_eq!(block_on(template.clone()).finished(), Some(42));
+///  assert_eq!(block_on(template).finished(), Some(42));
+/// ```
+#[derive(Debug, Clone)]
 #[must_use = "futures do nothing unless you `.await` or poll them"]
 pub struct Cancellable<F, S>
 where
@@ -66,6 +348,85 @@ where
 {
     inner: F,
     stopper: S,
+    name: Option<&'static str>,
+}
+
+//the layout guarantees documented on `Cancellable`
+const _: () = {
+    use core::{
+        future::{Pending, Ready},
+        mem::size_of,
+    };
+
+    type Name = Option<&'static str>;
+
+    assert!(
+        size_of::<Cancellable<Ready<u64>, Pending<()>>>() == size_of::<(Ready<u64>, Name)>()
+    );
+
+    //the same as the pair of futures, whatever the pointer width
+    #[cfg(feature = "std")]
+    assert!(
+        size_of::<Cancellable<Ready<u64>, token::WaitForCancellation>>()
+            == size_of::<(Ready<u64>, token::WaitForCancellation, Name)>()
+    );
+};
+
+impl<F, S> Cancellable<F, S>
+where
+    F: Future,
+    S: Future,
+{
+    ///Attach a name to this future, for diagnostics
+    ///
+    ///The name shows up in the `Debug` output, in the events emitted
+    /// when the future completes with the `tracing` or `log` feature,
+    /// and in [`Scope::running`](scope/struct.Scope.html#method.running) for the futures
+    /// spawned with [`Scope::spawn_named`](scope/struct.Scope.html#method.spawn_named)
+    ///
+    /// # Example
+    /// ```rust
+    /// # use futures::future::pending;
+    ///  use kyansel::FutureCancellable;
+    ///
+    ///  let job = pending::<()>().cancel_with(pending::<()>()).named("sync-job");
+    ///
+    ///  assert!(format!("{:?}", job).contains("sync-job"));
+    /// ```
+    pub const fn named(mut self, name: &'static str) -> Self {
+        self.name = Some(name);
+        self
+    }
+
+    ///Retrieve the name attached with [`named`](struct.Cancellable.html#method.named)
+    pub const fn name(&self) -> Option<&'static str> { self.name }
+
+    ///Poll the future exactly once with a no-op waker,
+    /// returning the result if either the inner future or the stopper was ready
+    ///
+    ///No wakeup is registered, so a `None` should be followed by a real poll
+    /// or dropping the future. Once `Some` is returned the future must not be polled again
+    ///
+    /// # Example
+    /// ```rust
+    /// # use futures::future::{pending, ready};
+    ///  use kyansel::{CancellableResult, FutureCancellable};
+    ///  use std::pin::pin;
+    ///
+    ///  let mut waiting = pin!(pending::<()>().cancel_with(pending::<()>()));
+    ///  assert_eq!(waiting.as_mut().try_finish_now(), None);
+    ///
+    ///  let mut cached = pin!(ready("hit").cancel_with(pending::<()>()));
+    ///  assert_eq!(cached.as_mut().try_finish_now(), Some(CancellableResult::Finished("hit")));
+    /// ```
+    pub fn try_finish_now(self: Pin<&mut Self>) -> Option<CancellableResult<F::Output, S::Output>> {
+        let mut cx = Context::from_waker(core::task::Waker::noop());
+
+        match self.poll(&mut cx) {
+            Poll::Ready(result) => Some(result),
+            Poll::Pending => None,
+        }
+    }
 }
 
 #[derive(Debug, PartialEq, Eq, Clone, Copy)]
@@ -80,12 +441,7 @@ pub enum CancellableResult<T, S> {
 
 impl<T, S> CancellableResult<T, S> {
     ///Check if the future was cancelled
-    pub fn is_cancelled(&self) -> bool {
-        match self {
-            Self::Cancelled(_) => true,
-            _ => false,
-        }
-    }
+    pub fn is_cancelled(&self) -> bool { matches!(self, Self::Cancelled(_)) }
 
     ///Retrieve the result of the future
     /// if it was not cancelled
@@ -104,6 +460,55 @@ impl<T, S> CancellableResult<T, S> {
             _ => None,
         }
     }
+
+    ///Convert into a `Result`, with `err` if the future was cancelled
+    pub fn ok_or<E>(self, err: E) -> Result<T, E> {
+        match self {
+            Self::Finished(t) => Ok(t),
+            Self::Cancelled(_) => Err(err),
+        }
+    }
+
+    ///Convert into a `Result`, with the error built from the output of the canceller future
+    /// if the future was cancelled
+    ///
+    /// # Example
+    /// ```rust
+    /// # use futures::{executor::block_on, future::{pending, ready}};
+    ///  use kyansel::FutureCancellable;
+    ///
+    ///  fn fetch() -> Result<u32, String> {
+    ///      block_on(pending().cancel_with(ready("shutting down")))
+    ///          .ok_or_else(|reason| format!("fetch cancelled: {}", reason))
+    ///  }
+    ///
+    ///  assert_eq!(fetch(), Err("fetch cancelled: shutting down".to_string()));
+    /// ```
+    pub fn ok_or_else<E, M>(self, err: M) -> Result<T, E>
+    where
+        M: FnOnce(S) -> E,
+    {
+        match self {
+            Self::Finished(t) => Ok(t),
+            Self::Cancelled(s) => Err(err(s)),
+        }
+    }
+}
+
+///Allows returning a `CancellableResult` from `main`
+///
+///A cancelled program exits with code 130, like a shell does for a program interrupted by Ctrl-C
+#[cfg(feature = "std")]
+impl<T, S> std::process::Termination for CancellableResult<T, S>
+where
+    T: std::process::Termination,
+{
+    fn report(self) -> std::process::ExitCode {
+        match self {
+            Self::Finished(t) => t.report(),
+            Self::Cancelled(_) => std::process::ExitCode::from(130),
+        }
+    }
 }
 
 impl<F, S> Future for Cancellable<F, S>
@@ -114,12 +519,17 @@ where
     type Output = CancellableResult<F::Output, S::Output>;
 
     fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
+        #[cfg(any(feature = "log", feature = "tracing"))]
+        let name = self.name;
         let this = self.project();
 
         //always poll inner future first
         match this.inner.poll(cx) {
             Poll::Pending => {}
             Poll::Ready(ready) => {
+                #[cfg(any(feature = "log", feature = "tracing"))]
+                trace_named(name, "finished");
+
                 //return early with the result
                 return Poll::Ready(CancellableResult::Finished(ready));
             }
@@ -127,7 +537,12 @@ where
 
         match this.stopper.poll(cx) {
             //if the inner future was ready we won't reach this
-            Poll::Ready(s) => return Poll::Ready(CancellableResult::Cancelled(s)),
+            Poll::Ready(s) => {
+                #[cfg(any(feature = "log", feature = "tracing"))]
+                trace_named(name, "cancelled");
+
+                return Poll::Ready(CancellableResult::Cancelled(s));
+            }
             Poll::Pending => {}
         };
 
@@ -136,6 +551,27 @@ where
     }
 }
 
+//only named futures are traced, anonymous ones would just be noise
+#[cfg(any(feature = "log", feature = "tracing"))]
+fn trace_named(name: Option<&'static str>, outcome: &'static str) {
+    if let Some(name) = name {
+        #[cfg(feature = "tracing")]
+        ::tracing::debug!(name, outcome, "cancellable completed");
+
+        #[cfg(feature = "log")]
+        ::log::log!(log_level(outcome), "cancellable {} {}", name, outcome);
+    }
+}
+
+//cancellations are the notable outcome, completions are only interesting when debugging
+#[cfg(feature = "log")]
+fn log_level(outcome: &'static str) -> ::log::Level {
+    match outcome {
+        "cancelled" => ::log::Level::Info,
+        _ => ::log::Level::Debug,
+    }
+}
+
 /// An extension trait for `Future` that provides the [`Cancellable`](struct.Cancellable.html)
 /// combinator.
 ///
@@ -150,7 +586,19 @@ pub trait FutureCancellable: Future {
         S: Future,
         Self: Sized,
     {
-        Cancellable { inner: self, stopper }
+        Cancellable { inner: self, stopper, name: None }
+    }
+
+    ///Cancel this future if a fallible stopper completes with `Ok`
+    ///
+    ///If the stopper completes with `Err` it's dropped, and the future can only finish.
+    ///See the [`fuse`](fuse/index.html) module
+    fn cancel_on_ok<S, T, E>(self, stopper: S) -> fuse::CancelOnOk<Self, S>
+    where
+        S: Future<Output = Result<T, E>>,
+        Self: Sized,
+    {
+        fuse::CancelOnOk::new(self, stopper)
     }
 }
 
@@ -158,13 +606,44 @@ pub trait FutureCancellable: Future {
 ///
 ///This is essentially the same as
 /// [`FutureCancellable::cancel_with`](trait.FutureCancellable.html#method.cancel_with),
-/// the difference being that this is a function instead of a method
-pub fn cancellable<Fut1, Fut2>(inner: Fut1, stopper: Fut2) -> Cancellable<Fut1, Fut2>
+/// the difference being that this is a function instead of a method,
+/// usable in `const` contexts too
+///
+/// # Example
+/// ```rust
+///  use core::{future::Future, pin::Pin, task::{Context, Poll}};
+///  use kyansel::{cancellable, Cancellable};
+///
+///  struct Blink;
+///  impl Future for Blink {
+///      type Output = ();
+///      fn poll(self: Pin<&mut Self>, _: &mut Context) -> Poll<()> { Poll::Ready(()) }
+///  }
+///
+///  //built at compile time, for executors allocating their tasks statically
+///  const TASK: Cancellable<Blink, Blink> = cancellable(Blink, Blink).named("blink");
+///
+///  assert!(!futures::executor::block_on(TASK).is_cancelled());
+/// ```
+pub const fn cancellable<Fut1, Fut2>(inner: Fut1, stopper: Fut2) -> Cancellable<Fut1, Fut2>
 where
     Fut1: Future,
     Fut2: Future,
 {
-    Cancellable { inner, stopper }
+    Cancellable { inner, stopper, name: None }
 }
 
 impl<T: ?Sized> FutureCancellable for T where T: Future {}
+
+///A type-erased, `Send` [`Cancellable`](struct.Cancellable.html)
+#[cfg(feature = "std")]
+pub type BoxCancellable<'a, T, S> = Cancellable<
+    Pin<Box<dyn Future<Output = T> + Send + 'a>>,
+    Pin<Box<dyn Future<Output = S> + Send + 'a>>,
+>;
+
+///A type-erased [`Cancellable`](struct.Cancellable.html) that is not `Send`,
+/// for single-threaded executors
+#[cfg(feature = "std")]
+pub type LocalBoxCancellable<'a, T, S> =
+    Cancellable<Pin<Box<dyn Future<Output = T> + 'a>>, Pin<Box<dyn Future<Output = S> + 'a>>>;
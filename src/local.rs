@@ -0,0 +1,305 @@
+//! `!Send` cancellation primitives for thread-per-core runtimes
+//!
+//! [`LocalCancellationToken`](struct.LocalCancellationToken.html) and
+//! [`local_scope`](fn.local_scope.html) mirror
+//! [`CancellationToken`](../token/struct.CancellationToken.html) and
+//! [`scope`](../scope/fn.scope.html), but use `Rc` and `RefCell` instead of atomics and locks,
+//! and don't require the spawned futures to be `Send`.
+//!
+//!They fit glommio, monoio or a tokio `LocalSet`, where nothing leaves the current thread
+//!
+//! # Example
+//! ```rust
+//! # use futures::{executor::block_on, future::pending};
+//!  use kyansel::local::{local_scope, LocalCancellationToken};
+//!  use std::{cell::Cell, rc::Rc};
+//!
+//!  let polls = Rc::new(Cell::new(0));
+//!  let token = LocalCancellationToken::new();
+//!
+//!  block_on(local_scope(|s| {
+//!      let (polls, token) = (polls.clone(), token.clone());
+//!      async move {
+//!          //`Rc` is not `Send`, but local scopes don't mind
+//!          s.spawn(async move {
+//!              polls.set(polls.get() + 1);
+//!              pending::<()>().await
+//!          });
+//!          token.cancel();
+//!      }
+//!  }));
+//!
+//!  assert_eq!(polls.get(), 1);
+//!  assert!(token.is_cancelled());
+//! ```
+
+use super::FutureCancellable;
+use core::{
+    cell::{Cell, RefCell},
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll, Waker},
+};
+use pin_project_lite::pin_project;
+use std::{boxed::Box, vec::Vec};
+use std::rc::{Rc, Weak};
+
+///Cloneable handle to a shared cancellation state, usable only on the current thread
+///
+///All clones observe the same state, cancelling one cancels them all
+#[derive(Debug, Clone, Default)]
+pub struct LocalCancellationToken {
+    inner: Rc<Inner>,
+}
+
+#[derive(Debug, Default)]
+struct Inner {
+    cancelled: Cell<bool>,
+    state: RefCell<State>,
+}
+
+#[derive(Debug, Default)]
+struct State {
+    wakers: Vec<Option<Waker>>,
+    free: Vec<usize>,
+    children: Vec<Weak<Inner>>,
+}
+
+impl Inner {
+    fn cancel(&self) {
+        if self.cancelled.replace(true) {
+            return;
+        }
+
+        let (wakers, children) = {
+            let mut state = self.state.borrow_mut();
+            state.free.clear();
+            (std::mem::take(&mut state.wakers), std::mem::take(&mut state.children))
+        };
+
+        //wake outside of the borrow, wakers might poll us again
+        wakers.into_iter().flatten().for_each(Waker::wake);
+        children.iter().filter_map(Weak::upgrade).for_each(|child| child.cancel());
+    }
+}
+
+impl LocalCancellationToken {
+    ///Create a new token that is not cancelled
+    pub fn new() -> Self { Self::default() }
+
+    ///Cancel this token, all its clones and all its children
+    ///
+    ///Cancelling an already cancelled token does nothing
+    pub fn cancel(&self) { self.inner.cancel() }
+
+    ///Check if the token was cancelled
+    pub fn is_cancelled(&self) -> bool { self.inner.cancelled.get() }
+
+    ///Create a token that will be cancelled when this one is,
+    /// but that can also be cancelled on its own without affecting the parent
+    pub fn child_token(&self) -> Self {
+        let child = Self::new();
+
+        if self.is_cancelled() {
+            child.cancel();
+        } else {
+            let mut state = self.inner.state.borrow_mut();
+            state.children.retain(|c| c.strong_count() > 0);
+            state.children.push(Rc::downgrade(&child.inner));
+        }
+
+        child
+    }
+
+    ///Retrieve a future that completes when the token is cancelled
+    ///
+    ///The future owns a clone of the token so it can be used as a stopper freely
+    pub fn cancelled(&self) -> LocalWaitForCancellation {
+        LocalWaitForCancellation { token: self.clone(), slot: None }
+    }
+}
+
+///Future returned by
+/// [`LocalCancellationToken::cancelled`](struct.LocalCancellationToken.html#method.cancelled)
+#[derive(Debug)]
+#[must_use = "futures do nothing unless you `.await` or poll them"]
+pub struct LocalWaitForCancellation {
+    token: LocalCancellationToken,
+    slot: Option<usize>,
+}
+
+impl Future for LocalWaitForCancellation {
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
+        let this = self.get_mut();
+
+        if this.token.is_cancelled() {
+            return Poll::Ready(());
+        }
+
+        let mut state = this.token.inner.state.borrow_mut();
+        match this.slot {
+            Some(slot) => match &mut state.wakers[slot] {
+                Some(waker) if waker.will_wake(cx.waker()) => {}
+                waker => *waker = Some(cx.waker().clone()),
+            },
+            None => {
+                let waker = Some(cx.waker().clone());
+                this.slot = Some(match state.free.pop() {
+                    Some(slot) => {
+                        state.wakers[slot] = waker;
+                        slot
+                    }
+                    None => {
+                        state.wakers.push(waker);
+                        state.wakers.len() - 1
+                    }
+                });
+            }
+        }
+
+        Poll::Pending
+    }
+}
+
+impl Drop for LocalWaitForCancellation {
+    fn drop(&mut self) {
+        if let Some(slot) = self.slot {
+            let mut state = self.token.inner.state.borrow_mut();
+            //the slots are cleared on cancellation
+            if let Some(waker) = state.wakers.get_mut(slot) {
+                *waker = None;
+                state.free.push(slot);
+            }
+        }
+    }
+}
+
+type LocalTask<'a> = Pin<Box<dyn Future<Output = ()> + 'a>>;
+
+///Handle used to spawn `!Send` cancellables on a local scope
+///
+///Created by [`local_scope`](fn.local_scope.html), it can be cloned and moved into the spawned
+/// futures
+#[derive(Clone)]
+pub struct LocalScope<'a> {
+    token: LocalCancellationToken,
+    shared: Rc<RefCell<Shared<'a>>>,
+}
+
+struct Shared<'a> {
+    incoming: Vec<LocalTask<'a>>,
+    waker: Option<Waker>,
+}
+
+impl<'a> LocalScope<'a> {
+    ///Spawn a future on the scope
+    ///
+    ///The future will be driven together with the scope body
+    /// and cancelled once the body completes
+    pub fn spawn<F>(&self, fut: F)
+    where
+        F: Future + 'a,
+    {
+        let cancellable = fut.cancel_with(self.token.cancelled());
+
+        let mut shared = self.shared.borrow_mut();
+        shared.incoming.push(Box::pin(async move {
+            let _ = cancellable.await;
+        }));
+
+        if let Some(waker) = shared.waker.take() {
+            waker.wake();
+        }
+    }
+
+    ///Cancel all the futures spawned on the scope, including the ones spawned from now on
+    ///
+    ///The body is not cancelled
+    pub fn cancel(&self) { self.token.cancel() }
+
+    ///Retrieve the token used to cancel the spawned futures
+    pub fn token(&self) -> &LocalCancellationToken { &self.token }
+}
+
+impl core::fmt::Debug for LocalScope<'_> {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        f.debug_struct("LocalScope").field("token", &self.token).finish()
+    }
+}
+
+pin_project! {
+    ///Future returned by [`local_scope`](fn.local_scope.html)
+    #[must_use = "futures do nothing unless you `.await` or poll them"]
+    pub struct LocalScopeFuture<'a, Fut>
+    where
+        Fut: Future,
+    {
+        #[pin]
+        body: Fut,
+        output: Option<Fut::Output>,
+        running: Vec<LocalTask<'a>>,
+        scope: LocalScope<'a>,
+    }
+}
+
+impl<Fut> Future for LocalScopeFuture<'_, Fut>
+where
+    Fut: Future,
+{
+    type Output = Fut::Output;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
+        let this = self.project();
+
+        if this.output.is_none() {
+            if let Poll::Ready(output) = this.body.poll(cx) {
+                *this.output = Some(output);
+                this.scope.cancel();
+            }
+        }
+
+        this.running.retain_mut(|task| task.as_mut().poll(cx).is_pending());
+
+        //newly spawned tasks can spawn more tasks when polled
+        loop {
+            let mut incoming = {
+                let mut shared = this.scope.shared.borrow_mut();
+                if shared.incoming.is_empty() {
+                    shared.waker = Some(cx.waker().clone());
+                    break;
+                }
+                std::mem::take(&mut shared.incoming)
+            };
+
+            incoming.retain_mut(|task| task.as_mut().poll(cx).is_pending());
+            this.running.extend(incoming);
+        }
+
+        match this.output.take() {
+            Some(output) if this.running.is_empty() => Poll::Ready(output),
+            output => {
+                *this.output = output;
+                Poll::Pending
+            }
+        }
+    }
+}
+
+///Run `body` with a [`LocalScope`](struct.LocalScope.html) to spawn `!Send` cancellables on
+///
+///The returned future resolves with the output of the body,
+/// after all the spawned futures have been cancelled and awaited
+pub fn local_scope<'a, F, Fut>(body: F) -> LocalScopeFuture<'a, Fut>
+where
+    F: FnOnce(LocalScope<'a>) -> Fut,
+    Fut: Future,
+{
+    let scope = LocalScope {
+        token: LocalCancellationToken::new(),
+        shared: Rc::new(RefCell::new(Shared { incoming: Vec::new(), waker: None })),
+    };
+
+    LocalScopeFuture { body: body(scope.clone()), output: None, running: Vec::new(), scope }
+}
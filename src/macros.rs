@@ -0,0 +1,104 @@
+///Race a future against stoppers, `select!`-style, running the branch of whichever completes
+///
+///The first branch is the work, the others are stoppers cancelling it.
+///When several complete in the same poll the earliest branch wins:
+/// the work is always polled first, then the stoppers in the order they're listed.
+///
+///It expands to nested [`cancel_with`](trait.FutureCancellable.html#method.cancel_with)
+/// combinators and must be used in an async context.
+///The patterns must be irrefutable, like `_` or a binding
+///
+/// # Example
+/// ```rust
+/// # use futures::{executor::block_on, future::{pending, ready}};
+///  use kyansel::cancel_with;
+///
+/// # block_on(async {
+///  let outcome = cancel_with! {
+///      out = pending::<u32>() => format!("finished with {}", out),
+///      reason = ready("shutdown") => format!("stopped by {}", reason),
+///      _ = ready(()) => "timed out".to_string(),
+///  };
+///
+///  //both stoppers are ready, the first listed wins
+///  assert_eq!(outcome, "stopped by shutdown");
+/// # });
+/// ```
+#[macro_export]
+macro_rules! cancel_with {
+    ($out:pat = $work:expr => $finished:expr, $($stoppers:tt)+) => {
+        match $crate::FutureCancellable::cancel_with(
+            $work,
+            $crate::__cancel_with_stoppers!($($stoppers)+),
+        )
+        .await
+        {
+            $crate::CancellableResult::Finished($out) => $finished,
+            $crate::CancellableResult::Cancelled(cancelled) => {
+                $crate::__cancel_with_branches!(cancelled; $($stoppers)+)
+            }
+        }
+    };
+}
+
+//each stopper cancels the ones listed after it, so earlier ones take precedence
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __cancel_with_stoppers {
+    ($sig:pat = $stopper:expr => $cancelled:expr $(,)?) => {
+        $stopper
+    };
+    ($sig:pat = $stopper:expr => $cancelled:expr, $($rest:tt)+) => {
+        $crate::FutureCancellable::cancel_with($stopper, $crate::__cancel_with_stoppers!($($rest)+))
+    };
+}
+
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __cancel_with_branches {
+    ($result:ident; $sig:pat = $stopper:expr => $cancelled:expr $(,)?) => {
+        match $result {
+            $sig => $cancelled,
+        }
+    };
+    ($result:ident; $sig:pat = $stopper:expr => $cancelled:expr, $($rest:tt)+) => {
+        match $result {
+            $crate::CancellableResult::Finished($sig) => $cancelled,
+            $crate::CancellableResult::Cancelled($result) => {
+                $crate::__cancel_with_branches!($result; $($rest)+)
+            }
+        }
+    };
+}
+
+///Create a [`Cancellable`](struct.Cancellable.html) pinned on the stack
+///
+///Like `core::pin::pin!` fused with [`cancellable`](fn.cancellable.html): the variable holds a
+/// `Pin<&mut Cancellable<_, _>>` that can be polled in a loop, or handed to `select!`,
+/// without boxing
+///
+/// # Example
+/// ```rust
+/// # use futures::{channel::oneshot, executor::block_on, future::pending, poll};
+///  use kyansel::cancellable;
+///
+/// # block_on(async {
+///  let (tx, rx) = oneshot::channel::<()>();
+///  cancellable!(job = pending::<()>(), rx);
+///
+///  //polled by reference, the future can still be awaited later
+///  assert!(poll!(job.as_mut()).is_pending());
+///
+///  tx.send(()).unwrap();
+///  assert!(job.await.is_cancelled());
+/// # });
+/// ```
+#[macro_export]
+macro_rules! cancellable {
+    ($($name:ident = $fut:expr, $stopper:expr);+ $(;)?) => {
+        $(
+            #[allow(unused_mut)]
+            let mut $name = ::core::pin::pin!($crate::cancellable($fut, $stopper));
+        )+
+    };
+}
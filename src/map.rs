@@ -0,0 +1,235 @@
+//! Transforming the outcome of a cancellable future
+//!
+//![`CancellableMapExt`](trait.CancellableMapExt.html) maps either side of the
+//! [`CancellableResult`](../enum.CancellableResult.html) a future resolves to,
+//! without leaving the combinator chain to `.await` and match on it first.
+//![`then_if_cancelled`](trait.CancellableMapExt.html#method.then_if_cancelled) goes further,
+//! running a fallback future built from the output of the stopper when the future is cancelled.
+//!
+//!It works with any future resolving to a `CancellableResult`,
+//! like [`Cancellable`](../struct.Cancellable.html) or the other combinators of this crate.
+//!
+//! # Example
+//! ```rust
+//! # use futures::{executor::block_on, future::{pending, ready}};
+//!  use kyansel::{map::CancellableMapExt, CancellableResult, FutureCancellable};
+//!
+//!  let request = pending::<u16>()
+//!      .cancel_with(ready("client went away"))
+//!      .map_finished(|status| status.to_string())
+//!      .map_cancelled(|reason| reason.len());
+//!
+//!  assert_eq!(block_on(request), CancellableResult::Cancelled(16));
+//!
+//!  //serve from cache if the fresh fetch gets cancelled
+//!  let fetch = pending::<&str>()
+//!      .cancel_with(ready(()))
+//!      .then_if_cancelled(|()| ready("cached"));
+//!
+//!  assert_eq!(block_on(fetch), "cached");
+//! ```
+
+use super::CancellableResult;
+use core::{
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll},
+};
+use pin_project_lite::pin_project;
+
+pin_project! {
+    ///Future for the
+    /// [`map_finished`](trait.CancellableMapExt.html#method.map_finished) combinator
+    #[must_use = "futures do nothing unless you `.await` or poll them"]
+    pub struct MapFinished<Fut, M> {
+        #[pin]
+        inner: Fut,
+        map: Option<M>,
+    }
+}
+
+impl<Fut, M, T, S, U> Future for MapFinished<Fut, M>
+where
+    Fut: Future<Output = CancellableResult<T, S>>,
+    M: FnOnce(T) -> U,
+{
+    type Output = CancellableResult<U, S>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
+        let this = self.project();
+
+        let result = match this.inner.poll(cx) {
+            Poll::Ready(result) => result,
+            Poll::Pending => return Poll::Pending,
+        };
+
+        let map = this.map.take().expect("MapFinished polled after completion");
+        Poll::Ready(match result {
+            CancellableResult::Finished(t) => CancellableResult::Finished(map(t)),
+            CancellableResult::Cancelled(s) => CancellableResult::Cancelled(s),
+        })
+    }
+}
+
+impl<Fut, M> core::fmt::Debug for MapFinished<Fut, M>
+where
+    Fut: core::fmt::Debug,
+{
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        f.debug_struct("MapFinished").field("inner", &self.inner).finish()
+    }
+}
+
+pin_project! {
+    ///Future for the
+    /// [`map_cancelled`](trait.CancellableMapExt.html#method.map_cancelled) combinator
+    #[must_use = "futures do nothing unless you `.await` or poll them"]
+    pub struct MapCancelled<Fut, M> {
+        #[pin]
+        inner: Fut,
+        map: Option<M>,
+    }
+}
+
+impl<Fut, M, T, S, U> Future for MapCancelled<Fut, M>
+where
+    Fut: Future<Output = CancellableResult<T, S>>,
+    M: FnOnce(S) -> U,
+{
+    type Output = CancellableResult<T, U>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
+        let this = self.project();
+
+        let result = match this.inner.poll(cx) {
+            Poll::Ready(result) => result,
+            Poll::Pending => return Poll::Pending,
+        };
+
+        let map = this.map.take().expect("MapCancelled polled after completion");
+        Poll::Ready(match result {
+            CancellableResult::Finished(t) => CancellableResult::Finished(t),
+            CancellableResult::Cancelled(s) => CancellableResult::Cancelled(map(s)),
+        })
+    }
+}
+
+impl<Fut, M> core::fmt::Debug for MapCancelled<Fut, M>
+where
+    Fut: core::fmt::Debug,
+{
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        f.debug_struct("MapCancelled").field("inner", &self.inner).finish()
+    }
+}
+
+pin_project! {
+    ///Future for the
+    /// [`then_if_cancelled`](trait.CancellableMapExt.html#method.then_if_cancelled) combinator
+    #[must_use = "futures do nothing unless you `.await` or poll them"]
+    pub struct ThenIfCancelled<Fut, M, G> {
+        #[pin]
+        state: State<Fut, M, G>,
+    }
+}
+
+pin_project! {
+    #[project = StateProj]
+    enum State<Fut, M, G> {
+        Running { #[pin] inner: Fut, fallback: Option<M> },
+        Fallback { #[pin] fallback: G },
+        Done,
+    }
+}
+
+impl<Fut, M, G, T, S> Future for ThenIfCancelled<Fut, M, G>
+where
+    Fut: Future<Output = CancellableResult<T, S>>,
+    M: FnOnce(S) -> G,
+    G: Future<Output = T>,
+{
+    type Output = T;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
+        let mut state = self.project().state;
+
+        loop {
+            match state.as_mut().project() {
+                StateProj::Running { inner, fallback } => match inner.poll(cx) {
+                    Poll::Pending => return Poll::Pending,
+                    Poll::Ready(CancellableResult::Finished(t)) => {
+                        state.set(State::Done);
+                        return Poll::Ready(t);
+                    }
+                    Poll::Ready(CancellableResult::Cancelled(s)) => {
+                        let fallback = fallback.take().expect("fallback already built");
+                        state.set(State::Fallback { fallback: fallback(s) });
+                    }
+                },
+                StateProj::Fallback { fallback } => {
+                    let t = match fallback.poll(cx) {
+                        Poll::Ready(t) => t,
+                        Poll::Pending => return Poll::Pending,
+                    };
+
+                    state.set(State::Done);
+                    return Poll::Ready(t);
+                }
+                StateProj::Done => panic!("ThenIfCancelled polled after completion"),
+            }
+        }
+    }
+}
+
+impl<Fut, M, G> core::fmt::Debug for ThenIfCancelled<Fut, M, G> {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        f.debug_struct("ThenIfCancelled")
+            .field("cancelled", &matches!(self.state, State::Fallback { .. }))
+            .finish()
+    }
+}
+
+///An extension trait for cancellable futures that provides the
+/// [`MapFinished`](struct.MapFinished.html), [`MapCancelled`](struct.MapCancelled.html)
+/// and [`ThenIfCancelled`](struct.ThenIfCancelled.html) combinators
+///
+///All futures resolving to a [`CancellableResult`](../enum.CancellableResult.html)
+/// already implement `CancellableMapExt`
+pub trait CancellableMapExt<T, S>: Future<Output = CancellableResult<T, S>> {
+    ///Map the output of the inner future with `map`, if it finished
+    fn map_finished<M, U>(self, map: M) -> MapFinished<Self, M>
+    where
+        M: FnOnce(T) -> U,
+        Self: Sized,
+    {
+        MapFinished { inner: self, map: Some(map) }
+    }
+
+    ///Map the output of the stopper with `map`, if the future was cancelled
+    fn map_cancelled<M, U>(self, map: M) -> MapCancelled<Self, M>
+    where
+        M: FnOnce(S) -> U,
+        Self: Sized,
+    {
+        MapCancelled { inner: self, map: Some(map) }
+    }
+
+    ///Run the future built by `fallback` from the output of the stopper,
+    /// if the future was cancelled
+    ///
+    ///Resolves to the output of the inner future if it finished,
+    /// and to the output of the fallback otherwise
+    fn then_if_cancelled<M, G>(self, fallback: M) -> ThenIfCancelled<Self, M, G>
+    where
+        M: FnOnce(S) -> G,
+        G: Future<Output = T>,
+        Self: Sized,
+    {
+        ThenIfCancelled { state: State::Running { inner: self, fallback: Some(fallback) } }
+    }
+}
+
+impl<Fut, T, S> CancellableMapExt<T, S> for Fut where
+    Fut: Future<Output = CancellableResult<T, S>>
+{
+}
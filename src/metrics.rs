@@ -0,0 +1,105 @@
+//! Counters and histograms with the `metrics` facade
+//!
+//![`with_metrics`](trait.CancellableMetricsExt.html#method.with_metrics) records,
+//! when a cancellable future completes:
+//!
+//! * `kyansel_finished_total`, a counter of futures that finished
+//! * `kyansel_cancelled_total`, a counter of futures that were cancelled
+//! * `kyansel_time_to_cancel_seconds`, a histogram of the time from the first poll
+//!   to the cancellation
+//!
+//!All of them are tagged with a `label` if one is given.
+//!The metrics go to whichever recorder is installed, and nowhere if there is none
+//!
+//! # Example
+//! ```rust
+//! # use futures::{executor::block_on, future::{pending, ready}};
+//!  use kyansel::{metrics::CancellableMetricsExt, FutureCancellable};
+//!
+//!  let sync = pending::<()>().cancel_with(ready(())).with_metrics(Some("sync"));
+//!
+//!  //increments `kyansel_cancelled_total{label="sync"}`
+//!  assert!(block_on(sync).is_cancelled());
+//! ```
+
+use super::CancellableResult;
+use core::{
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll},
+};
+use pin_project_lite::pin_project;
+use std::time::Instant;
+
+pin_project! {
+    ///Future for the [`with_metrics`](trait.CancellableMetricsExt.html#method.with_metrics)
+    /// combinator
+    #[must_use = "futures do nothing unless you `.await` or poll them"]
+    pub struct Metered<Fut> {
+        #[pin]
+        inner: Fut,
+        label: Option<&'static str>,
+        started: Option<Instant>,
+    }
+}
+
+impl<Fut, T, S> Future for Metered<Fut>
+where
+    Fut: Future<Output = CancellableResult<T, S>>,
+{
+    type Output = CancellableResult<T, S>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
+        let this = self.project();
+        let started = *this.started.get_or_insert_with(Instant::now);
+
+        let result = match this.inner.poll(cx) {
+            Poll::Ready(result) => result,
+            Poll::Pending => return Poll::Pending,
+        };
+
+        let labels: &[(&str, &'static str)] = match &this.label {
+            Some(label) => &[("label", label)],
+            None => &[],
+        };
+
+        match &result {
+            CancellableResult::Finished(_) => {
+                metrics::counter!("kyansel_finished_total", labels).increment(1)
+            }
+            CancellableResult::Cancelled(_) => {
+                metrics::counter!("kyansel_cancelled_total", labels).increment(1);
+                metrics::histogram!("kyansel_time_to_cancel_seconds", labels)
+                    .record(started.elapsed().as_secs_f64());
+            }
+        }
+
+        Poll::Ready(result)
+    }
+}
+
+impl<Fut> core::fmt::Debug for Metered<Fut> {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        f.debug_struct("Metered").field("label", &self.label).finish()
+    }
+}
+
+///An extension trait for cancellable futures that provides the
+/// [`Metered`](struct.Metered.html) combinator
+///
+///All futures resolving to a [`CancellableResult`](../enum.CancellableResult.html)
+/// already implement `CancellableMetricsExt`
+pub trait CancellableMetricsExt<T, S>: Future<Output = CancellableResult<T, S>> {
+    ///Record whether this future finished or was cancelled, tagged with `label`
+    fn with_metrics(self, label: Option<&'static str>) -> Metered<Self>
+    where
+        Self: Sized,
+    {
+        Metered { inner: self, label, started: None }
+    }
+}
+
+impl<Fut, T, S> CancellableMetricsExt<T, S> for Fut where
+    Fut: Future<Output = CancellableResult<T, S>>
+{
+}
@@ -0,0 +1,119 @@
+//! Graceful shutdown of TCP servers, backed by tokio
+//!
+//! [`serve`](fn.serve.html) runs the accept loop of a `TcpListener`, spawning a task for each
+//! connection. When the token is cancelled it stops accepting, the connections are told to wrap
+//! up through their own child token, and it waits for them to finish, up to a drain timeout
+//! after which the remaining ones are aborted.
+//!
+//! # Example
+//! ```rust
+//! # #[tokio::main]
+//! # async fn main() -> std::io::Result<()> {
+//!  use kyansel::{net::{serve, Drain}, token::CancellationToken};
+//! #  use std::time::Duration;
+//!  use tokio::net::{TcpListener, TcpStream};
+//!
+//!  let listener = TcpListener::bind("127.0.0.1:0").await?;
+//!  let addr = listener.local_addr()?;
+//!  let token = CancellationToken::new();
+//!
+//!  let server = tokio::spawn(serve(listener, token.clone(), Duration::from_secs(5), {
+//!      |_stream, _addr, token: CancellationToken| async move {
+//!          //serve the connection until asked to wrap up
+//!          token.cancelled().await;
+//!      }
+//!  }));
+//!
+//!  let _client = TcpStream::connect(addr).await?;
+//!
+//!  token.cancel();
+//!  assert_eq!(server.await.unwrap()?, Drain::Complete);
+//! # Ok(())
+//! # }
+//! ```
+
+use super::{token::CancellationToken, CancellableResult, FutureCancellable};
+use core::future::Future;
+use std::{io, net::SocketAddr, time::Duration};
+use tokio::{
+    net::{TcpListener, TcpStream},
+    task::JoinSet,
+};
+
+///How the connections ended once [`serve`](fn.serve.html) stopped accepting
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Drain {
+    ///If all the connections finished within the drain timeout
+    Complete,
+
+    ///If the drain timeout passed, with the number of connections that had to be aborted
+    TimedOut(usize),
+}
+
+impl Drain {
+    ///Check if some connections had to be aborted
+    pub fn is_timed_out(&self) -> bool { matches!(self, Self::TimedOut(_)) }
+}
+
+//errors about a single connection, the listener itself is still fine
+fn is_connection_error(err: &io::Error) -> bool {
+    matches!(
+        err.kind(),
+        io::ErrorKind::ConnectionAborted
+            | io::ErrorKind::ConnectionRefused
+            | io::ErrorKind::ConnectionReset
+            | io::ErrorKind::Interrupted
+    )
+}
+
+///Accept connections on `listener` until `token` is cancelled, handling each one in a new task
+///
+///`handler` is given each connection with a child token of `token`,
+/// cancelled when the server shuts down.
+///Once `token` is cancelled no more connections are accepted,
+/// and the connections still open are given `drain` to finish before being aborted.
+///
+///Panics in the connection tasks are not propagated
+///
+/// # Errors
+///Errors about a single connection are ignored, any other error accepting connections
+/// is returned right away, aborting the open connections
+pub async fn serve<H, Fut>(
+    listener: TcpListener,
+    token: CancellationToken,
+    drain: Duration,
+    mut handler: H,
+) -> io::Result<Drain>
+where
+    H: FnMut(TcpStream, SocketAddr, CancellationToken) -> Fut,
+    Fut: Future<Output = ()> + Send + 'static,
+{
+    let mut connections = JoinSet::new();
+
+    loop {
+        match listener.accept().cancel_with(token.cancelled()).await {
+            CancellableResult::Finished(Ok((stream, addr))) => {
+                connections.spawn(handler(stream, addr, token.child_token()));
+            }
+            CancellableResult::Finished(Err(err)) if is_connection_error(&err) => {}
+            CancellableResult::Finished(Err(err)) => return Err(err),
+            CancellableResult::Cancelled(()) => break,
+        }
+
+        //reap the finished connections, so a long running server doesn't accumulate them
+        while connections.try_join_next().is_some() {}
+    }
+
+    let drained = async {
+        while connections.join_next().await.is_some() {}
+    };
+
+    match drained.cancel_with(tokio::time::sleep(drain)).await {
+        CancellableResult::Finished(()) => Ok(Drain::Complete),
+        CancellableResult::Cancelled(()) => {
+            let remaining = connections.len();
+            connections.abort_all();
+            Ok(Drain::TimedOut(remaining))
+        }
+    }
+}
@@ -0,0 +1,320 @@
+//! Hooks for cancelling in-flight operations for real
+//!
+//![`Cancellable`](../struct.Cancellable.html) cancels by dropping the inner future, which is
+//! enough for readiness-based IO but not for completion-based IO (io_uring, IOCP),
+//! where the kernel keeps working on the operation and owns its buffers until it completes.
+//!
+//!Operations implementing [`CancelOp`](trait.CancelOp.html) are told when they are cancelled,
+//! so they can submit a real cancellation request and wait for it to be acknowledged.
+//![`OnCancel`](struct.OnCancel.html) is a reference integration,
+//! running a closure when the operation is cancelled.
+//!
+//!Database and network drivers often cancel out of band instead, like Postgres does with a
+//! cancel request sent over a new connection, and the query then has to be drained so the
+//! connection can be reused. [`cancel_request`](fn.cancel_request.html) builds such an operation
+//! from the future sending the request, and tells the drained output of a cancelled query apart
+//! from the one of a query that completed before the server handled the request.
+//!
+//!A [`Cancellable`](../struct.Cancellable.html) wrapping an operation can be turned into one
+//! going through the protocol with
+//! [`Cancellable::cancel_op`](../struct.Cancellable.html#method.cancel_op)
+//!
+//! # Example
+//! ```rust
+//! # use futures::{executor::block_on, future::{pending, ready}};
+//!  use kyansel::op::{on_cancel, CancelOpExt};
+//!  use std::cell::Cell;
+//!
+//!  let cancelled = Cell::new(false);
+//!
+//!  //submit an async cancel request to the ring here
+//!  let op = on_cancel(pending::<()>(), || cancelled.set(true));
+//!
+//!  let result = block_on(op.cancel_op_with(ready(())));
+//!
+//!  assert!(result.is_cancelled());
+//!  assert!(cancelled.get());
+//! ```
+
+use super::{Cancellable, CancellableResult};
+use core::{
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll},
+};
+use pin_project_lite::pin_project;
+
+///An operation that can be cancelled for real, instead of just being dropped
+///
+///Once cancelled, the future won't be polled again: only
+/// [`poll_cancel`](trait.CancelOp.html#method.poll_cancel) will be, until it completes
+pub trait CancelOp: Future {
+    ///Request the cancellation of the operation
+    ///
+    ///Called once, when the stopper completes
+    fn start_cancel(self: Pin<&mut Self>);
+
+    ///Wait for the cancellation to be acknowledged
+    ///
+    ///Completing with `Some` means the operation finished before it could be cancelled,
+    /// the result is then reported as finished rather than discarded.
+    ///
+    ///The default implementation completes right away with `None`
+    fn poll_cancel(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Option<Self::Output>> {
+        let _ = cx;
+        Poll::Ready(None)
+    }
+}
+
+pin_project! {
+    ///Future for the [`cancel_op_with`](trait.CancelOpExt.html#method.cancel_op_with) combinator
+    #[derive(Debug)]
+    #[must_use = "futures do nothing unless you `.await` or poll them"]
+    pub struct CancellableOp<F, S>
+    where
+        F: CancelOp,
+        S: Future,
+    {
+        #[pin]
+        inner: F,
+        #[pin]
+        stopper: S,
+        cancelled: Option<S::Output>,
+    }
+}
+
+impl<F, S> Future for CancellableOp<F, S>
+where
+    F: CancelOp,
+    S: Future,
+{
+    type Output = CancellableResult<F::Output, S::Output>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
+        let this = self.project();
+        let mut inner = this.inner;
+
+        if this.cancelled.is_none() {
+            //always poll inner future first
+            if let Poll::Ready(t) = inner.as_mut().poll(cx) {
+                return Poll::Ready(CancellableResult::Finished(t));
+            }
+
+            match this.stopper.poll(cx) {
+                Poll::Ready(s) => {
+                    *this.cancelled = Some(s);
+                    inner.as_mut().start_cancel();
+                }
+                Poll::Pending => return Poll::Pending,
+            }
+        }
+
+        match inner.poll_cancel(cx) {
+            Poll::Ready(Some(t)) => Poll::Ready(CancellableResult::Finished(t)),
+            Poll::Ready(None) => match this.cancelled.take() {
+                Some(s) => Poll::Ready(CancellableResult::Cancelled(s)),
+                None => panic!("CancellableOp polled after completion"),
+            },
+            Poll::Pending => Poll::Pending,
+        }
+    }
+}
+
+///An extension trait for [`CancelOp`](trait.CancelOp.html) that provides the
+/// [`CancellableOp`](struct.CancellableOp.html) combinator
+///
+///All types that implement `CancelOp` already implement `CancelOpExt`
+pub trait CancelOpExt: CancelOp {
+    ///Cancel this operation if `stopper` completes first,
+    /// letting it acknowledge the cancellation before completing
+    fn cancel_op_with<S>(self, stopper: S) -> CancellableOp<Self, S>
+    where
+        S: Future,
+        Self: Sized,
+    {
+        CancellableOp { inner: self, stopper, cancelled: None }
+    }
+}
+
+impl<T: ?Sized> CancelOpExt for T where T: CancelOp {}
+
+pin_project! {
+    ///Operation running a closure when it's cancelled
+    ///
+    ///Created with [`on_cancel`](fn.on_cancel.html)
+    #[derive(Debug)]
+    #[must_use = "futures do nothing unless you `.await` or poll them"]
+    pub struct OnCancel<F, C> {
+        #[pin]
+        fut: F,
+        on_cancel: Option<C>,
+    }
+}
+
+impl<F, C> Future for OnCancel<F, C>
+where
+    F: Future,
+{
+    type Output = F::Output;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
+        self.project().fut.poll(cx)
+    }
+}
+
+impl<F, C> CancelOp for OnCancel<F, C>
+where
+    F: Future,
+    C: FnOnce(),
+{
+    fn start_cancel(self: Pin<&mut Self>) {
+        if let Some(on_cancel) = self.project().on_cancel.take() {
+            on_cancel()
+        }
+    }
+}
+
+///Turn `fut` into an operation calling `on_cancel` when it's cancelled
+pub const fn on_cancel<F, C>(fut: F, on_cancel: C) -> OnCancel<F, C>
+where
+    F: Future,
+    C: FnOnce(),
+{
+    OnCancel { fut, on_cancel: Some(on_cancel) }
+}
+
+pin_project! {
+    ///Operation sending an out-of-band cancel request when it's cancelled
+    ///
+    ///Created with [`cancel_request`](fn.cancel_request.html)
+    #[must_use = "futures do nothing unless you `.await` or poll them"]
+    pub struct CancelRequest<F, Mk, R, C> {
+        #[pin]
+        fut: F,
+        make_request: Option<Mk>,
+        #[pin]
+        request: Option<R>,
+        is_cancelled: C,
+    }
+}
+
+impl<F, Mk, R, C> Future for CancelRequest<F, Mk, R, C>
+where
+    F: Future,
+{
+    type Output = F::Output;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
+        self.project().fut.poll(cx)
+    }
+}
+
+impl<F, Mk, R, C> CancelOp for CancelRequest<F, Mk, R, C>
+where
+    F: Future,
+    Mk: FnOnce() -> R,
+    R: Future<Output = ()>,
+    C: FnMut(&F::Output) -> bool,
+{
+    fn start_cancel(self: Pin<&mut Self>) {
+        let mut this = self.project();
+        if let Some(make_request) = this.make_request.take() {
+            this.request.set(Some(make_request()));
+        }
+    }
+
+    fn poll_cancel(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Option<Self::Output>> {
+        let mut this = self.project();
+
+        if let Some(request) = this.request.as_mut().as_pin_mut() {
+            match request.poll(cx) {
+                Poll::Ready(()) => this.request.set(None),
+                Poll::Pending => return Poll::Pending,
+            }
+        }
+
+        //the operation is drained, it may have completed before the request was handled
+        match this.fut.poll(cx) {
+            Poll::Ready(t) if (this.is_cancelled)(&t) => Poll::Ready(None),
+            Poll::Ready(t) => Poll::Ready(Some(t)),
+            Poll::Pending => Poll::Pending,
+        }
+    }
+}
+
+impl<F, Mk, R, C> core::fmt::Debug for CancelRequest<F, Mk, R, C>
+where
+    F: core::fmt::Debug,
+{
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        f.debug_struct("CancelRequest")
+            .field("fut", &self.fut)
+            .field("requested", &self.make_request.is_none())
+            .finish()
+    }
+}
+
+///Turn `fut` into an operation sending the cancel request built by `make_request`
+/// when it's cancelled
+///
+///Once the request is sent, `fut` is driven to completion so the connection it runs on is left
+/// in a clean state. Its output is discarded if `is_cancelled` recognizes it as the error
+/// reported for a cancelled operation, otherwise the operation completed before the request was
+/// handled and the output is reported as finished
+///
+/// # Example
+/// ```rust
+/// # use futures::{executor::block_on, future::{poll_fn, ready}};
+///  use kyansel::{op::cancel_request, FutureCancellable};
+///  use std::{cell::Cell, task::Poll};
+///
+///  const CANCELED: &str = "canceling statement due to user request";
+///  let cancel_sent = Cell::new(false);
+///
+///  //a query only ending once the server got the cancel request
+///  let query = poll_fn(|_| match cancel_sent.get() {
+///      true => Poll::Ready(Err::<u32, _>(CANCELED)),
+///      false => Poll::Pending,
+///  });
+///
+///  let op = cancel_request(query, || async { cancel_sent.set(true) }, |r| r == &Err(CANCELED));
+///  assert!(block_on(op.cancel_with(ready(())).cancel_op()).is_cancelled());
+///
+///  //a query whose rows were already on the way when the request was sent
+///  let query = poll_fn(|_| match cancel_sent.get() {
+///      true => Poll::Ready(Ok::<_, &str>(42)),
+///      false => Poll::Pending,
+///  });
+///
+///  cancel_sent.set(false);
+///  let op = cancel_request(query, || async { cancel_sent.set(true) }, |r| r == &Err(CANCELED));
+///  assert_eq!(block_on(op.cancel_with(ready(())).cancel_op()).finished(), Some(Ok(42)));
+/// ```
+pub const fn cancel_request<F, Mk, R, C>(
+    fut: F,
+    make_request: Mk,
+    is_cancelled: C,
+) -> CancelRequest<F, Mk, R, C>
+where
+    F: Future,
+    Mk: FnOnce() -> R,
+    R: Future<Output = ()>,
+    C: FnMut(&F::Output) -> bool,
+{
+    CancelRequest { fut, make_request: Some(make_request), request: None, is_cancelled }
+}
+
+impl<F, S> Cancellable<F, S>
+where
+    F: CancelOp,
+    S: Future,
+{
+    ///Go through the cancellation protocol of the inner operation when the stopper completes,
+    /// instead of just dropping it
+    ///
+    ///See the [`op`](op/index.html) module
+    pub fn cancel_op(self) -> CancellableOp<F, S> {
+        let Self { inner, stopper, .. } = self;
+        CancellableOp { inner, stopper, cancelled: None }
+    }
+}
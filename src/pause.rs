@@ -0,0 +1,131 @@
+//! Pausing futures without cancelling them
+//!
+//!A [`PauseHandle`](struct.PauseHandle.html) suspends the futures it controls: while paused
+//! they aren't polled at all, and they pick up where they left off once resumed.
+//!
+//!A [`Pausable`](struct.Pausable.html) future can still be cancelled while paused:
+//! [`Cancellable`](../struct.Cancellable.html) keeps polling its stopper,
+//! so batch processors can pause, resume and cancel the same work.
+//!
+//! # Example
+//! ```rust
+//! # use futures::{executor::block_on, future::{pending, ready}, pin_mut, poll};
+//!  use kyansel::{pause::PauseHandle, FutureCancellable};
+//!
+//! # block_on(async {
+//!  let handle = PauseHandle::new();
+//!
+//!  handle.pause();
+//!  let batch = handle.pausable(ready(42));
+//!  pin_mut!(batch);
+//!  assert!(poll!(batch.as_mut()).is_pending());
+//!
+//!  handle.resume();
+//!  assert_eq!(batch.await, 42);
+//!
+//!  //paused futures can still be cancelled
+//!  let batch = handle.pausable(pending::<()>()).cancel_with(ready(()));
+//!  handle.pause();
+//!  assert!(batch.await.is_cancelled());
+//! # });
+//! ```
+
+use core::{
+    future::Future,
+    pin::Pin,
+    sync::atomic::{AtomicBool, Ordering},
+    task::{Context, Poll, Waker},
+};
+use pin_project_lite::pin_project;
+use std::{
+    sync::{Arc, Mutex},
+    vec::Vec,
+};
+
+#[derive(Debug, Default)]
+struct Shared {
+    paused: AtomicBool,
+    wakers: Mutex<Vec<Waker>>,
+}
+
+///Handle pausing and resuming the [`Pausable`](struct.Pausable.html) futures created with it
+///
+///The handle is cheap to clone, all clones control the same futures
+#[derive(Debug, Clone, Default)]
+pub struct PauseHandle {
+    shared: Arc<Shared>,
+}
+
+impl PauseHandle {
+    ///Create a new handle, not paused
+    pub fn new() -> Self { Self::default() }
+
+    ///Stop polling the futures controlled by this handle, until resumed
+    ///
+    ///A future being polled finishes its current poll first
+    pub fn pause(&self) { self.shared.paused.store(true, Ordering::Release) }
+
+    ///Resume the futures controlled by this handle, waking the ones that were suspended
+    pub fn resume(&self) {
+        self.shared.paused.store(false, Ordering::Release);
+
+        let wakers = std::mem::take(&mut *self.shared.wakers.lock().unwrap());
+        wakers.into_iter().for_each(Waker::wake);
+    }
+
+    ///Check if the handle is paused
+    pub fn is_paused(&self) -> bool { self.shared.paused.load(Ordering::Acquire) }
+
+    ///Wrap `fut` so it's suspended while this handle is paused
+    pub fn pausable<F>(&self, fut: F) -> Pausable<F>
+    where
+        F: Future,
+    {
+        Pausable { inner: fut, shared: self.shared.clone() }
+    }
+}
+
+pin_project! {
+    ///Future suspended while its [`PauseHandle`](struct.PauseHandle.html) is paused
+    ///
+    ///Created with [`PauseHandle::pausable`](struct.PauseHandle.html#method.pausable)
+    #[must_use = "futures do nothing unless you `.await` or poll them"]
+    pub struct Pausable<F> {
+        #[pin]
+        inner: F,
+        shared: Arc<Shared>,
+    }
+}
+
+impl<F> Future for Pausable<F>
+where
+    F: Future,
+{
+    type Output = F::Output;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
+        let this = self.project();
+
+        if this.shared.paused.load(Ordering::Acquire) {
+            {
+                let mut wakers = this.shared.wakers.lock().unwrap();
+                if !wakers.iter().any(|waker| waker.will_wake(cx.waker())) {
+                    wakers.push(cx.waker().clone());
+                }
+            }
+
+            //resumed before the waker was stored, it won't be woken
+            if this.shared.paused.load(Ordering::Acquire) {
+                return Poll::Pending;
+            }
+        }
+
+        this.inner.poll(cx)
+    }
+}
+
+impl<F> core::fmt::Debug for Pausable<F> {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        f.debug_struct("Pausable").field("paused", &self.shared.paused).finish()
+    }
+}
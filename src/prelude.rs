@@ -0,0 +1,41 @@
+//! Extension traits and common types, in a single import
+//!
+//!`use kyansel::prelude::*` brings in the combinators of every enabled feature,
+//! without importing each extension trait from its own module.
+//!
+//!Only the traits and types are exported, helper functions stay in their modules
+//!
+//! # Example
+//! ```rust
+//! # use futures::{executor::block_on, future::pending};
+//!  use kyansel::prelude::*;
+//!
+//!  let token = CancellationToken::new();
+//!  let cancellable = pending::<()>().cancel_with(token.cancelled());
+//!
+//!  token.cancel();
+//!  assert!(block_on(cancellable).is_cancelled());
+//! ```
+
+pub use super::{
+    fast_path::CancelSignal,
+    map::CancellableMapExt,
+    op::{CancelOp, CancelOpExt},
+    timer::Timer,
+    Cancellable, CancellableResult, FutureCancellable,
+};
+
+#[cfg(feature = "std")]
+pub use super::{
+    token::{CancellationToken, WaitForCancellation},
+    BoxCancellable, LocalBoxCancellable,
+};
+
+#[cfg(feature = "futures-concurrency")]
+pub use super::futures_concurrency::CancelRaceExt;
+
+#[cfg(feature = "metrics")]
+pub use super::metrics::CancellableMetricsExt;
+
+#[cfg(feature = "tracing")]
+pub use super::tracing::CancellableInstrumentExt;
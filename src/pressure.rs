@@ -0,0 +1,290 @@
+//! Shedding low-priority work under resource pressure
+//!
+//!A [`PressureSignal`](trait.PressureSignal.html) reports how much pressure a resource is under,
+//! like memory over a watermark, a queue backing up or file descriptors running out, as a level
+//! where work of lower priority should be shed.
+//![`PressureShedder`](struct.PressureShedder.html) holds in-flight futures with a priority,
+//! and cancels the ones below the level whenever the signal reports pressure.
+//!
+//![`QueueDepth`](struct.QueueDepth.html) is a signal reporting pressure once a queue is deeper
+//! than a watermark, and closures returning the level are signals too.
+//!
+//!The signal is sampled on every submission and every poll of the shedder,
+//! and signals implementing [`poll_pressure`](trait.PressureSignal.html#method.poll_pressure),
+//! like `QueueDepth`, wake the shedder when the pressure rises while all its work is pending.
+//!Closures can't, so they're only sampled on the next submission or poll
+//!
+//! # Example
+//! ```rust
+//! # use futures::{executor::block_on, future::{pending, ready, Either}, StreamExt};
+//!  use kyansel::{
+//!      pressure::{PressureShedder, QueueDepth},
+//!      CancellableResult,
+//!  };
+//!
+//!  //past 100 queued requests, shed the work below priority 5
+//!  let queue = QueueDepth::new(100, 5);
+//!  let mut work = PressureShedder::new(queue.clone());
+//!
+//!  work.submit(1, Either::Left(pending()));
+//!  work.submit(9, Either::Right(ready("urgent")));
+//!
+//!  //the queue backs up
+//!  queue.set(150);
+//!
+//!  let mut results = block_on(work.collect::<Vec<_>>());
+//!  results.sort_by_key(|(priority, _)| *priority);
+//!
+//!  assert_eq!(results, vec![
+//!      (1, CancellableResult::Cancelled(())),
+//!      (9, CancellableResult::Finished("urgent")),
+//!  ]);
+//! ```
+
+use super::{
+    tasks::{Task, Tasks},
+    CancellableResult,
+};
+use core::{
+    future::Future,
+    pin::Pin,
+    sync::atomic::{AtomicUsize, Ordering},
+    task::{Context, Poll, Waker},
+};
+use futures_core::Stream;
+use pin_project_lite::pin_project;
+use std::{
+    sync::{Arc, Mutex},
+    vec::Vec,
+};
+
+///Source of resource pressure
+pub trait PressureSignal {
+    ///Retrieve the current pressure, as the priority below which work should be shed
+    ///
+    ///0 means no pressure, since no priority is below it
+    fn pressure(&self) -> u32;
+
+    ///Retrieve the current pressure like [`pressure`](trait.PressureSignal.html#tymethod.pressure),
+    /// scheduling the task in `cx` to be woken once the pressure rises
+    ///
+    ///The default implementation doesn't schedule any wakeup
+    ///
+    /// # Example
+    /// ```rust
+    /// # use futures::{future::pending, StreamExt};
+    /// # use std::{
+    /// #     sync::{atomic::{AtomicBool, Ordering}, Arc},
+    /// #     task::{Context, Poll, Wake, Waker},
+    /// # };
+    /// # struct Flag(AtomicBool);
+    /// # impl Wake for Flag {
+    /// #     fn wake(self: Arc<Self>) { self.0.store(true, Ordering::SeqCst) }
+    /// # }
+    ///  use kyansel::{
+    ///      pressure::{PressureShedder, QueueDepth},
+    ///      CancellableResult,
+    ///  };
+    ///
+    ///  let queue = QueueDepth::new(100, 5);
+    ///  let mut work = PressureShedder::new(queue.clone());
+    ///  work.submit(1, pending::<()>());
+    ///
+    ///  let woken = Arc::new(Flag(AtomicBool::new(false)));
+    ///  let waker = Waker::from(woken.clone());
+    ///  let mut cx = Context::from_waker(&waker);
+    ///  assert!(work.poll_next_unpin(&mut cx).is_pending());
+    ///
+    ///  //all the work is pending, so only the signal can wake the shedder
+    ///  queue.set(150);
+    ///  assert!(woken.0.load(Ordering::SeqCst));
+    ///
+    ///  let shed = work.poll_next_unpin(&mut cx);
+    ///  assert_eq!(shed, Poll::Ready(Some((1, CancellableResult::Cancelled(())))));
+    /// ```
+    fn poll_pressure(&self, cx: &mut Context) -> u32 {
+        let _ = cx;
+        self.pressure()
+    }
+}
+
+impl<Fun> PressureSignal for Fun
+where
+    Fun: Fn() -> u32,
+{
+    fn pressure(&self) -> u32 { self() }
+}
+
+impl<P> PressureSignal for Arc<P>
+where
+    P: PressureSignal + ?Sized,
+{
+    fn pressure(&self) -> u32 { (**self).pressure() }
+
+    fn poll_pressure(&self, cx: &mut Context) -> u32 { (**self).poll_pressure(cx) }
+}
+
+///Signal reporting pressure once a queue is deeper than a watermark
+///
+///The depth is updated by the queue's owner, all clones share it.
+///Shedders are woken when the depth goes over the watermark
+#[derive(Debug, Clone)]
+pub struct QueueDepth {
+    shared: Arc<Depth>,
+    watermark: usize,
+    shed_below: u32,
+}
+
+#[derive(Debug, Default)]
+struct Depth {
+    depth: AtomicUsize,
+    //tasks to wake once the depth goes over the watermark
+    wakers: Mutex<Vec<Waker>>,
+}
+
+impl QueueDepth {
+    ///Create a signal shedding the work below `shed_below` while the depth is over `watermark`
+    pub fn new(watermark: usize, shed_below: u32) -> Self {
+        Self { shared: Arc::default(), watermark, shed_below }
+    }
+
+    ///Set the depth of the queue
+    pub fn set(&self, depth: usize) {
+        let previous = self.shared.depth.swap(depth, Ordering::Relaxed);
+        self.rose(previous, depth);
+    }
+
+    ///Record an item entering the queue
+    pub fn push(&self) {
+        let previous = self.shared.depth.fetch_add(1, Ordering::Relaxed);
+        self.rose(previous, previous.saturating_add(1));
+    }
+
+    ///Record an item leaving the queue
+    pub fn pop(&self) {
+        let _ = self.shared.depth.fetch_update(Ordering::Relaxed, Ordering::Relaxed, |depth| {
+            Some(depth.saturating_sub(1))
+        });
+    }
+
+    ///Retrieve the depth of the queue
+    pub fn depth(&self) -> usize { self.shared.depth.load(Ordering::Relaxed) }
+
+    //wake the shedders if the depth went over the watermark
+    fn rose(&self, previous: usize, depth: usize) {
+        if previous <= self.watermark && depth > self.watermark {
+            let wakers = core::mem::take(&mut *self.shared.wakers.lock().unwrap());
+            for waker in wakers {
+                waker.wake();
+            }
+        }
+    }
+}
+
+impl PressureSignal for QueueDepth {
+    fn pressure(&self) -> u32 {
+        match self.depth() > self.watermark {
+            true => self.shed_below,
+            false => 0,
+        }
+    }
+
+    fn poll_pressure(&self, cx: &mut Context) -> u32 {
+        //registered before reading the depth, the lock orders it against `rose`
+        let mut wakers = self.shared.wakers.lock().unwrap();
+        if !wakers.iter().any(|waker| waker.will_wake(cx.waker())) {
+            wakers.push(cx.waker().clone());
+        }
+        drop(wakers);
+
+        self.pressure()
+    }
+}
+
+pin_project! {
+    ///Container of futures where the ones below the pressure reported by a signal are cancelled
+    ///
+    ///Work submitted under pressure is shed right away if its priority is too low
+    #[must_use = "streams do nothing unless polled"]
+    pub struct PressureShedder<F, P>
+    where
+        F: Future,
+    {
+        signal: P,
+        tasks: Tasks<u32, F>,
+    }
+}
+
+impl<F, P> PressureShedder<F, P>
+where
+    F: Future,
+    P: PressureSignal,
+{
+    ///Create an empty container shedding work when `signal` reports pressure
+    pub fn new(signal: P) -> Self { Self { signal, tasks: Tasks::new() } }
+
+    ///Submit work with `priority`
+    pub fn submit(&mut self, priority: u32, fut: F) {
+        self.tasks.push(Task::new(priority, fut));
+
+        self.shed();
+    }
+
+    ///Sample the signal and cancel the work below the pressure, returns how many were cancelled
+    pub fn shed(&mut self) -> usize {
+        let pressure = self.signal.pressure();
+        self.shed_below(pressure)
+    }
+
+    fn shed_below(&mut self, pressure: u32) -> usize {
+        let mut shed = 0;
+        for task in self.tasks.iter() {
+            if task.data < pressure && !task.is_cancelled() {
+                task.cancel();
+                shed += 1;
+            }
+        }
+        shed
+    }
+
+    ///Retrieve the signal
+    pub fn signal(&self) -> &P { &self.signal }
+
+    ///Number of futures in the container, including the cancelled ones not yet yielded
+    pub fn len(&self) -> usize { self.tasks.len() }
+
+    ///Check if the container has no futures left
+    pub fn is_empty(&self) -> bool { self.tasks.is_empty() }
+}
+
+impl<F, P> Stream for PressureShedder<F, P>
+where
+    F: Future,
+    P: PressureSignal,
+{
+    type Item = (u32, CancellableResult<F::Output, ()>);
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+
+        let pressure = this.signal.poll_pressure(cx);
+        this.shed_below(pressure);
+        this.tasks.poll_next(cx)
+    }
+}
+
+impl<F, P> core::fmt::Debug for PressureShedder<F, P>
+where
+    F: Future,
+    P: core::fmt::Debug,
+{
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        f.debug_struct("PressureShedder")
+            .field("signal", &self.signal)
+            .field(
+                "in_flight",
+                &self.tasks.iter().map(|task| task.data).collect::<Vec<_>>(),
+            )
+            .finish()
+    }
+}
@@ -0,0 +1,241 @@
+//! Preempting low-priority work
+//!
+//![`PriorityCanceller`](struct.PriorityCanceller.html) holds in-flight futures with a priority:
+//! submitting work cancels the in-flight work whose priority is lower by more than a margin,
+//! so urgent work doesn't have to compete with background jobs.
+//!
+//!Work submitted with a factory, with
+//! [`submit_resumable`](struct.PriorityCanceller.html#method.submit_resumable),
+//! isn't dropped when preempted: it's parked, and started over once the work that preempted it
+//! is done.
+//!
+//!The container is a `Stream` of `(priority, result)` pairs, where preempted work
+//! shows up as [`CancellableResult::Cancelled`](../enum.CancellableResult.html#variant.Cancelled)
+//!
+//! # Example
+//! ```rust
+//! # use futures::{executor::block_on, future::{pending, ready, Either}, StreamExt};
+//!  use kyansel::{priority::PriorityCanceller, CancellableResult};
+//!
+//!  let mut work = PriorityCanceller::new(0);
+//!
+//!  //a background job that never finishes
+//!  work.submit(1, Either::Left(pending()));
+//!  //an urgent request preempting it
+//!  work.submit(10, Either::Right(ready("urgent")));
+//!
+//!  let mut results = block_on(work.collect::<Vec<_>>());
+//!  results.sort_by_key(|(priority, _)| *priority);
+//!
+//!  assert_eq!(results, vec![
+//!      (1, CancellableResult::Cancelled(())),
+//!      (10, CancellableResult::Finished("urgent")),
+//!  ]);
+//! ```
+
+use super::{
+    tasks::{Task, Tasks},
+    CancellableResult,
+};
+use core::{
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll},
+};
+use futures_core::Stream;
+use std::{boxed::Box, vec::Vec};
+
+type Factory<F> = Box<dyn FnMut() -> F + Send>;
+
+//in-flight work, with the factory to start it again if it's resumable
+struct Work<F> {
+    priority: u32,
+    resume: Option<Factory<F>>,
+}
+
+struct Parked<F> {
+    priority: u32,
+    resume: Factory<F>,
+}
+
+///Container of futures where submitting work preempts the work of lower priority
+#[must_use = "streams do nothing unless polled"]
+pub struct PriorityCanceller<F>
+where
+    F: Future,
+{
+    margin: u32,
+    tasks: Tasks<Work<F>, F>,
+    parked: Vec<Parked<F>>,
+}
+
+impl<F> PriorityCanceller<F>
+where
+    F: Future,
+{
+    ///Create an empty container, where work preempts the work
+    /// whose priority is lower by more than `margin`
+    pub fn new(margin: u32) -> Self { Self { margin, tasks: Tasks::new(), parked: Vec::new() } }
+
+    ///Submit work with `priority`, preempting the in-flight work of lower priority
+    pub fn submit(&mut self, priority: u32, fut: F) { self.start(priority, fut, None) }
+
+    ///Submit work with `priority` built by `make_fut`, preempting the in-flight work of lower
+    /// priority
+    ///
+    ///If this work gets preempted it's parked instead of yielded as cancelled,
+    /// and `make_fut` builds it again once no work preempting it is in flight
+    ///
+    /// # Example
+    /// ```rust
+    /// # use futures::{executor::block_on, future::{pending, ready, Either}, StreamExt};
+    ///  use kyansel::{priority::PriorityCanceller, CancellableResult};
+    ///  use std::sync::{atomic::{AtomicU32, Ordering}, Arc};
+    ///
+    ///  let mut work = PriorityCanceller::new(0);
+    ///  let runs = Arc::new(AtomicU32::new(0));
+    ///
+    ///  //a background job only done the second time it runs
+    ///  let counter = runs.clone();
+    ///  work.submit_resumable(1, move || match counter.fetch_add(1, Ordering::SeqCst) {
+    ///      0 => Either::Left(pending()),
+    ///      run => Either::Right(ready(run + 1)),
+    ///  });
+    ///  work.submit(10, Either::Right(ready(0)));
+    ///
+    ///  let results = block_on(work.collect::<Vec<_>>());
+    ///
+    ///  //the background job was started over once the urgent one was done
+    ///  assert_eq!(results, vec![
+    ///      (10, CancellableResult::Finished(0)),
+    ///      (1, CancellableResult::Finished(2)),
+    ///  ]);
+    ///  assert_eq!(runs.load(Ordering::SeqCst), 2);
+    /// ```
+    pub fn submit_resumable<Mk>(&mut self, priority: u32, mut make_fut: Mk)
+    where
+        Mk: FnMut() -> F + Send + 'static,
+    {
+        let fut = make_fut();
+        self.start(priority, fut, Some(Box::new(make_fut)))
+    }
+
+    ///Cancel all the in-flight work, and drop the parked work
+    pub fn cancel_all(&mut self) {
+        self.parked.clear();
+        for task in self.tasks.iter_mut() {
+            task.data.resume = None;
+            task.cancel();
+        }
+    }
+
+    ///Number of futures in the container, including the parked ones
+    /// and the cancelled ones not yet yielded
+    pub fn len(&self) -> usize { self.tasks.len() + self.parked.len() }
+
+    ///Check if the container has no futures left
+    pub fn is_empty(&self) -> bool { self.tasks.is_empty() && self.parked.is_empty() }
+
+    fn preempts(&self, priority: u32, other: u32) -> bool {
+        priority > other.saturating_add(self.margin)
+    }
+
+    fn start(&mut self, priority: u32, fut: F, resume: Option<Factory<F>>) {
+        for task in self.tasks.iter() {
+            if self.preempts(priority, task.data.priority) {
+                task.cancel();
+            }
+        }
+
+        self.tasks.push(Task::new(Work { priority, resume }, fut));
+    }
+
+    //start again the parked work no in-flight work would preempt, returns if any was started
+    fn resume_parked(&mut self) -> bool {
+        let mut resumed = false;
+
+        let mut i = 0;
+        while i < self.parked.len() {
+            let priority = self.parked[i].priority;
+            let preempted = self.tasks.iter().any(|task| {
+                !task.is_cancelled() && self.preempts(task.data.priority, priority)
+            });
+
+            if preempted {
+                i += 1;
+                continue;
+            }
+
+            let Parked { priority, mut resume } = self.parked.swap_remove(i);
+            let fut = resume();
+            self.start(priority, fut, Some(resume));
+            resumed = true;
+        }
+
+        resumed
+    }
+}
+
+impl<F> Default for PriorityCanceller<F>
+where
+    F: Future,
+{
+    fn default() -> Self { Self::new(0) }
+}
+
+impl<F> Stream for PriorityCanceller<F>
+where
+    F: Future,
+{
+    type Item = (u32, CancellableResult<F::Output, ()>);
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+
+        if this.tasks.is_empty() {
+            this.resume_parked();
+        }
+
+        loop {
+            let (work, result) = match this.tasks.poll_next(cx) {
+                Poll::Ready(Some(next)) => next,
+                Poll::Ready(None) => return Poll::Ready(None),
+                Poll::Pending => return Poll::Pending,
+            };
+
+            match (result, work.resume) {
+                (CancellableResult::Cancelled(()), Some(resume)) => {
+                    this.parked.push(Parked { priority: work.priority, resume });
+                    //started again right away if the work preempting it is done already
+                    this.resume_parked();
+                }
+                (result, _) => {
+                    //the parked work will be polled on the next call
+                    if this.resume_parked() {
+                        cx.waker().wake_by_ref();
+                    }
+                    return Poll::Ready(Some((work.priority, result)));
+                }
+            }
+        }
+    }
+}
+
+impl<F> core::fmt::Debug for PriorityCanceller<F>
+where
+    F: Future,
+{
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        f.debug_struct("PriorityCanceller")
+            .field("margin", &self.margin)
+            .field(
+                "in_flight",
+                &self.tasks.iter().map(|task| task.data.priority).collect::<Vec<_>>(),
+            )
+            .field(
+                "parked",
+                &self.parked.iter().map(|parked| parked.priority).collect::<Vec<_>>(),
+            )
+            .finish()
+    }
+}
@@ -0,0 +1,111 @@
+//! Cancelling child processes, backed by tokio
+//!
+//! Dropping a `tokio::process::Child` doesn't stop the process. With
+//! [`wait_cancellable`](fn.wait_cancellable.html), when the stopper completes the process is asked
+//! to terminate (SIGTERM on Unix), given a grace period to exit, and killed if it's still running.
+//!
+//! # Example
+//! ```rust
+//! # #[tokio::main]
+//! # async fn main() -> std::io::Result<()> {
+//!  use kyansel::process::{wait_cancellable, ChildExit};
+//! #  use futures::future::ready;
+//! #  use std::time::Duration;
+//!
+//!  let mut child = tokio::process::Command::new("sleep").arg("60").spawn()?;
+//!
+//!  let exit = wait_cancellable(&mut child, ready(()), Duration::from_secs(5)).await?;
+//!
+//!  //`sleep` exits on SIGTERM, it doesn't need to be killed
+//! # #[cfg(unix)]
+//!  assert!(matches!(exit, ChildExit::Terminated((), _)));
+//! # Ok(())
+//! # }
+//! ```
+
+use super::{CancellableResult, FutureCancellable};
+use core::future::Future;
+use std::{io, process::ExitStatus, time::Duration};
+use tokio::process::Child;
+
+///How the process waited on by [`wait_cancellable`](fn.wait_cancellable.html) ended
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum ChildExit<S> {
+    ///If the process exited on its own
+    Exited(ExitStatus),
+
+    ///If the stopper completed and the process exited within the grace period
+    Terminated(S, ExitStatus),
+
+    ///If the stopper completed and the process had to be killed
+    Killed(S, ExitStatus),
+}
+
+impl<S> ChildExit<S> {
+    ///Check if the process was stopped because the stopper completed
+    pub fn is_cancelled(&self) -> bool { !matches!(self, Self::Exited(_)) }
+
+    ///Retrieve the exit status of the process
+    pub fn status(&self) -> ExitStatus {
+        match self {
+            Self::Exited(status) | Self::Terminated(_, status) | Self::Killed(_, status) => *status,
+        }
+    }
+}
+
+//ask the process to exit, returns false if it can only be killed
+fn terminate(child: &Child) -> io::Result<bool> {
+    #[cfg(unix)]
+    {
+        //already reaped
+        let pid = match child.id() {
+            Some(pid) => pid,
+            None => return Ok(true),
+        };
+
+        if unsafe { libc::kill(pid as libc::pid_t, libc::SIGTERM) } == 0 {
+            return Ok(true);
+        }
+
+        match io::Error::last_os_error() {
+            //exited in the meantime
+            err if err.raw_os_error() == Some(libc::ESRCH) => Ok(true),
+            err => Err(err),
+        }
+    }
+
+    #[cfg(not(unix))]
+    {
+        let _ = child;
+        Ok(false)
+    }
+}
+
+///Wait for `child` to exit, stopping it when `stopper` completes
+///
+///On cancellation the process is sent SIGTERM and given `grace` to exit before being killed.
+///Outside of Unix there is no graceful termination, so the process is killed right away
+pub async fn wait_cancellable<S>(
+    child: &mut Child,
+    stopper: S,
+    grace: Duration,
+) -> io::Result<ChildExit<S::Output>>
+where
+    S: Future,
+{
+    let s = match child.wait().cancel_with(stopper).await {
+        CancellableResult::Finished(status) => return Ok(ChildExit::Exited(status?)),
+        CancellableResult::Cancelled(s) => s,
+    };
+
+    if terminate(child)? {
+        if let CancellableResult::Finished(status) =
+            child.wait().cancel_with(tokio::time::sleep(grace)).await
+        {
+            return Ok(ChildExit::Terminated(s, status?));
+        }
+    }
+
+    child.kill().await?;
+    Ok(ChildExit::Killed(s, child.wait().await?))
+}
@@ -15,7 +15,7 @@ where
     F: Future,
     S: Future,
 {
-    pub(crate) fn project(self: Pin<&mut Self>) -> Projection<F, S> {
+    pub(crate) fn project(self: Pin<&mut Self>) -> Projection<'_, F, S> {
         unsafe {
             let this = self.get_unchecked_mut();
             Projection {
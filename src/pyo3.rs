@@ -0,0 +1,103 @@
+//! Cancellation across Python's asyncio and Rust futures
+//!
+//!For Rust futures driven on behalf of Python coroutines,
+//! [`py_cancelled`](fn.py_cancelled.html) turns the cancellation of an asyncio future or task,
+//! the one raising `asyncio.CancelledError` in the coroutine, into a stopper,
+//! so a cancelled coroutine cancels the Rust work it awaits.
+//!
+//!The other way around, [`cancel_py_on`](fn.cancel_py_on.html) cancels an asyncio future
+//! or task when a [`CancellationToken`](../token/struct.CancellationToken.html) is cancelled.
+//!
+//! # Example
+//! ```rust
+//! # use futures::{executor::block_on, future::pending};
+//!  use kyansel::{pyo3::py_cancelled, FutureCancellable};
+//!  use pyo3::prelude::*;
+//!
+//!  Python::initialize();
+//!  let rust_work = Python::attach(|py| -> PyResult<_> {
+//!      let event_loop = py.import("asyncio")?.call_method0("new_event_loop")?;
+//!      let py_future = event_loop.call_method0("create_future")?;
+//!
+//!      let rust_work = pending::<()>().cancel_with(py_cancelled(&py_future)?);
+//!
+//!      //the coroutine awaiting `py_future` was cancelled
+//!      py_future.call_method0("cancel")?;
+//!      let tick = py.import("asyncio")?.call_method1("sleep", (0,))?;
+//!      event_loop.call_method1("run_until_complete", (tick,))?;
+//!      Ok(rust_work)
+//!  })
+//!  .unwrap();
+//!
+//!  assert!(block_on(rust_work).is_cancelled());
+//! ```
+
+use super::token::{CancellationToken, WaitForCancellation};
+use core::{
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll},
+};
+use pyo3::{prelude::*, types::PyCFunction};
+
+///Retrieve a stopper completing when the asyncio future or task `py_future` is cancelled
+///
+///Completing `py_future` in any other way doesn't fire the stopper
+pub fn py_cancelled(py_future: &Bound<'_, PyAny>) -> PyResult<WaitForCancellation> {
+    let token = CancellationToken::new();
+    let cancel = token.clone();
+
+    let on_done = move |args: &Bound<'_, pyo3::types::PyTuple>, _: Option<&Bound<'_, _>>| {
+        if args.get_item(0)?.call_method0("cancelled")?.is_truthy()? {
+            cancel.cancel();
+        }
+        PyResult::Ok(())
+    };
+
+    let on_done = PyCFunction::new_closure(py_future.py(), None, None, on_done)?;
+    py_future.call_method1("add_done_callback", (on_done,))?;
+
+    Ok(token.cancelled())
+}
+
+///Future for the [`cancel_py_on`](fn.cancel_py_on.html) bridge
+#[must_use = "futures do nothing unless you `.await` or poll them"]
+pub struct CancelPyOn {
+    cancelled: WaitForCancellation,
+    py_future: Option<Py<PyAny>>,
+}
+
+impl Future for CancelPyOn {
+    type Output = PyResult<()>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
+        let this = self.get_mut();
+
+        if Pin::new(&mut this.cancelled).poll(cx).is_pending() {
+            return Poll::Pending;
+        }
+
+        let py_future = this.py_future.take().expect("CancelPyOn polled after completion");
+        Poll::Ready(Python::attach(|py| {
+            //asyncio objects aren't thread-safe, the cancellation goes through their event loop
+            let py_future = py_future.bind(py);
+            let event_loop = py_future.call_method0("get_loop")?;
+            event_loop.call_method1("call_soon_threadsafe", (py_future.getattr("cancel")?,))?;
+            Ok(())
+        }))
+    }
+}
+
+impl core::fmt::Debug for CancelPyOn {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        f.debug_struct("CancelPyOn").field("cancelled", &self.cancelled).finish()
+    }
+}
+
+///Cancel the asyncio future or task `py_future` when `token` is cancelled
+///
+///The returned future has to be driven, for example by spawning it,
+/// and completes once the cancellation is scheduled on the event loop of `py_future`
+pub fn cancel_py_on(token: &CancellationToken, py_future: Py<PyAny>) -> CancelPyOn {
+    CancelPyOn { cancelled: token.cancelled(), py_future: Some(py_future) }
+}
@@ -0,0 +1,491 @@
+//! Racing multiple copies of work, cancelling the losers
+//!
+//! [`hedge`](fn.hedge.html) starts a backup copy of slow work,
+//! [`race_ok_n`](fn.race_ok_n.html) races many fallible futures for the first success
+//! and [`try_group`](fn.try_group.html) runs them all, bailing out on the first error
+//!
+//! # Example
+//! ```rust
+//! # #[tokio::main]
+//! # async fn main() {
+//!  use kyansel::race::{hedge, Hedged};
+//! #  use std::time::Duration;
+//! #  use tokio::time::sleep;
+//!
+//!  let mut attempt = 0;
+//!  let result = hedge(
+//!      move || {
+//!          attempt += 1;
+//!          //the first attempt hangs, the second one completes right away
+//!          let latency = if attempt == 1 { 60 } else { 0 };
+//!          async move {
+//!              sleep(Duration::from_secs(latency)).await;
+//!              attempt
+//!          }
+//!      },
+//!      sleep(Duration::from_millis(10)),
+//!  )
+//!  .await;
+//!
+//!  assert_eq!(result, Hedged::Backup(2));
+//! # }
+//! ```
+
+use super::{
+    timer::Timer,
+    token::{CancellationToken, WaitForCancellation},
+    Cancellable, CancellableResult, FutureCancellable,
+};
+use core::{
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll},
+    time::Duration,
+};
+use pin_project_lite::pin_project;
+use std::{boxed::Box, vec::Vec};
+
+///Result returned by [`Hedge`](struct.Hedge.html)
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Hedged<T> {
+    ///If the primary copy completed first
+    Primary(T),
+
+    ///If the backup copy completed first
+    Backup(T),
+}
+
+impl<T> Hedged<T> {
+    ///Check if the backup copy won the race
+    pub fn is_backup(&self) -> bool { matches!(self, Self::Backup(_)) }
+
+    ///Retrieve the result regardless of which copy produced it
+    pub fn into_inner(self) -> T {
+        match self {
+            Self::Primary(t) | Self::Backup(t) => t,
+        }
+    }
+}
+
+pin_project! {
+    ///Future for the [`hedge`](fn.hedge.html) combinator
+    #[must_use = "futures do nothing unless you `.await` or poll them"]
+    pub struct Hedge<Fac, F, D> {
+        factory: Fac,
+        #[pin]
+        primary: F,
+        #[pin]
+        delay: Option<D>,
+        #[pin]
+        backup: Option<F>,
+    }
+}
+
+impl<Fac, F, D> Hedge<Fac, F, D> {
+    ///Check if the backup copy has been started
+    pub fn backup_started(&self) -> bool { self.backup.is_some() }
+}
+
+impl<Fac, F, D> Future for Hedge<Fac, F, D>
+where
+    Fac: FnMut() -> F,
+    F: Future,
+    D: Future,
+{
+    type Output = Hedged<F::Output>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
+        let mut this = self.project();
+
+        //always poll the primary first
+        if let Poll::Ready(t) = this.primary.poll(cx) {
+            return Poll::Ready(Hedged::Primary(t));
+        }
+
+        if let Some(delay) = this.delay.as_mut().as_pin_mut() {
+            if delay.poll(cx).is_pending() {
+                return Poll::Pending;
+            }
+
+            this.delay.set(None);
+            this.backup.set(Some((this.factory)()));
+        }
+
+        match this.backup.as_pin_mut() {
+            Some(backup) => backup.poll(cx).map(Hedged::Backup),
+            None => Poll::Pending,
+        }
+    }
+}
+
+impl<Fac, F, D> core::fmt::Debug for Hedge<Fac, F, D> {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        f.debug_struct("Hedge").field("backup_started", &self.backup_started()).finish()
+    }
+}
+
+///Start the work produced by `factory`, and a backup copy of it if the first one
+/// hasn't completed by the time `backup_delay` completes
+///
+///When either copy completes the other one is cancelled, by dropping it.
+///If both are ready in the same poll the primary wins
+pub fn hedge<Fac, F, D>(mut factory: Fac, backup_delay: D) -> Hedge<Fac, F, D>
+where
+    Fac: FnMut() -> F,
+    F: Future,
+    D: Future,
+{
+    let primary = factory();
+    Hedge { factory, primary, delay: Some(backup_delay), backup: None }
+}
+
+///Like [`hedge`](fn.hedge.html), starting the backup copy after `backup_delay` on `timer`
+///
+///Wrap the timer in [`Jittered`](../timer/struct.Jittered.html) to randomize the delay,
+/// so that many clients hedging at once don't all send their backup requests together
+pub fn hedge_after<Fac, F, T>(
+    factory: Fac,
+    timer: &T,
+    backup_delay: Duration,
+) -> Hedge<Fac, F, T::Sleep>
+where
+    Fac: FnMut() -> F,
+    F: Future,
+    T: Timer + ?Sized,
+{
+    hedge(factory, timer.sleep(backup_delay))
+}
+
+///Successful result of [`race_ok_n`](fn.race_ok_n.html)
+#[derive(Debug)]
+pub struct RaceOk<T, E> {
+    ///Position of the winning future
+    pub index: usize,
+
+    ///Output of the winning future
+    pub value: T,
+
+    ///Position and error of every future that failed before the winner completed,
+    /// in order of position
+    pub failed: Vec<(usize, E)>,
+
+    ///Position and outcome of every future still running when the winner completed,
+    /// in order of position
+    ///
+    ///Futures that completed on their last poll show up as
+    /// [`Finished`](../enum.CancellableResult.html#variant.Finished).
+    ///Empty if the outcomes were handed to a cleanup hook, see
+    /// [`RaceOkN::with_cleanup`](struct.RaceOkN.html#method.with_cleanup)
+    pub cancelled: Vec<(usize, LoserResult<T, E>)>,
+}
+
+///Outcome of a future cancelled by [`race_ok_n`](fn.race_ok_n.html)
+/// or [`try_group`](fn.try_group.html)
+pub type LoserResult<T, E> = CancellableResult<Result<T, E>, ()>;
+
+type Racer<F> = Pin<Box<Cancellable<F, WaitForCancellation>>>;
+
+pin_project! {
+    ///Future for the [`race_ok_n`](fn.race_ok_n.html) combinator
+    #[must_use = "futures do nothing unless you `.await` or poll them"]
+    pub struct RaceOkN<F, T, E, C = fn(usize, LoserResult<T, E>)>
+    where
+        F: Future<Output = Result<T, E>>,
+    {
+        token: CancellationToken,
+        racers: Vec<(usize, Racer<F>)>,
+        errors: Vec<(usize, E)>,
+        cleanup: Option<C>,
+    }
+}
+
+impl<F, T, E> RaceOkN<F, T, E>
+where
+    F: Future<Output = Result<T, E>>,
+{
+    ///Hand the position and outcome of every future cancelled by the winner to `cleanup`,
+    /// instead of collecting them in
+    /// [`RaceOk::cancelled`](struct.RaceOk.html#structfield.cancelled)
+    ///
+    /// # Example
+    /// ```rust
+    /// # use futures::{executor::block_on, future::{pending, ready, Either}};
+    ///  use kyansel::race::race_ok_n;
+    ///
+    ///  let replicas = vec![
+    ///      Either::Left(ready(Ok::<_, ()>("slow connection"))),
+    ///      Either::Right(pending()),
+    ///  ];
+    ///
+    ///  let mut closed = Vec::new();
+    ///  let won = block_on(race_ok_n(replicas).with_cleanup(|index, _| closed.push(index)));
+    ///
+    ///  assert_eq!(won.unwrap().value, "slow connection");
+    ///  assert_eq!(closed, vec![1]);
+    /// ```
+    pub fn with_cleanup<C>(self, cleanup: C) -> RaceOkN<F, T, E, C>
+    where
+        C: FnMut(usize, LoserResult<T, E>),
+    {
+        let Self { token, racers, errors, .. } = self;
+        RaceOkN { token, racers, errors, cleanup: Some(cleanup) }
+    }
+}
+
+impl<F, T, E, C> Future for RaceOkN<F, T, E, C>
+where
+    F: Future<Output = Result<T, E>>,
+    C: FnMut(usize, LoserResult<T, E>),
+{
+    type Output = Result<RaceOk<T, E>, Vec<E>>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
+        let this = self.project();
+
+        let mut winner = None;
+        let mut i = 0;
+        while i < this.racers.len() {
+            let (index, racer) = &mut this.racers[i];
+            match racer.as_mut().poll(cx) {
+                Poll::Pending => i += 1,
+                //the token is only cancelled once we have a winner
+                Poll::Ready(CancellableResult::Cancelled(())) => unreachable!(),
+                Poll::Ready(CancellableResult::Finished(Ok(value))) => {
+                    winner = Some((*index, value));
+                    let _ = this.racers.remove(i);
+                    break;
+                }
+                Poll::Ready(CancellableResult::Finished(Err(e))) => {
+                    this.errors.push((*index, e));
+                    let _ = this.racers.remove(i);
+                }
+            }
+        }
+
+        match winner {
+            Some((index, value)) => {
+                this.token.cancel();
+
+                //with the token cancelled every racer resolves on this poll
+                let mut cancelled = Vec::new();
+                for (index, mut racer) in this.racers.drain(..) {
+                    let result = match racer.as_mut().poll(cx) {
+                        Poll::Ready(result) => result,
+                        Poll::Pending => unreachable!(),
+                    };
+
+                    match this.cleanup {
+                        Some(cleanup) => cleanup(index, result),
+                        None => cancelled.push((index, result)),
+                    }
+                }
+
+                let mut failed = core::mem::take(this.errors);
+                failed.sort_by_key(|(index, _)| *index);
+
+                Poll::Ready(Ok(RaceOk { index, value, failed, cancelled }))
+            }
+            None if this.racers.is_empty() => {
+                let mut errors = core::mem::take(this.errors);
+                errors.sort_by_key(|(index, _)| *index);
+                Poll::Ready(Err(errors.into_iter().map(|(_, e)| e).collect()))
+            }
+            None => Poll::Pending,
+        }
+    }
+}
+
+impl<F, T, E, C> core::fmt::Debug for RaceOkN<F, T, E, C>
+where
+    F: Future<Output = Result<T, E>>,
+{
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        f.debug_struct("RaceOkN")
+            .field("running", &self.racers.len())
+            .field("failed", &self.errors.len())
+            .finish()
+    }
+}
+
+///Run all the futures concurrently, resolving with the first one that succeeds
+///
+///Once a future succeeds all the others are cancelled and polled one last time,
+/// their outcomes are collected in [`RaceOk::cancelled`](struct.RaceOk.html#structfield.cancelled)
+/// or handed to the hook set with [`with_cleanup`](struct.RaceOkN.html#method.with_cleanup),
+/// so any cleanup can be performed. The errors of the futures that failed before are kept in
+/// [`RaceOk::failed`](struct.RaceOk.html#structfield.failed).
+///
+///If all the futures fail, resolves with all the errors in order of position
+///
+/// # Example
+/// ```rust
+/// # use futures::{executor::block_on, future::{pending, ready, Either}};
+///  use kyansel::race::race_ok_n;
+///
+///  let replicas = vec![
+///      Either::Left(ready(Err("unreachable replica"))),
+///      Either::Right(pending()),
+///      Either::Left(ready(Ok("response"))),
+///  ];
+///
+///  let won = block_on(race_ok_n(replicas)).unwrap();
+///
+///  assert_eq!((won.index, won.value), (2, "response"));
+///  assert_eq!(won.failed, vec![(0, "unreachable replica")]);
+///  assert!(won.cancelled.iter().all(|(index, result)| *index == 1 && result.is_cancelled()));
+/// ```
+pub fn race_ok_n<I, F, T, E>(futs: I) -> RaceOkN<F, T, E>
+where
+    I: IntoIterator<Item = F>,
+    F: Future<Output = Result<T, E>>,
+{
+    let token = CancellationToken::new();
+    let racers = futs
+        .into_iter()
+        .map(|fut| Box::pin(fut.cancel_with(token.cancelled())))
+        .enumerate()
+        .collect();
+
+    RaceOkN { token, racers, errors: Vec::new(), cleanup: None }
+}
+
+///Error returned by [`try_group`](fn.try_group.html)
+#[derive(Debug)]
+pub struct GroupError<T, E> {
+    ///Position of the future that failed
+    pub index: usize,
+
+    ///Error of the future that failed
+    pub error: E,
+
+    ///Position and outcome of every other future, in order of position
+    ///
+    ///Futures that completed before the error show up as
+    /// [`Finished`](../enum.CancellableResult.html#variant.Finished)
+    pub siblings: Vec<(usize, LoserResult<T, E>)>,
+}
+
+pin_project! {
+    ///Future for the [`try_group`](fn.try_group.html) combinator
+    #[must_use = "futures do nothing unless you `.await` or poll them"]
+    pub struct TryGroup<F, T, E>
+    where
+        F: Future<Output = Result<T, E>>,
+    {
+        token: CancellationToken,
+        racers: Vec<(usize, Racer<F>)>,
+        done: Vec<(usize, T)>,
+    }
+}
+
+impl<F, T, E> Future for TryGroup<F, T, E>
+where
+    F: Future<Output = Result<T, E>>,
+{
+    type Output = Result<Vec<T>, GroupError<T, E>>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
+        let this = self.project();
+
+        let mut failed = None;
+        let mut i = 0;
+        while i < this.racers.len() {
+            let (index, racer) = &mut this.racers[i];
+            match racer.as_mut().poll(cx) {
+                Poll::Pending => i += 1,
+                //the token is only cancelled once a future failed
+                Poll::Ready(CancellableResult::Cancelled(())) => unreachable!(),
+                Poll::Ready(CancellableResult::Finished(Ok(value))) => {
+                    this.done.push((*index, value));
+                    let _ = this.racers.remove(i);
+                }
+                Poll::Ready(CancellableResult::Finished(Err(error))) => {
+                    failed = Some((*index, error));
+                    let _ = this.racers.remove(i);
+                    break;
+                }
+            }
+        }
+
+        match failed {
+            Some((index, error)) => {
+                this.token.cancel();
+
+                //with the token cancelled every racer resolves on this poll
+                let mut siblings: Vec<_> = this
+                    .racers
+                    .drain(..)
+                    .map(|(index, mut racer)| match racer.as_mut().poll(cx) {
+                        Poll::Ready(result) => (index, result),
+                        Poll::Pending => unreachable!(),
+                    })
+                    .chain(
+                        this.done
+                            .drain(..)
+                            .map(|(index, value)| (index, CancellableResult::Finished(Ok(value)))),
+                    )
+                    .collect();
+                siblings.sort_by_key(|(index, _)| *index);
+
+                Poll::Ready(Err(GroupError { index, error, siblings }))
+            }
+            None if this.racers.is_empty() => {
+                let mut done = core::mem::take(this.done);
+                done.sort_by_key(|(index, _)| *index);
+                Poll::Ready(Ok(done.into_iter().map(|(_, value)| value).collect()))
+            }
+            None => Poll::Pending,
+        }
+    }
+}
+
+impl<F, T, E> core::fmt::Debug for TryGroup<F, T, E>
+where
+    F: Future<Output = Result<T, E>>,
+{
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        f.debug_struct("TryGroup")
+            .field("running", &self.racers.len())
+            .field("finished", &self.done.len())
+            .finish()
+    }
+}
+
+///Run all the futures concurrently, resolving with all their outputs in order of position
+///
+///As soon as a future fails all the others are cancelled and polled one last time,
+/// the error is returned together with the outcome of every sibling
+///
+/// # Example
+/// ```rust
+/// # use futures::{executor::block_on, future::{pending, ready, Either}};
+///  use kyansel::{race::try_group, CancellableResult};
+///
+///  let jobs = vec![
+///      Either::Left(ready(Ok(1))),
+///      Either::Right(pending()),
+///      Either::Left(ready(Err("disk full"))),
+///  ];
+///
+///  let failed = block_on(try_group(jobs)).unwrap_err();
+///
+///  assert_eq!((failed.index, failed.error), (2, "disk full"));
+///  assert_eq!(failed.siblings, vec![
+///      (0, CancellableResult::Finished(Ok(1))),
+///      (1, CancellableResult::Cancelled(())),
+///  ]);
+/// ```
+pub fn try_group<I, F, T, E>(futs: I) -> TryGroup<F, T, E>
+where
+    I: IntoIterator<Item = F>,
+    F: Future<Output = Result<T, E>>,
+{
+    let token = CancellationToken::new();
+    let racers = futs
+        .into_iter()
+        .map(|fut| Box::pin(fut.cancel_with(token.cancelled())))
+        .enumerate()
+        .collect();
+
+    TryGroup { token, racers, done: Vec::new() }
+}
@@ -0,0 +1,129 @@
+//! Cancelling parallel jobs running on rayon
+//!
+//![`spawn_cancellable`](fn.spawn_cancellable.html) runs a job on the rayon pool and returns a
+//! future resolving to its output, or resolving as cancelled as soon as a
+//! [`CancellationToken`](../token/struct.CancellationToken.html) fires,
+//! so CPU-bound work can be tied to the lifetime of an async request.
+//!
+//!The job receives the token to check in its splits, and
+//! [`until_cancelled`](fn.until_cancelled.html) stops a parallel iterator once it fires:
+//! the pool work stops at the next check, while the future has already resolved.
+//!
+//! # Example
+//! ```rust
+//! # use futures::executor::block_on;
+//!  use kyansel::{rayon::{spawn_cancellable, until_cancelled}, token::CancellationToken};
+//!  use rayon::{iter::repeat, prelude::*};
+//!
+//!  let token = CancellationToken::new();
+//!
+//!  let job = spawn_cancellable(&token, |token| {
+//!      until_cancelled((0..1_000u64).into_par_iter(), &token).map(|i| i * i).sum::<u64>()
+//!  });
+//!  assert_eq!(block_on(job).finished(), Some(332_833_500));
+//!
+//!  //the job never ends on its own, but stops once the token fires
+//!  let job = spawn_cancellable(&token, |token| until_cancelled(repeat(()), &token).count());
+//!  token.cancel();
+//!  assert!(block_on(job).is_cancelled());
+//! ```
+
+use super::{
+    token::{CancellationToken, WaitForCancellation},
+    CancellableResult,
+};
+use core::{
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll, Waker},
+};
+use rayon::iter::ParallelIterator;
+use std::{
+    panic::{self, AssertUnwindSafe},
+    sync::{Arc, Mutex},
+    thread,
+};
+
+struct Slot<T> {
+    output: Option<thread::Result<T>>,
+    waker: Option<Waker>,
+}
+
+///Future for the [`spawn_cancellable`](fn.spawn_cancellable.html) function
+///
+///Dropping it doesn't stop the job, cancel its token for that
+#[must_use = "futures do nothing unless you `.await` or poll them"]
+pub struct RayonJob<T> {
+    slot: Arc<Mutex<Slot<T>>>,
+    cancelled: WaitForCancellation,
+}
+
+impl<T> Future for RayonJob<T> {
+    type Output = CancellableResult<T, ()>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
+        let this = self.get_mut();
+
+        //always check the job first
+        {
+            let mut slot = this.slot.lock().unwrap();
+            match slot.output.take() {
+                Some(Ok(t)) => return Poll::Ready(CancellableResult::Finished(t)),
+                Some(Err(payload)) => panic::resume_unwind(payload),
+                None => slot.waker = Some(cx.waker().clone()),
+            }
+        }
+
+        Pin::new(&mut this.cancelled).poll(cx).map(CancellableResult::Cancelled)
+    }
+}
+
+impl<T> core::fmt::Debug for RayonJob<T> {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        f.debug_struct("RayonJob").field("cancelled", &self.cancelled).finish()
+    }
+}
+
+///Run `job` on the global rayon pool, handing it a clone of `token` to check in its splits
+///
+///The returned future resolves as cancelled as soon as `token` fires,
+/// without waiting for the job to notice.
+///If the job panics, the panic is resumed when the future is polled
+pub fn spawn_cancellable<Fun, T>(token: &CancellationToken, job: Fun) -> RayonJob<T>
+where
+    Fun: FnOnce(CancellationToken) -> T + Send + 'static,
+    T: Send + 'static,
+{
+    let slot = Arc::new(Mutex::new(Slot { output: None, waker: None }));
+
+    let job_token = token.clone();
+    let job_slot = slot.clone();
+    rayon::spawn(move || {
+        let output = panic::catch_unwind(AssertUnwindSafe(|| job(job_token)));
+
+        let waker = {
+            let mut slot = job_slot.lock().unwrap();
+            slot.output = Some(output);
+            slot.waker.take()
+        };
+        if let Some(waker) = waker {
+            waker.wake();
+        }
+    });
+
+    RayonJob { slot, cancelled: token.cancelled() }
+}
+
+///Stop `iter` once `token` fires
+///
+///Every item checks the token, the items already being processed are still completed
+pub fn until_cancelled<I>(
+    iter: I,
+    token: &CancellationToken,
+) -> impl ParallelIterator<Item = I::Item>
+where
+    I: ParallelIterator,
+{
+    let token = token.clone();
+    iter.take_any_while(move |_| !token.is_cancelled())
+}
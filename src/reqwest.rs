@@ -0,0 +1,134 @@
+//! Cancellation and deadlines for outgoing reqwest requests
+//!
+//![`CancelMiddleware`](struct.CancelMiddleware.html) is a `reqwest-middleware` middleware tying
+//! each request to the cancellation scope of its caller: the request is aborted when its
+//! [`CancellationToken`](../token/struct.CancellationToken.html) is cancelled,
+//! and bounded by its [`Deadline`](struct.Deadline.html), which can be forwarded to the server
+//! in a header.
+//!
+//!The token and the deadline are attached to each request as extensions, with
+//! `RequestBuilder::with_extension`, falling back to the middleware's token if there's none.
+//!
+//! # Example
+//! ```rust
+//! # #[tokio::main]
+//! # async fn main() {
+//!  use kyansel::{reqwest::CancelMiddleware, token::CancellationToken};
+//!  use reqwest_middleware::{ClientBuilder, Error};
+//! #  use tokio::net::TcpListener;
+//!
+//!  let client = ClientBuilder::new(reqwest::Client::new()).with(CancelMiddleware::new()).build();
+//!
+//!  //a server that never responds
+//!  let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+//!  let url = format!("http://{}", listener.local_addr().unwrap());
+//!  tokio::spawn(async move {
+//!      let _connection = listener.accept().await;
+//!      std::future::pending::<()>().await
+//!  });
+//!
+//!  let token = CancellationToken::new();
+//!  let request = client.get(url).with_extension(token.clone()).send();
+//!
+//!  token.cancel();
+//!  assert!(matches!(request.await, Err(Error::Middleware(_))));
+//! # }
+//! ```
+
+use super::{token::CancellationToken, CancellableResult, FutureCancellable};
+use core::{convert::TryFrom, fmt};
+use http::Extensions;
+use reqwest::{
+    header::{HeaderName, HeaderValue},
+    Request, Response,
+};
+use reqwest_middleware::{Error, Middleware, Next, Result};
+use std::{boxed::Box, time::Instant};
+
+///Point in time a request must complete by, attached as an extension
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct Deadline(pub Instant);
+
+///Error of a request aborted because its token was cancelled
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct RequestCancelled;
+
+impl fmt::Display for RequestCancelled {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result { f.write_str("request cancelled") }
+}
+
+impl std::error::Error for RequestCancelled {}
+
+///Error of a request whose deadline passed before it was sent
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct DeadlineExceeded;
+
+impl fmt::Display for DeadlineExceeded {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result { f.write_str("deadline exceeded") }
+}
+
+impl std::error::Error for DeadlineExceeded {}
+
+///Middleware aborting requests when their token is cancelled, and bounding them by their deadline
+///
+///The token of a request is its `CancellationToken` extension,
+/// or the one set with [`with_token`](struct.CancelMiddleware.html#method.with_token).
+///A [`Deadline`](struct.Deadline.html) extension sets the timeout of the request,
+/// unless it already had a shorter one
+#[derive(Debug, Clone, Default)]
+pub struct CancelMiddleware {
+    token: Option<CancellationToken>,
+    timeout_header: Option<HeaderName>,
+}
+
+impl CancelMiddleware {
+    ///Create a middleware only using the extensions of each request
+    pub fn new() -> Self { Self::default() }
+
+    ///Abort the requests without a token of their own when `token` is cancelled
+    pub fn with_token(mut self, token: CancellationToken) -> Self {
+        self.token = Some(token);
+        self
+    }
+
+    ///Send the time left until the deadline, in milliseconds, in the `name` header
+    pub fn timeout_header(mut self, name: HeaderName) -> Self {
+        self.timeout_header = Some(name);
+        self
+    }
+}
+
+#[async_trait::async_trait]
+impl Middleware for CancelMiddleware {
+    async fn handle(
+        &self,
+        mut req: Request,
+        extensions: &mut Extensions,
+        next: Next<'_>,
+    ) -> Result<Response> {
+        if let Some(Deadline(deadline)) = extensions.get::<Deadline>().copied() {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            if remaining.is_zero() {
+                return Err(Error::middleware(DeadlineExceeded));
+            }
+
+            let timeout = req.timeout_mut();
+            *timeout = Some(timeout.map_or(remaining, |timeout| timeout.min(remaining)));
+
+            if let Some(name) = &self.timeout_header {
+                let millis = u64::try_from(remaining.as_millis()).unwrap_or(u64::MAX);
+                req.headers_mut().insert(name.clone(), HeaderValue::from(millis));
+            }
+        }
+
+        let token = match extensions.get::<CancellationToken>().or(self.token.as_ref()) {
+            Some(token) => token.clone(),
+            None => return next.run(req, extensions).await,
+        };
+
+        match next.run(req, extensions).cancel_with(token.cancelled()).await {
+            CancellableResult::Finished(response) => response,
+            CancellableResult::Cancelled(()) => Err(Error::middleware(RequestCancelled)),
+        }
+    }
+}
@@ -0,0 +1,239 @@
+//! Running a future again when it gets cancelled
+//!
+//!Some stoppers signal transient conditions, like a configuration reload, where the work
+//! should start over rather than be abandoned.
+//![`retry_on_cancel`](fn.retry_on_cancel.html) rebuilds the cancellable future with a factory
+//! every time it's cancelled, as long as its [`RetryPolicy`](trait.RetryPolicy.html) allows,
+//! and only surfaces the cancellation once the policy is exhausted.
+//!
+//!Async closures can be used as factories, and the ones mutating their captures,
+//! which only implement `AsyncFnMut`, with
+//! [`retry_on_cancel_async`](fn.retry_on_cancel_async.html).
+//!
+//! # Example
+//! ```rust
+//! # use futures::{executor::block_on, future::pending};
+//!  use kyansel::{
+//!      retry::{retry_on_cancel, MaxAttempts},
+//!      FutureCancellable,
+//!  };
+//!  use std::cell::Cell;
+//!
+//!  let attempts = Cell::new(0);
+//!
+//!  //the first two attempts are interrupted by a reload, the third one goes through
+//!  let job = retry_on_cancel(
+//!      || {
+//!          attempts.set(attempts.get() + 1);
+//!          let attempt = attempts.get();
+//!
+//!          let work = async move {
+//!              if attempt < 3 {
+//!                  pending::<()>().await;
+//!              }
+//!              attempt
+//!          };
+//!          let reload = async move {
+//!              if attempt >= 3 {
+//!                  pending::<()>().await;
+//!              }
+//!              "reload"
+//!          };
+//!
+//!          work.cancel_with(reload)
+//!      },
+//!      MaxAttempts(5),
+//!  );
+//!
+//!  assert_eq!(block_on(job).finished(), Some(3));
+//! ```
+
+use super::CancellableResult;
+use core::{
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll},
+};
+use pin_project_lite::pin_project;
+
+///Decides whether a cancelled future is run again
+pub trait RetryPolicy<S> {
+    ///Check if the future should be run again, after `attempts` attempts
+    /// of which the last was cancelled with `reason`
+    fn should_retry(&mut self, attempts: u32, reason: &S) -> bool;
+}
+
+impl<S, Fun> RetryPolicy<S> for Fun
+where
+    Fun: FnMut(u32, &S) -> bool,
+{
+    fn should_retry(&mut self, attempts: u32, reason: &S) -> bool { self(attempts, reason) }
+}
+
+///Policy running the future at most this many times, whatever the reason of the cancellation
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct MaxAttempts(pub u32);
+
+impl<S> RetryPolicy<S> for MaxAttempts {
+    fn should_retry(&mut self, attempts: u32, _: &S) -> bool { attempts < self.0 }
+}
+
+pin_project! {
+    ///Future for the [`retry_on_cancel`](fn.retry_on_cancel.html) combinator
+    #[must_use = "futures do nothing unless you `.await` or poll them"]
+    pub struct RetryOnCancel<Mk, Fut, P> {
+        make_fut: Mk,
+        policy: P,
+        attempts: u32,
+        #[pin]
+        current: Fut,
+    }
+}
+
+impl<Mk, Fut, P> RetryOnCancel<Mk, Fut, P> {
+    ///Retrieve how many times the future was built, including the one running
+    pub const fn attempts(&self) -> u32 { self.attempts }
+}
+
+impl<Mk, Fut, P, T, S> Future for RetryOnCancel<Mk, Fut, P>
+where
+    Mk: FnMut() -> Fut,
+    Fut: Future<Output = CancellableResult<T, S>>,
+    P: RetryPolicy<S>,
+{
+    type Output = CancellableResult<T, S>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
+        let this = self.project();
+        let mut current = this.current;
+
+        loop {
+            match current.as_mut().poll(cx) {
+                Poll::Ready(CancellableResult::Cancelled(s))
+                    if this.policy.should_retry(*this.attempts, &s) =>
+                {
+                    *this.attempts += 1;
+                    current.set((this.make_fut)());
+                }
+                Poll::Ready(result) => return Poll::Ready(result),
+                Poll::Pending => return Poll::Pending,
+            }
+        }
+    }
+}
+
+impl<Mk, Fut, P> core::fmt::Debug for RetryOnCancel<Mk, Fut, P>
+where
+    Fut: core::fmt::Debug,
+    P: core::fmt::Debug,
+{
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        f.debug_struct("RetryOnCancel")
+            .field("policy", &self.policy)
+            .field("attempts", &self.attempts)
+            .field("current", &self.current)
+            .finish()
+    }
+}
+
+///Run the cancellable future built by `make_fut`, building and running it again
+/// every time it's cancelled, as long as `policy` allows
+///
+///Resolves to the output of the first attempt that finishes,
+/// or to the reason of the last cancellation once the policy is exhausted
+pub fn retry_on_cancel<Mk, Fut, P, T, S>(mut make_fut: Mk, policy: P) -> RetryOnCancel<Mk, Fut, P>
+where
+    Mk: FnMut() -> Fut,
+    Fut: Future<Output = CancellableResult<T, S>>,
+    P: RetryPolicy<S>,
+{
+    let current = make_fut();
+    RetryOnCancel { make_fut, policy, attempts: 1, current }
+}
+
+pin_project! {
+    ///Future for the [`retry_on_cancel_async`](fn.retry_on_cancel_async.html) combinator
+    #[must_use = "futures do nothing unless you `.await` or poll them"]
+    pub struct RetryOnCancelAsync<Fut> {
+        #[pin]
+        inner: Fut,
+    }
+}
+
+impl<Fut, T, S> Future for RetryOnCancelAsync<Fut>
+where
+    Fut: Future<Output = CancellableResult<T, S>>,
+{
+    type Output = CancellableResult<T, S>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
+        self.project().inner.poll(cx)
+    }
+}
+
+impl<Fut> core::fmt::Debug for RetryOnCancelAsync<Fut> {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        f.debug_struct("RetryOnCancelAsync").finish_non_exhaustive()
+    }
+}
+
+///Run the cancellable future built by the async closure `make_fut`, running it again
+/// every time it's cancelled, as long as `policy` allows
+///
+///Like [`retry_on_cancel`](fn.retry_on_cancel.html), but the closure can mutate its captures
+/// across attempts, since each attempt is done before the next one is built
+///
+/// # Example
+/// ```rust
+/// # use futures::{executor::block_on, future::{pending, ready}};
+///  use kyansel::{
+///      retry::{retry_on_cancel_async, MaxAttempts},
+///      FutureCancellable,
+///  };
+///
+///  let mut log = Vec::new();
+///
+///  let job = retry_on_cancel_async(
+///      async || {
+///          log.push("attempt");
+///          let attempt = log.len();
+///
+///          //interrupted by a reload until the third attempt
+///          let work = async move {
+///              if attempt < 3 {
+///                  pending::<()>().await;
+///              }
+///              attempt
+///          };
+///          work.cancel_with(ready("reload")).await
+///      },
+///      MaxAttempts(5),
+///  );
+///
+///  assert_eq!(block_on(job).finished(), Some(3));
+///  assert_eq!(log.len(), 3);
+/// ```
+pub fn retry_on_cancel_async<Mk, P, T, S>(
+    mut make_fut: Mk,
+    mut policy: P,
+) -> RetryOnCancelAsync<impl Future<Output = CancellableResult<T, S>>>
+where
+    Mk: AsyncFnMut() -> CancellableResult<T, S>,
+    P: RetryPolicy<S>,
+{
+    //the attempts borrow the closure, so they can only be driven from an async block
+    let inner = async move {
+        let mut attempts = 1;
+
+        loop {
+            match make_fut().await {
+                CancellableResult::Cancelled(s) if policy.should_retry(attempts, &s) => {
+                    attempts += 1
+                }
+                result => return result,
+            }
+        }
+    };
+
+    RetryOnCancelAsync { inner }
+}
@@ -0,0 +1,29 @@
+//xorshift64 seeded through splitmix64: fast and reproducible, in no way cryptographically secure
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct XorShift {
+    state: u64,
+}
+
+const GOLDEN_GAMMA: u64 = 0x9E37_79B9_7F4A_7C15;
+
+impl XorShift {
+    pub(crate) const fn seeded(seed: u64) -> Self { Self::stream(seed, 0) }
+
+    //the `stream`th generator of `seed`, each one seeded by the next splitmix64 output
+    pub(crate) const fn stream(seed: u64, stream: u64) -> Self {
+        let mut z = seed.wrapping_add(stream.wrapping_add(1).wrapping_mul(GOLDEN_GAMMA));
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^= z >> 31;
+
+        //xorshift gets stuck on 0
+        Self { state: if z == 0 { GOLDEN_GAMMA } else { z } }
+    }
+
+    pub(crate) fn next_u64(&mut self) -> u64 {
+        self.state ^= self.state << 13;
+        self.state ^= self.state >> 7;
+        self.state ^= self.state << 17;
+        self.state
+    }
+}
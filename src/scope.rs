@@ -0,0 +1,256 @@
+//! Structured cancellation scopes
+//!
+//! A [`Scope`](struct.Scope.html) runs a body future alongside every cancellable spawned on it.
+//! When the body completes, whether normally or by returning early with an error,
+//! all the spawned futures are cancelled and awaited before the scope itself completes.
+//!
+//! Futures spawned with [`spawn_named`](struct.Scope.html#method.spawn_named) are listed
+//! with their name by [`running`](struct.Scope.html#method.running) until they complete,
+//! to tell which ones a cancellation interrupted.
+//!
+//! The [`scope!`](../macro.scope.html) macro is a shorthand for [`scope`](fn.scope.html)
+//!
+//! # Example
+//! ```rust
+//! # use futures::{executor::block_on, future::pending};
+//!  use kyansel::scope::scope;
+//!
+//!  let result = block_on(scope(|s| async move {
+//!      //this will be cancelled when the body completes
+//!      s.spawn(pending::<()>());
+//!
+//!      Err::<(), _>("early exit")?;
+//!      Ok(())
+//!  }));
+//!
+//!  assert_eq!(result, Err("early exit"));
+//! ```
+
+use super::{
+    id::CancelId,
+    token::{CancellationToken, WaitForCancellation},
+    Cancellable, FutureCancellable,
+};
+use core::{
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll, Waker},
+};
+use pin_project_lite::pin_project;
+use std::{boxed::Box, vec::Vec};
+use std::sync::{Arc, Mutex};
+
+type Task<'a> = Pin<Box<dyn Future<Output = ()> + Send + 'a>>;
+
+///Handle used to spawn cancellables on a scope
+///
+///Created by [`scope`](fn.scope.html), it can be cloned and moved into the spawned futures
+#[derive(Clone)]
+pub struct Scope<'a> {
+    token: CancellationToken,
+    shared: Arc<Mutex<Shared<'a>>>,
+}
+
+struct Shared<'a> {
+    incoming: Vec<Task<'a>>,
+    listed: Vec<(CancelId, Option<&'static str>)>,
+    waker: Option<Waker>,
+}
+
+impl<'a> Scope<'a> {
+    fn new() -> Self {
+        Self {
+            token: CancellationToken::new(),
+            shared: Arc::new(Mutex::new(Shared {
+                incoming: Vec::new(),
+                listed: Vec::new(),
+                waker: None,
+            })),
+        }
+    }
+
+    ///Spawn a future on the scope, returning its [`CancelId`](../id/struct.CancelId.html)
+    ///
+    ///The future will be driven together with the scope body
+    /// and cancelled once the body completes
+    pub fn spawn<F>(&self, fut: F) -> CancelId
+    where
+        F: Future + Send + 'a,
+    {
+        self.push(fut.cancel_with(self.token.cancelled()))
+    }
+
+    ///Spawn a future on the scope like [`spawn`](struct.Scope.html#method.spawn),
+    /// naming it with [`Cancellable::named`](../struct.Cancellable.html#method.named)
+    ///
+    ///The name is listed by [`running`](struct.Scope.html#method.running)
+    /// until the future completes
+    pub fn spawn_named<F>(&self, name: &'static str, fut: F) -> CancelId
+    where
+        F: Future + Send + 'a,
+    {
+        self.push(fut.cancel_with(self.token.cancelled()).named(name))
+    }
+
+    fn push<F>(&self, cancellable: Cancellable<F, WaitForCancellation>) -> CancelId
+    where
+        F: Future + Send + 'a,
+    {
+        let name = cancellable.name();
+        let cancellable = cancellable.identified();
+        let id = cancellable.id();
+
+        //weak, the task is stored in the shared state itself
+        let unlist = Arc::downgrade(&self.shared);
+        let mut shared = self.shared.lock().unwrap();
+        shared.listed.push((id, name));
+        shared.incoming.push(Box::pin(async move {
+            let _ = cancellable.await;
+
+            if let Some(shared) = unlist.upgrade() {
+                shared.lock().unwrap().listed.retain(|&(listed, _)| listed != id);
+            }
+        }));
+
+        if let Some(waker) = shared.waker.take() {
+            waker.wake();
+        }
+
+        id
+    }
+
+    ///Retrieve the id and name of the futures spawned on the scope that are still running,
+    /// in the order they were spawned
+    ///
+    ///Futures spawned with [`spawn`](struct.Scope.html#method.spawn) have no name
+    ///
+    /// # Example
+    /// ```rust
+    /// # use futures::{executor::block_on, future::pending};
+    ///  use kyansel::scope::scope;
+    ///
+    ///  let running = block_on(scope(|s| async move {
+    ///      let id = s.spawn_named("sync-job", pending::<()>());
+    ///      s.spawn(pending::<()>());
+    ///
+    ///      let running = s.running();
+    ///      assert_eq!(running[0], (id, Some("sync-job")));
+    ///      running.len()
+    ///  }));
+    ///
+    ///  assert_eq!(running, 2);
+    /// ```
+    pub fn running(&self) -> Vec<(CancelId, Option<&'static str>)> {
+        self.shared.lock().unwrap().listed.clone()
+    }
+
+    ///Cancel all the futures spawned on the scope, including the ones spawned from now on
+    ///
+    ///The body is not cancelled
+    pub fn cancel(&self) { self.token.cancel() }
+
+    ///Retrieve the token used to cancel the spawned futures
+    pub fn token(&self) -> &CancellationToken { &self.token }
+}
+
+impl core::fmt::Debug for Scope<'_> {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        f.debug_struct("Scope").field("token", &self.token).finish()
+    }
+}
+
+pin_project! {
+    ///Future returned by [`scope`](fn.scope.html)
+    #[must_use = "futures do nothing unless you `.await` or poll them"]
+    pub struct ScopeFuture<'a, Fut>
+    where
+        Fut: Future,
+    {
+        #[pin]
+        body: Fut,
+        output: Option<Fut::Output>,
+        running: Vec<Task<'a>>,
+        scope: Scope<'a>,
+    }
+}
+
+impl<Fut> Future for ScopeFuture<'_, Fut>
+where
+    Fut: Future,
+{
+    type Output = Fut::Output;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
+        let this = self.project();
+
+        if this.output.is_none() {
+            if let Poll::Ready(output) = this.body.poll(cx) {
+                *this.output = Some(output);
+                this.scope.cancel();
+            }
+        }
+
+        this.running.retain_mut(|task| task.as_mut().poll(cx).is_pending());
+
+        //newly spawned tasks can spawn more tasks when polled
+        loop {
+            let mut incoming = {
+                let mut shared = this.scope.shared.lock().unwrap();
+                if shared.incoming.is_empty() {
+                    //register before releasing the lock so spawns from other threads wake us
+                    shared.waker = Some(cx.waker().clone());
+                    break;
+                }
+                std::mem::take(&mut shared.incoming)
+            };
+
+            incoming.retain_mut(|task| task.as_mut().poll(cx).is_pending());
+            this.running.extend(incoming);
+        }
+
+        match this.output.take() {
+            Some(output) if this.running.is_empty() => Poll::Ready(output),
+            output => {
+                *this.output = output;
+                Poll::Pending
+            }
+        }
+    }
+}
+
+///Run `body` with a [`Scope`](struct.Scope.html) to spawn cancellables on
+///
+///The returned future resolves with the output of the body,
+/// after all the spawned futures have been cancelled and awaited
+pub fn scope<'a, F, Fut>(body: F) -> ScopeFuture<'a, Fut>
+where
+    F: FnOnce(Scope<'a>) -> Fut,
+    Fut: Future,
+{
+    let scope = Scope::new();
+
+    ScopeFuture { body: body(scope.clone()), output: None, running: Vec::new(), scope }
+}
+
+///Create a [`Scope`](scope/struct.Scope.html) and run the block with it
+///
+///All the cancellables spawned on the scope are cancelled and awaited when the block exits,
+/// see [`scope`](scope/fn.scope.html) for details
+///
+/// # Example
+/// ```rust
+/// # use futures::{executor::block_on, future::{pending, ready}};
+///  let answer = block_on(kyansel::scope! { |s| {
+///      s.spawn(pending::<()>());
+///      s.spawn(ready(()));
+///      42
+///  }});
+///
+///  assert_eq!(answer, 42);
+/// ```
+#[macro_export]
+macro_rules! scope {
+    (|$scope:ident| $body:block) => {
+        $crate::scope::scope(move |$scope| async move $body)
+    };
+}
@@ -0,0 +1,247 @@
+//! Coordinated shutdown of tokio tasks, with a report of how each one ended
+//!
+//!A [`Shutdown`](struct.Shutdown.html) spawns named tasks, each given a child of its
+//! [`CancellationToken`](../token/struct.CancellationToken.html).
+//![`shutdown`](struct.Shutdown.html#method.shutdown) cancels the token, gives the tasks a grace
+//! period to wind down and aborts the ones still running, then returns a
+//! [`ShutdownReport`](struct.ShutdownReport.html) telling what each task was doing when the
+//! process went down, for post-incident reviews.
+//!
+//!The report implements `Display`, one line per task, to be logged as is,
+//! and `Serialize` with the `serde` feature
+//!
+//! # Example
+//! ```rust
+//! # #[tokio::main(flavor = "current_thread", start_paused = true)]
+//! # async fn main() {
+//!  use kyansel::shutdown::{Shutdown, TaskOutcome};
+//!  use std::time::Duration;
+//!
+//!  let mut coordinator = Shutdown::new();
+//!
+//!  coordinator.spawn("flush", |_| async {});
+//!  coordinator.spawn("consumer", |token| async move { token.cancelled().await });
+//!  coordinator.spawn("stuck", |_| std::future::pending());
+//! #  tokio::task::yield_now().await;
+//!
+//!  let report = coordinator.shutdown(Duration::from_secs(5)).await;
+//!  println!("{}", report);
+//!
+//!  let outcomes: Vec<_> = report.tasks().iter().map(|task| task.outcome).collect();
+//!  assert_eq!(outcomes, [TaskOutcome::Finished, TaskOutcome::Cancelled, TaskOutcome::TimedOut]);
+//!  assert!(!report.is_clean());
+//! # }
+//! ```
+
+use super::{id::CancelId, token::CancellationToken, FutureCancellable};
+use core::{fmt, future::Future, time::Duration};
+use std::{collections::HashMap, string::String, time::Instant, vec::Vec};
+use tokio::task::{Id, JoinError, JoinSet};
+
+///How a task ended
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize), serde(rename_all = "snake_case"))]
+pub enum TaskOutcome {
+    ///If the task completed before the shutdown started
+    Finished,
+
+    ///If the task wound down within the grace period
+    Cancelled,
+
+    ///If the task was still running after the grace period, and was aborted
+    TimedOut,
+
+    ///If the task panicked
+    Panicked,
+}
+
+impl fmt::Display for TaskOutcome {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            Self::Finished => "finished",
+            Self::Cancelled => "cancelled",
+            Self::TimedOut => "timed out",
+            Self::Panicked => "panicked",
+        })
+    }
+}
+
+///How a task of a [`Shutdown`](struct.Shutdown.html) ended
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct TaskReport {
+    ///Identifier of the task, returned when it was spawned
+    pub id: CancelId,
+
+    ///Name the task was spawned with
+    pub name: String,
+
+    ///How the task ended
+    pub outcome: TaskOutcome,
+
+    ///How long the task ran for, until it ended or was aborted
+    pub duration: Duration,
+}
+
+impl fmt::Display for TaskReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "#{} {}: {} after {:?}", self.id, self.name, self.outcome, self.duration)
+    }
+}
+
+///Report of a shutdown, with how each task ended in the order they were spawned
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct ShutdownReport {
+    tasks: Vec<TaskReport>,
+    duration: Duration,
+}
+
+impl ShutdownReport {
+    ///Retrieve the reports of the tasks, in the order they were spawned
+    pub fn tasks(&self) -> &[TaskReport] { &self.tasks }
+
+    ///How long the shutdown took, from the cancellation to the last task ending
+    pub fn duration(&self) -> Duration { self.duration }
+
+    ///Number of tasks that ended with `outcome`
+    pub fn count(&self, outcome: TaskOutcome) -> usize {
+        self.tasks.iter().filter(|task| task.outcome == outcome).count()
+    }
+
+    ///Check if no task timed out or panicked
+    pub fn is_clean(&self) -> bool {
+        self.count(TaskOutcome::TimedOut) == 0 && self.count(TaskOutcome::Panicked) == 0
+    }
+}
+
+impl fmt::Display for ShutdownReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "shutdown took {:?}", self.duration)?;
+        for task in &self.tasks {
+            write!(f, "\n{}", task)?;
+        }
+        Ok(())
+    }
+}
+
+struct Task {
+    id: CancelId,
+    name: String,
+    started: Instant,
+    ended: Option<(TaskOutcome, Instant)>,
+}
+
+///Coordinator spawning tokio tasks and shutting them down, reporting how each one ended
+pub struct Shutdown {
+    token: CancellationToken,
+    set: JoinSet<Instant>,
+    tasks: Vec<Task>,
+    indices: HashMap<Id, usize>,
+}
+
+impl Shutdown {
+    ///Create a coordinator without tasks
+    pub fn new() -> Self {
+        Self {
+            token: CancellationToken::new(),
+            set: JoinSet::new(),
+            tasks: Vec::new(),
+            indices: HashMap::new(),
+        }
+    }
+
+    ///Retrieve the token cancelled when the shutdown starts
+    pub fn token(&self) -> &CancellationToken { &self.token }
+
+    ///Spawn the task built by `make_fut`, with a child token of the coordinator
+    ///
+    ///The task should wind down once the token is cancelled
+    ///
+    /// # Panics
+    ///Panics if called outside of a tokio runtime
+    pub fn spawn<Mk, Fut>(&mut self, name: impl Into<String>, make_fut: Mk) -> CancelId
+    where
+        Mk: FnOnce(CancellationToken) -> Fut,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        let fut = make_fut(self.token.child_token());
+        let handle = self.set.spawn(async move {
+            fut.await;
+            Instant::now()
+        });
+
+        let id = CancelId::next();
+        self.indices.insert(handle.id(), self.tasks.len());
+        self.tasks.push(Task { id, name: name.into(), started: Instant::now(), ended: None });
+        id
+    }
+
+    //record how a joined task ended
+    fn record(&mut self, joined: Result<(Id, Instant), JoinError>) {
+        let (id, outcome, ended) = match joined {
+            Ok((id, ended)) if self.token.is_cancelled() => (id, TaskOutcome::Cancelled, ended),
+            //completing before the shutdown started is finishing
+            Ok((id, ended)) => (id, TaskOutcome::Finished, ended),
+            Err(err) if err.is_panic() => (err.id(), TaskOutcome::Panicked, Instant::now()),
+            Err(err) => (err.id(), TaskOutcome::TimedOut, Instant::now()),
+        };
+
+        if let Some(&index) = self.indices.get(&id) {
+            self.tasks[index].ended = Some((outcome, ended));
+        }
+    }
+
+    ///Cancel the token, wait up to `grace` for the tasks to end and abort the ones still running
+    pub async fn shutdown(mut self, grace: Duration) -> ShutdownReport {
+        //the tasks already done finished on their own
+        while let Some(joined) = self.set.try_join_next_with_id() {
+            self.record(joined);
+        }
+
+        let start = Instant::now();
+        self.token.cancel();
+
+        let drained = async {
+            while let Some(joined) = self.set.join_next_with_id().await {
+                self.record(joined);
+            }
+        };
+        let _ = drained.cancel_with(tokio::time::sleep(grace)).await;
+
+        self.set.abort_all();
+        while let Some(joined) = self.set.join_next_with_id().await {
+            //tasks completing right as they were aborted still wound down in time
+            self.record(joined);
+        }
+
+        let tasks = self
+            .tasks
+            .into_iter()
+            .map(|task| {
+                let (outcome, ended) = task.ended.unwrap_or((TaskOutcome::TimedOut, start));
+                TaskReport {
+                    id: task.id,
+                    name: task.name,
+                    outcome,
+                    duration: ended.saturating_duration_since(task.started),
+                }
+            })
+            .collect();
+
+        ShutdownReport { tasks, duration: start.elapsed() }
+    }
+}
+
+impl Default for Shutdown {
+    fn default() -> Self { Self::new() }
+}
+
+impl fmt::Debug for Shutdown {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Shutdown")
+            .field("token", &self.token)
+            .field("tasks", &self.tasks.len())
+            .finish()
+    }
+}
@@ -0,0 +1,98 @@
+//! Stoppers completing on Unix signals, backed by `signal-hook`
+//!
+//!Unlike the [`signals`](../signals/index.html) module these stoppers don't need tokio:
+//! they're driven by `async-io`, so they work with async-std, smol or any other executor.
+//!
+//!The signal handlers are registered when a stopper is created, and a stopper completes with
+//! the number of the signal that was received, or with an error if the handlers could not be
+//! registered.
+//!
+//!Only available on Unix
+//!
+//! # Example
+//! ```rust
+//! # use futures::{executor::block_on, future::pending};
+//!  use kyansel::{signal_hook::shutdown, FutureCancellable};
+//!  use signal_hook::{consts::SIGTERM, low_level::raise};
+//!
+//!  let stopper = shutdown();
+//!  raise(SIGTERM).unwrap();
+//!
+//!  let result = block_on(pending::<()>().cancel_with(stopper));
+//!  assert_eq!(result.cancelled().unwrap().unwrap(), SIGTERM);
+//! ```
+
+use core::{
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll},
+};
+use futures_core::Stream;
+use signal_hook::consts::{SIGHUP, SIGINT, SIGTERM};
+use signal_hook_async_std::{Handle, Signals};
+use std::{io, os::raw::c_int};
+
+///Future completing when one of a set of signals is received
+///
+///Created by the functions in this module.
+///The handlers are unregistered when the stopper is dropped
+#[must_use = "futures do nothing unless you `.await` or poll them"]
+pub struct SignalStopper {
+    signals: Result<(Signals, Handle), Option<io::Error>>,
+}
+
+impl Drop for SignalStopper {
+    fn drop(&mut self) {
+        if let Ok((_, handle)) = &self.signals {
+            handle.close();
+        }
+    }
+}
+
+impl Future for SignalStopper {
+    type Output = io::Result<c_int>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
+        let signals = match &mut self.get_mut().signals {
+            Ok((signals, _)) => signals,
+            Err(err) => {
+                let err = err.take().expect("SignalStopper polled after completion");
+                return Poll::Ready(Err(err));
+            }
+        };
+
+        match Pin::new(signals).poll_next(cx) {
+            Poll::Ready(Some(signal)) => Poll::Ready(Ok(signal)),
+            Poll::Ready(None) => Poll::Ready(Err(io::ErrorKind::BrokenPipe.into())),
+            Poll::Pending => Poll::Pending,
+        }
+    }
+}
+
+impl core::fmt::Debug for SignalStopper {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        f.debug_struct("SignalStopper").finish()
+    }
+}
+
+///Stopper completing on the first of `signals` to be received
+pub fn signals(signals: &[c_int]) -> SignalStopper {
+    let signals = Signals::new(signals).map(|signals| {
+        let handle = signals.handle();
+        (signals, handle)
+    });
+
+    SignalStopper { signals: signals.map_err(Some) }
+}
+
+///Stopper completing on SIGTERM
+pub fn terminate() -> SignalStopper { signals(&[SIGTERM]) }
+
+///Stopper completing on SIGINT
+pub fn interrupt() -> SignalStopper { signals(&[SIGINT]) }
+
+///Stopper completing on SIGHUP
+pub fn hangup() -> SignalStopper { signals(&[SIGHUP]) }
+
+///Stopper completing on the first of the usual shutdown signals, SIGINT and SIGTERM
+pub fn shutdown() -> SignalStopper { signals(&[SIGINT, SIGTERM]) }
@@ -0,0 +1,161 @@
+//! Stoppers completing on OS signals, backed by tokio
+//!
+//! Unix signals and Windows console control events are available on the respective platforms,
+//! while [`ctrl_c`](fn.ctrl_c.html) and [`shutdown`](fn.shutdown.html) work everywhere.
+//!
+//! The stoppers can be passed straight to
+//! [`cancel_with`](../trait.FutureCancellable.html#method.cancel_with) or used to cancel a root
+//! token with
+//! [`CancellationToken::cancel_on`](../token/struct.CancellationToken.html#method.cancel_on).
+//!
+//!The stoppers complete with an error if the signal handler could not be registered
+//!
+//! # Example
+//! ```rust,no_run
+//! # #[tokio::main]
+//! # async fn main() {
+//!  use kyansel::{signals, token::CancellationToken, FutureCancellable};
+//! #  use futures::future::pending;
+//!
+//!  let root = CancellationToken::new();
+//!  tokio::spawn(root.cancel_on(signals::shutdown()));
+//!
+//!  let result = pending::<()>().cancel_with(root.cancelled()).await;
+//!  assert!(result.is_cancelled());
+//! # }
+//! ```
+
+use core::{
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll},
+};
+use std::{boxed::Box, vec, vec::Vec};
+use std::io;
+
+///Future completing when a signal is received
+///
+///Created by the functions in this module
+#[must_use = "futures do nothing unless you `.await` or poll them"]
+pub struct SignalStopper {
+    inner: Pin<Box<dyn Future<Output = io::Result<()>> + Send>>,
+}
+
+impl SignalStopper {
+    fn new<F>(fut: F) -> Self
+    where
+        F: Future<Output = io::Result<()>> + Send + 'static,
+    {
+        Self { inner: Box::pin(fut) }
+    }
+}
+
+impl Future for SignalStopper {
+    type Output = io::Result<()>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
+        self.get_mut().inner.as_mut().poll(cx)
+    }
+}
+
+impl core::fmt::Debug for SignalStopper {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        f.debug_struct("SignalStopper").finish()
+    }
+}
+
+///Stopper completing on Ctrl-C (SIGINT on Unix)
+pub fn ctrl_c() -> SignalStopper { SignalStopper::new(tokio::signal::ctrl_c()) }
+
+#[cfg(unix)]
+fn unix(kind: tokio::signal::unix::SignalKind) -> SignalStopper {
+    SignalStopper::new(async move {
+        tokio::signal::unix::signal(kind)?.recv().await;
+        Ok(())
+    })
+}
+
+///Stopper completing on SIGTERM
+#[cfg(unix)]
+pub fn terminate() -> SignalStopper { unix(tokio::signal::unix::SignalKind::terminate()) }
+
+///Stopper completing on SIGINT
+///
+///Unlike [`ctrl_c`](fn.ctrl_c.html) this is only available on Unix
+#[cfg(unix)]
+pub fn interrupt() -> SignalStopper { unix(tokio::signal::unix::SignalKind::interrupt()) }
+
+///Stopper completing on SIGHUP
+#[cfg(unix)]
+pub fn hangup() -> SignalStopper { unix(tokio::signal::unix::SignalKind::hangup()) }
+
+#[cfg(windows)]
+macro_rules! windows_stopper {
+    ($(#[$doc:meta])* $name:ident) => {
+        $(#[$doc])*
+        pub fn $name() -> SignalStopper {
+            SignalStopper::new(async move {
+                tokio::signal::windows::$name()?.recv().await;
+                Ok(())
+            })
+        }
+    };
+}
+
+#[cfg(windows)]
+windows_stopper!(
+    ///Stopper completing on the `CTRL_BREAK_EVENT` console event
+    ctrl_break
+);
+
+#[cfg(windows)]
+windows_stopper!(
+    ///Stopper completing on the `CTRL_CLOSE_EVENT` console event,
+    /// sent when the console window is closed
+    ///
+    ///Windows terminates the process shortly after delivering this event,
+    /// so cleanup should be kept brief
+    ctrl_close
+);
+
+#[cfg(windows)]
+windows_stopper!(
+    ///Stopper completing on the `CTRL_SHUTDOWN_EVENT` console event,
+    /// sent when the system is shutting down
+    ctrl_shutdown
+);
+
+#[cfg(windows)]
+windows_stopper!(
+    ///Stopper completing on the `CTRL_LOGOFF_EVENT` console event,
+    /// sent when the user is logging off
+    ctrl_logoff
+);
+
+//completes with the first stopper to complete
+fn first(mut stoppers: Vec<SignalStopper>) -> SignalStopper {
+    SignalStopper::new(core::future::poll_fn(move |cx| {
+        stoppers
+            .iter_mut()
+            .map(|stopper| Pin::new(stopper).poll(cx))
+            .find(Poll::is_ready)
+            .unwrap_or(Poll::Pending)
+    }))
+}
+
+///Stopper completing on the first of the usual shutdown signals
+///
+///On Unix these are SIGINT and SIGTERM, on Windows Ctrl-C, Ctrl-Break and the close and
+/// shutdown console events, elsewhere only Ctrl-C
+pub fn shutdown() -> SignalStopper {
+    #[cfg(unix)]
+    let stoppers = vec![ctrl_c(), terminate()];
+
+    #[cfg(windows)]
+    let stoppers = vec![ctrl_c(), ctrl_break(), ctrl_close(), ctrl_shutdown()];
+
+    #[cfg(not(any(unix, windows)))]
+    let stoppers = vec![ctrl_c()];
+
+    first(stoppers)
+}
@@ -0,0 +1,288 @@
+//! Deduplication of concurrent work
+//!
+//! Concurrent calls to [`Singleflight::call`](struct.Singleflight.html#method.call) with the same
+//! key share a single execution: only the first caller (the leader) drives its future, while the
+//! others wait for the leader's result.
+//!
+//! Every caller provides its own future, so if the leader is cancelled (its
+//! [`Flight`](struct.Flight.html) is dropped before completing) leadership transfers to one of the
+//! waiters, which starts driving its own future instead of failing everyone.
+//!
+//! # Example
+//! ```rust
+//! # use futures::{executor::block_on, future::{join, pending, ready}};
+//!  use kyansel::{singleflight::Singleflight, FutureCancellable};
+//!
+//!  let group = Singleflight::new();
+//!
+//!  //the leader gets cancelled before its work completes
+//!  let leader = group.call("config", pending()).cancel_with(ready(()));
+//!  let waiter = group.call("config", ready(42));
+//!
+//!  let (leader, waiter) = block_on(join(leader, waiter));
+//!
+//!  //the leader was cancelled, but the waiter took over
+//!  assert!(leader.is_cancelled());
+//!  assert_eq!(waiter, 42);
+//! ```
+
+use core::{
+    future::Future,
+    hash::Hash,
+    pin::Pin,
+    task::{Context, Poll, Waker},
+};
+use pin_project_lite::pin_project;
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+
+///Group of deduplicated calls, keyed by `K` and resolving to a shared `T`
+///
+///The group can be cloned, all the clones share the in-flight calls
+#[derive(Debug)]
+pub struct Singleflight<K, T> {
+    calls: Arc<Mutex<HashMap<K, Arc<Call<T>>>>>,
+}
+
+#[derive(Debug)]
+struct Call<T> {
+    state: Mutex<CallState<T>>,
+}
+
+#[derive(Debug)]
+struct CallState<T> {
+    leader: Option<usize>,
+    result: Option<T>,
+    waiters: HashMap<usize, Waker>,
+    next_id: usize,
+    //live flights of the call, polled or not
+    flights: usize,
+}
+
+impl<K, T> Clone for Singleflight<K, T> {
+    fn clone(&self) -> Self { Self { calls: self.calls.clone() } }
+}
+
+impl<K, T> Default for Singleflight<K, T>
+where
+    K: Hash + Eq + Clone,
+    T: Clone,
+{
+    fn default() -> Self { Self::new() }
+}
+
+impl<K, T> Singleflight<K, T>
+where
+    K: Hash + Eq + Clone,
+    T: Clone,
+{
+    ///Create a new empty group
+    pub fn new() -> Self { Self { calls: Arc::new(Mutex::new(HashMap::new())) } }
+
+    ///Join the call for `key`, or start a new one
+    ///
+    ///`fut` is only driven if this caller is, or becomes, the leader of the call
+    pub fn call<F>(&self, key: K, fut: F) -> Flight<K, T, F>
+    where
+        F: Future<Output = T>,
+    {
+        let mut calls = self.calls.lock().unwrap();
+        let call = calls
+            .entry(key.clone())
+            .or_insert_with(|| {
+                Arc::new(Call {
+                    state: Mutex::new(CallState {
+                        leader: None,
+                        result: None,
+                        waiters: HashMap::new(),
+                        next_id: 0,
+                        flights: 0,
+                    }),
+                })
+            })
+            .clone();
+
+        //counted while the group is locked, so the call can't be forgotten in between
+        let id = {
+            let mut state = call.state.lock().unwrap();
+            state.flights += 1;
+            state.next_id += 1;
+            state.next_id
+        };
+        drop(calls);
+
+        Flight { group: self.clone(), key, call, id, fut }
+    }
+
+    ///Number of calls currently in flight
+    ///
+    ///A call stays in flight as long as one of its [`Flight`](struct.Flight.html)s is alive,
+    /// polled or not
+    ///
+    /// # Example
+    /// ```rust
+    /// # use futures::{executor::block_on, future::{pending, poll_immediate, ready}};
+    ///  use kyansel::singleflight::Singleflight;
+    ///
+    ///  let group = Singleflight::new();
+    ///
+    ///  let mut leader = group.call("config", pending());
+    ///  let waiter = group.call("config", ready(42));
+    ///  assert_eq!(block_on(poll_immediate(&mut leader)), None);
+    ///
+    ///  //the leader is gone, but the waiter still holds the call
+    ///  drop(leader);
+    ///  assert_eq!(group.len(), 1);
+    ///  assert_eq!(block_on(waiter), 42);
+    ///
+    ///  //a flight dropped without being polled doesn't leave the call behind
+    ///  drop(group.call("config", ready(7)));
+    ///  assert!(group.is_empty());
+    /// ```
+    pub fn len(&self) -> usize { self.calls.lock().unwrap().len() }
+
+    ///Check if there are no calls in flight
+    pub fn is_empty(&self) -> bool { self.len() == 0 }
+
+    fn forget(&self, key: &K, call: &Arc<Call<T>>) {
+        let mut calls = self.calls.lock().unwrap();
+        if calls.get(key).is_some_and(|current| Arc::ptr_eq(current, call)) {
+            calls.remove(key);
+        }
+    }
+}
+
+pin_project! {
+    ///Future returned by [`Singleflight::call`](struct.Singleflight.html#method.call)
+    #[must_use = "futures do nothing unless you `.await` or poll them"]
+    pub struct Flight<K, T, F>
+    where
+        K: Hash,
+        K: Eq,
+        K: Clone,
+        T: Clone,
+    {
+        group: Singleflight<K, T>,
+        key: K,
+        call: Arc<Call<T>>,
+        id: usize,
+        #[pin]
+        fut: F,
+    }
+
+    impl<K, T, F> PinnedDrop for Flight<K, T, F>
+    where
+        K: Hash,
+        K: Eq,
+        K: Clone,
+        T: Clone,
+    {
+        fn drop(this: Pin<&mut Self>) {
+            let this = this.project();
+
+            //the group is locked first, like in `call`,
+            // so no flight joins while the call is forgotten
+            let mut calls = match this.group.calls.lock() {
+                Ok(calls) => calls,
+                Err(_) => return,
+            };
+
+            let waiters = {
+                let mut state = match this.call.state.lock() {
+                    Ok(state) => state,
+                    Err(_) => return,
+                };
+                state.waiters.remove(this.id);
+                state.flights -= 1;
+
+                //the last flight is gone, a new caller should start a new call
+                if state.flights == 0
+                    && calls.get(this.key).is_some_and(|current| Arc::ptr_eq(current, this.call))
+                {
+                    calls.remove(this.key);
+                }
+
+                if state.leader != Some(*this.id) || state.result.is_some() {
+                    return;
+                }
+
+                //the leader was cancelled, let the waiters race for leadership
+                state.leader = None;
+                std::mem::take(&mut state.waiters)
+            };
+            drop(calls);
+
+            waiters.into_values().for_each(Waker::wake);
+        }
+    }
+}
+
+impl<K, T, F> Flight<K, T, F>
+where
+    K: Hash + Eq + Clone,
+    T: Clone,
+{
+    ///Check if this caller is currently driving the shared call
+    pub fn is_leader(&self) -> bool { self.call.state.lock().unwrap().leader == Some(self.id) }
+}
+
+impl<K, T, F> Future for Flight<K, T, F>
+where
+    K: Hash + Eq + Clone,
+    T: Clone,
+    F: Future<Output = T>,
+{
+    type Output = T;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
+        let this = self.project();
+
+        {
+            let mut state = this.call.state.lock().unwrap();
+
+            if let Some(result) = &state.result {
+                return Poll::Ready(result.clone());
+            }
+
+            match state.leader {
+                Some(leader) if leader != *this.id => {
+                    state.waiters.insert(*this.id, cx.waker().clone());
+                    return Poll::Pending;
+                }
+                _ => {
+                    state.leader = Some(*this.id);
+                    state.waiters.remove(this.id);
+                }
+            }
+        }
+
+        let result = match this.fut.poll(cx) {
+            Poll::Pending => return Poll::Pending,
+            Poll::Ready(result) => result,
+        };
+
+        //new callers should start a new call from now on
+        this.group.forget(this.key, this.call);
+
+        let waiters = {
+            let mut state = this.call.state.lock().unwrap();
+            state.result = Some(result.clone());
+            std::mem::take(&mut state.waiters)
+        };
+        waiters.into_values().for_each(Waker::wake);
+
+        Poll::Ready(result)
+    }
+}
+
+impl<K, T, F> core::fmt::Debug for Flight<K, T, F>
+where
+    K: Hash + Eq + Clone + core::fmt::Debug,
+    T: Clone,
+{
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        f.debug_struct("Flight").field("key", &self.key).field("id", &self.id).finish()
+    }
+}
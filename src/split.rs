@@ -0,0 +1,203 @@
+//! Polling the stopper only when it asks to be polled
+//!
+//![`Cancellable`](../struct.Cancellable.html) polls its stopper every time it's polled,
+//! which is wasteful when the inner future wakes thousands of times per second while the stopper
+//! sits idle.
+//![`Cancellable::split_wakers`](../struct.Cancellable.html#method.split_wakers) hands the stopper
+//! its own waker, and only polls it again once that waker was woken.
+//!
+//!The inner future gets its own waker too, so
+//! [`SplitWakers::last_wake`](struct.SplitWakers.html#method.last_wake) can tell which side
+//! caused the last wakeup, to diagnose spurious wakeups and busy polling.
+//!
+//! # Example
+//! ```rust
+//! # use futures::{executor::block_on, future::poll_fn};
+//!  use kyansel::FutureCancellable;
+//!  use std::{cell::Cell, task::Poll};
+//!
+//!  let (wakes, stopper_polls) = (Cell::new(0), Cell::new(0));
+//!
+//!  //wakes itself 1000 times before finishing
+//!  let busy = poll_fn(|cx| {
+//!      wakes.set(wakes.get() + 1);
+//!      if wakes.get() == 1000 {
+//!          return Poll::Ready(());
+//!      }
+//!      cx.waker().wake_by_ref();
+//!      Poll::Pending
+//!  });
+//!
+//!  //never wakes the task
+//!  let stopper = poll_fn(|_| {
+//!      stopper_polls.set(stopper_polls.get() + 1);
+//!      Poll::<()>::Pending
+//!  });
+//!
+//!  assert!(!block_on(busy.cancel_with(stopper).split_wakers()).is_cancelled());
+//!  assert_eq!(stopper_polls.get(), 1);
+//! ```
+
+use super::{wake::WakeFlag, Cancellable, CancellableResult};
+use core::{
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll, Waker},
+};
+use pin_project_lite::pin_project;
+use std::sync::Arc;
+
+///Which waker caused a wakeup of a [`SplitWakers`](struct.SplitWakers.html)
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum WakeSource {
+    ///If the inner future woke the task
+    Inner,
+
+    ///If the stopper woke the task
+    Stopper,
+
+    ///If both woke the task since the previous poll
+    Both,
+
+    ///If neither woke the task: it's the first poll, or the task was polled for another reason
+    Unknown,
+}
+
+pin_project! {
+    ///[`Cancellable`](../struct.Cancellable.html) polling its stopper only when the stopper
+    /// is woken
+    ///
+    ///Created with [`Cancellable::split_wakers`](../struct.Cancellable.html#method.split_wakers)
+    #[must_use = "futures do nothing unless you `.await` or poll them"]
+    pub struct SplitWakers<F, S>
+    where
+        F: Future,
+        S: Future,
+    {
+        #[pin]
+        cancellable: Cancellable<F, S>,
+        inner_woken: Arc<WakeFlag>,
+        inner_waker: Waker,
+        stopper_woken: Arc<WakeFlag>,
+        stopper_waker: Waker,
+        last_wake: Option<WakeSource>,
+    }
+}
+
+impl<F, S> SplitWakers<F, S>
+where
+    F: Future,
+    S: Future,
+{
+    ///Retrieve which waker caused the most recent poll, `None` if it was never polled
+    ///
+    /// # Example
+    /// ```rust
+    /// # use futures::{executor::block_on, future::{pending, poll_fn}};
+    ///  use kyansel::{split::WakeSource, FutureCancellable};
+    ///  use std::task::Poll;
+    ///
+    ///  let mut woken = false;
+    ///  let mut cancellable = pending::<()>()
+    ///      .cancel_with(poll_fn(|cx| match woken {
+    ///          true => Poll::Ready(()),
+    ///          false => {
+    ///              woken = true;
+    ///              cx.waker().wake_by_ref();
+    ///              Poll::Pending
+    ///          }
+    ///      }))
+    ///      .split_wakers();
+    ///
+    ///  assert_eq!(cancellable.last_wake(), None);
+    ///  assert!(block_on(&mut cancellable).is_cancelled());
+    ///  assert_eq!(cancellable.last_wake(), Some(WakeSource::Stopper));
+    /// ```
+    pub fn last_wake(&self) -> Option<WakeSource> { self.last_wake }
+}
+
+impl<F, S> Future for SplitWakers<F, S>
+where
+    F: Future,
+    S: Future,
+{
+    type Output = CancellableResult<F::Output, S::Output>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
+        let this = self.project();
+        #[cfg(any(feature = "log", feature = "tracing"))]
+        let name = this.cancellable.name;
+        let projection = this.cancellable.project();
+
+        this.inner_woken.register(cx.waker());
+        this.stopper_woken.register(cx.waker());
+
+        let polled = this.last_wake.is_some();
+        let stopper_woken = this.stopper_woken.take();
+        *this.last_wake = Some(match (this.inner_woken.take(), stopper_woken) {
+            (true, true) => WakeSource::Both,
+            (true, false) => WakeSource::Inner,
+            (false, true) => WakeSource::Stopper,
+            (false, false) => WakeSource::Unknown,
+        });
+
+        //always poll inner future first
+        if let Poll::Ready(t) = projection.inner.poll(&mut Context::from_waker(this.inner_waker)) {
+            #[cfg(any(feature = "log", feature = "tracing"))]
+            super::trace_named(name, "finished");
+
+            return Poll::Ready(CancellableResult::Finished(t));
+        }
+
+        //the stopper is polled once to register its waker, then only when it wakes us
+        if !stopper_woken && polled {
+            return Poll::Pending;
+        }
+
+        match projection.stopper.poll(&mut Context::from_waker(this.stopper_waker)) {
+            Poll::Ready(s) => {
+                #[cfg(any(feature = "log", feature = "tracing"))]
+                super::trace_named(name, "cancelled");
+
+                Poll::Ready(CancellableResult::Cancelled(s))
+            }
+            Poll::Pending => Poll::Pending,
+        }
+    }
+}
+
+impl<F, S> core::fmt::Debug for SplitWakers<F, S>
+where
+    F: Future + core::fmt::Debug,
+    S: Future + core::fmt::Debug,
+{
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        f.debug_struct("SplitWakers")
+            .field("cancellable", &self.cancellable)
+            .field("last_wake", &self.last_wake)
+            .finish()
+    }
+}
+
+impl<F, S> Cancellable<F, S>
+where
+    F: Future,
+    S: Future,
+{
+    ///Poll the stopper only when it's woken, instead of every time the future is polled
+    ///
+    ///See the [`split`](split/index.html) module
+    pub fn split_wakers(self) -> SplitWakers<F, S> {
+        let inner_woken = Arc::new(WakeFlag::default());
+        let stopper_woken = Arc::new(WakeFlag::default());
+
+        SplitWakers {
+            cancellable: self,
+            inner_waker: Waker::from(inner_woken.clone()),
+            inner_woken,
+            stopper_waker: Waker::from(stopper_woken.clone()),
+            stopper_woken,
+            last_wake: None,
+        }
+    }
+}
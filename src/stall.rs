@@ -0,0 +1,120 @@
+//! Cancelling futures that stop making progress
+//!
+//!A deadline bounds how long a future can run in total, while
+//! [`cancel_on_stall`](fn.cancel_on_stall.html) bounds how long it can go without being woken.
+//!A future streaming a large response can run for minutes, but if its connection stops
+//! delivering data it's hung, and gets cancelled after the configured idle time.
+//!
+//! # Example
+//! ```rust
+//! # use futures::{executor::block_on, future::pending, pin_mut, poll};
+//!  use kyansel::{stall::cancel_on_stall, timer::ManualTimer};
+//! #  use std::time::Duration;
+//!
+//! # block_on(async {
+//!  let timer = ManualTimer::new();
+//!  let cancellable = cancel_on_stall(pending::<()>(), timer.clone(), Duration::from_secs(5));
+//!  pin_mut!(cancellable);
+//!  assert!(poll!(cancellable.as_mut()).is_pending());
+//!
+//!  //never woken for 5 seconds
+//!  timer.advance(Duration::from_secs(5));
+//!  assert!(cancellable.await.is_cancelled());
+//! # });
+//! ```
+
+use super::{timer::Timer, wake::WakeFlag, CancellableResult};
+use core::{
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll, Waker},
+    time::Duration,
+};
+use pin_project_lite::pin_project;
+use std::sync::Arc;
+
+///Reason of the cancellation of a future that stopped making progress
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct Stalled {
+    ///How long the future went without being woken
+    pub idle: Duration,
+}
+
+pin_project! {
+    ///Future for the [`cancel_on_stall`](fn.cancel_on_stall.html) combinator
+    #[must_use = "futures do nothing unless you `.await` or poll them"]
+    pub struct Stall<F, T>
+    where
+        T: Timer,
+    {
+        #[pin]
+        inner: F,
+        timer: T,
+        idle: Duration,
+        #[pin]
+        sleep: Option<T::Sleep>,
+        progress: Arc<WakeFlag>,
+        waker: Waker,
+    }
+}
+
+impl<F, T> Future for Stall<F, T>
+where
+    F: Future,
+    T: Timer,
+{
+    type Output = CancellableResult<F::Output, Stalled>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
+        let mut this = self.project();
+
+        this.progress.register(cx.waker());
+
+        //a wakeup since the last poll counts as progress, restart the idle timer
+        if this.progress.take() || this.sleep.is_none() {
+            this.sleep.set(Some(this.timer.sleep(*this.idle)));
+        }
+
+        //always poll inner future first
+        if let Poll::Ready(t) = this.inner.poll(&mut Context::from_waker(this.waker)) {
+            return Poll::Ready(CancellableResult::Finished(t));
+        }
+
+        match this.sleep.as_pin_mut().map(|sleep| sleep.poll(cx)) {
+            Some(Poll::Ready(())) => {
+                Poll::Ready(CancellableResult::Cancelled(Stalled { idle: *this.idle }))
+            }
+            _ => Poll::Pending,
+        }
+    }
+}
+
+impl<F, T> core::fmt::Debug for Stall<F, T>
+where
+    T: Timer + core::fmt::Debug,
+{
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        f.debug_struct("Stall").field("timer", &self.timer).field("idle", &self.idle).finish()
+    }
+}
+
+///Cancel `fut` if it goes `idle` without being woken, measured with `timer`
+///
+///Every wakeup of the inner future restarts the idle timer,
+/// the future itself can run for as long as it keeps making progress
+pub fn cancel_on_stall<F, T>(fut: F, timer: T, idle: Duration) -> Stall<F, T>
+where
+    F: Future,
+    T: Timer,
+{
+    let progress = Arc::new(WakeFlag::default());
+
+    Stall {
+        inner: fut,
+        timer,
+        idle,
+        sleep: None,
+        waker: Waker::from(progress.clone()),
+        progress,
+    }
+}
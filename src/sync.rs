@@ -0,0 +1,283 @@
+//! Cancellable async sections for synchronous code
+//!
+//! [`cancel_block_on`](fn.cancel_block_on.html) drives a future on the current thread,
+//! handing out a [`CancelHandle`](struct.CancelHandle.html) that other threads
+//! can use to make the blocked call return.
+//!
+//! [`scope`](fn.scope.html) is the cancellable counterpart of `std::thread::scope`:
+//! each future spawned on it is driven on its own thread, and they are all cancelled and joined
+//! when the scope closes.
+//!
+//! # Example
+//! ```rust
+//!  use kyansel::sync::cancel_block_on;
+//! #  use futures::future::pending;
+//!
+//!  let (handle, blocking) = cancel_block_on(pending::<()>());
+//!
+//!  std::thread::spawn(move || handle.cancel());
+//!
+//!  assert!(blocking.wait().is_cancelled());
+//! ```
+
+use super::{token::CancellationToken, CancellableResult, FutureCancellable};
+use core::{
+    future::Future,
+    pin::pin,
+    sync::atomic::{fence, AtomicBool, Ordering},
+    task::{Context, Poll, Waker},
+};
+use std::{
+    sync::{Arc, OnceLock},
+    task::Wake,
+    thread::{self, ScopedJoinHandle, Thread},
+};
+
+#[derive(Debug, Default)]
+struct Shared {
+    cancelled: AtomicBool,
+    thread: OnceLock<Thread>,
+    //cancelled once the future is dropped
+    dropped: CancellationToken,
+}
+
+//marks the future as dropped, declared after it so it's dropped last
+#[derive(Debug)]
+struct DropGuard(Arc<Shared>);
+
+impl Drop for DropGuard {
+    fn drop(&mut self) { self.0.dropped.cancel() }
+}
+
+///Handle used to cancel a future driven by [`BlockOn::wait`](struct.BlockOn.html#method.wait)
+///
+///The handle is `Send` and cheap to clone
+#[derive(Debug, Clone)]
+pub struct CancelHandle {
+    shared: Arc<Shared>,
+}
+
+impl CancelHandle {
+    ///Cancel the future, making the blocked call return
+    pub fn cancel(&self) {
+        self.shared.cancelled.store(true, Ordering::Release);
+        //pairs with the fence in `wait`: either the thread is seen here, or the flag there
+        fence(Ordering::SeqCst);
+        if let Some(thread) = self.shared.thread.get() {
+            thread.unpark();
+        }
+    }
+
+    ///Cancel the future, resolving once it was dropped
+    ///
+    ///Unlike [`cancel`](struct.CancelHandle.html#method.cancel), once this resolves the future
+    /// is done, having observed the cancellation or finished first,
+    /// so the resources it was using can be released safely
+    ///
+    /// # Example
+    /// ```rust
+    ///  use kyansel::sync::cancel_block_on;
+    /// #  use futures::{executor::block_on, future::pending};
+    ///  use std::sync::Arc;
+    ///
+    ///  let connection = Arc::new(());
+    ///
+    ///  let worker = connection.clone();
+    ///  let (handle, blocking) = cancel_block_on(async move {
+    ///      let _busy = worker;
+    ///      pending::<()>().await
+    ///  });
+    ///
+    ///  let thread = std::thread::spawn(move || blocking.wait());
+    ///
+    ///  block_on(handle.cancel_and_wait());
+    ///  //the future let go of the connection
+    ///  assert_eq!(Arc::strong_count(&connection), 1);
+    /// #  assert!(thread.join().unwrap().is_cancelled());
+    /// ```
+    pub async fn cancel_and_wait(&self) {
+        self.cancel();
+        self.shared.dropped.cancelled().await
+    }
+
+    ///Check if the handle was used to cancel the future
+    pub fn is_cancelled(&self) -> bool { self.shared.cancelled.load(Ordering::Acquire) }
+
+    ///Check if the future was dropped, after being cancelled or finishing
+    pub fn is_dropped(&self) -> bool { self.shared.dropped.is_cancelled() }
+}
+
+struct Unparker(Thread);
+
+impl Wake for Unparker {
+    fn wake(self: Arc<Self>) { self.0.unpark() }
+
+    fn wake_by_ref(self: &Arc<Self>) { self.0.unpark() }
+}
+
+//drive `fut` to completion on the current thread
+fn block_on<F>(fut: F) -> F::Output
+where
+    F: Future,
+{
+    let waker = Waker::from(Arc::new(Unparker(thread::current())));
+    let mut cx = Context::from_waker(&waker);
+
+    let mut fut = pin!(fut);
+
+    loop {
+        if let Poll::Ready(t) = fut.as_mut().poll(&mut cx) {
+            return t;
+        }
+
+        thread::park();
+    }
+}
+
+///Future waiting to be driven to completion on the current thread
+///
+///Returned by [`cancel_block_on`](fn.cancel_block_on.html)
+#[must_use = "the future is only driven by `wait`"]
+pub struct BlockOn<F> {
+    fut: F,
+    shared: Arc<Shared>,
+    _dropped: DropGuard,
+}
+
+impl<F> BlockOn<F>
+where
+    F: Future,
+{
+    ///Block the current thread driving the future,
+    /// until it completes or the [`CancelHandle`](struct.CancelHandle.html) is used
+    pub fn wait(self) -> CancellableResult<F::Output, ()> {
+        let Self { fut, shared, _dropped } = self;
+
+        let current = thread::current();
+        let _ = shared.thread.set(current.clone());
+        //pairs with the fence in `cancel`: either the flag is seen here, or the thread there
+        fence(Ordering::SeqCst);
+
+        let waker = Waker::from(Arc::new(Unparker(current)));
+        let mut cx = Context::from_waker(&waker);
+
+        //the future is dropped before the guard, at the end of the block
+        let result = {
+            let mut fut = pin!(fut);
+
+            loop {
+                //always poll inner future first
+                if let Poll::Ready(t) = fut.as_mut().poll(&mut cx) {
+                    break CancellableResult::Finished(t);
+                }
+
+                if shared.cancelled.load(Ordering::Acquire) {
+                    break CancellableResult::Cancelled(());
+                }
+
+                thread::park();
+            }
+        };
+
+        drop(_dropped);
+        result
+    }
+}
+
+impl<F> core::fmt::Debug for BlockOn<F> {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        f.debug_struct("BlockOn").field("shared", &self.shared).finish()
+    }
+}
+
+///Prepare `fut` to be driven on the current thread, with a handle to cancel it
+///
+///The future doesn't run until [`BlockOn::wait`](struct.BlockOn.html#method.wait) is called,
+/// while the handle can be moved to another thread beforehand.
+///Cancelling before `wait` is called makes it return right away, after polling the future once
+pub fn cancel_block_on<F>(fut: F) -> (CancelHandle, BlockOn<F>)
+where
+    F: Future,
+{
+    let shared = Arc::new(Shared::default());
+
+    let _dropped = DropGuard(shared.clone());
+
+    (CancelHandle { shared: shared.clone() }, BlockOn { fut, shared, _dropped })
+}
+
+///Scope to spawn cancellable futures on, each driven on its own thread
+///
+///Created by [`scope`](fn.scope.html)
+pub struct Scope<'scope, 'env: 'scope> {
+    threads: &'scope thread::Scope<'scope, 'env>,
+    token: CancellationToken,
+}
+
+impl<'scope, 'env> Scope<'scope, 'env> {
+    ///Spawn a thread driving `fut` until it completes or the scope closes
+    ///
+    ///Like with `std::thread::scope`, the future can borrow from outside the scope
+    pub fn spawn<F>(&self, fut: F) -> ScopedJoinHandle<'scope, CancellableResult<F::Output, ()>>
+    where
+        F: Future + Send + 'scope,
+        F::Output: Send + 'scope,
+    {
+        let cancellable = fut.cancel_with(self.token.cancelled());
+        self.threads.spawn(move || block_on(cancellable))
+    }
+
+    ///Cancel all the futures spawned on the scope, including the ones spawned from now on
+    pub fn cancel(&self) { self.token.cancel() }
+
+    ///Retrieve the token cancelling the spawned futures
+    pub fn token(&self) -> &CancellationToken { &self.token }
+}
+
+impl core::fmt::Debug for Scope<'_, '_> {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        f.debug_struct("Scope").field("token", &self.token).finish()
+    }
+}
+
+//cancels the futures even if the scope body panics, or joining them would never end
+struct CancelOnDrop(CancellationToken);
+
+impl Drop for CancelOnDrop {
+    fn drop(&mut self) { self.0.cancel() }
+}
+
+///Run `f` with a [`Scope`](struct.Scope.html) to spawn cancellable futures on,
+/// blocking until they are all done
+///
+///When `f` returns the futures still running are cancelled, then all the threads are joined.
+///Like with `std::thread::scope`, if a thread panicked and wasn't joined manually this panics
+///
+/// # Example
+/// ```rust
+///  use kyansel::sync::scope;
+/// #  use futures::future::{pending, ready};
+///
+///  let data = vec![1, 2, 3];
+///
+///  let sum = scope(|s| {
+///      //cancelled when the scope closes
+///      s.spawn(pending::<()>());
+///
+///      let sum = s.spawn(async { data.iter().sum::<i32>() });
+///      sum.join().unwrap().finished()
+///  });
+///
+///  assert_eq!(sum, Some(6));
+/// ```
+pub fn scope<'env, F, T>(f: F) -> T
+where
+    F: for<'scope> FnOnce(&Scope<'scope, 'env>) -> T,
+{
+    let token = CancellationToken::new();
+
+    thread::scope(|threads| {
+        let _cancel = CancelOnDrop(token.clone());
+        f(&Scope { threads, token })
+    })
+}
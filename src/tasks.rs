@@ -0,0 +1,91 @@
+use super::{
+    token::{CancellationToken, WaitForCancellation},
+    Cancellable, CancellableResult, FutureCancellable,
+};
+use core::{
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll},
+};
+use std::{boxed::Box, vec::Vec};
+
+//result of a task, cancelled with `()` by its container
+type Output<F> = CancellableResult<<F as Future>::Output, ()>;
+
+//future cancelled by its own token, along with what its container knows about it
+pub(crate) struct Task<T, F>
+where
+    F: Future,
+{
+    pub(crate) data: T,
+    token: CancellationToken,
+    fut: Pin<Box<Cancellable<F, WaitForCancellation>>>,
+}
+
+impl<T, F> Task<T, F>
+where
+    F: Future,
+{
+    pub(crate) fn new(data: T, fut: F) -> Self {
+        let token = CancellationToken::new();
+        let fut = Box::pin(fut.cancel_with(token.cancelled()));
+        Self { data, token, fut }
+    }
+
+    pub(crate) fn cancel(&self) { self.token.cancel() }
+
+    pub(crate) fn is_cancelled(&self) -> bool { self.token.is_cancelled() }
+
+    //the same future with other data, like a key moved out of a map
+    pub(crate) fn with_data<U>(self, data: U) -> Task<U, F> {
+        Task { data, token: self.token, fut: self.fut }
+    }
+
+    pub(crate) fn poll(&mut self, cx: &mut Context) -> Poll<Output<F>> {
+        self.fut.as_mut().poll(cx)
+    }
+}
+
+//in-flight futures of the cancelling containers, yielded with their data once done.
+// Cancelled futures are still polled once more, so they can finish if they were already done
+pub(crate) struct Tasks<T, F>
+where
+    F: Future,
+{
+    tasks: Vec<Task<T, F>>,
+}
+
+impl<T, F> Tasks<T, F>
+where
+    F: Future,
+{
+    pub(crate) const fn new() -> Self { Self { tasks: Vec::new() } }
+
+    pub(crate) fn push(&mut self, task: Task<T, F>) { self.tasks.push(task) }
+
+    pub(crate) fn iter(&self) -> core::slice::Iter<'_, Task<T, F>> { self.tasks.iter() }
+
+    pub(crate) fn iter_mut(&mut self) -> core::slice::IterMut<'_, Task<T, F>> {
+        self.tasks.iter_mut()
+    }
+
+    pub(crate) fn len(&self) -> usize { self.tasks.len() }
+
+    pub(crate) fn is_empty(&self) -> bool { self.tasks.is_empty() }
+
+    //yield the first future done, or `None` once there are none left
+    pub(crate) fn poll_next(&mut self, cx: &mut Context) -> Poll<Option<(T, Output<F>)>> {
+        if self.tasks.is_empty() {
+            return Poll::Ready(None);
+        }
+
+        for i in 0..self.tasks.len() {
+            if let Poll::Ready(result) = self.tasks[i].poll(cx) {
+                let task = self.tasks.swap_remove(i);
+                return Poll::Ready(Some((task.data, result)));
+            }
+        }
+
+        Poll::Pending
+    }
+}
@@ -0,0 +1,71 @@
+//! Utilities to test cancellation deterministically
+//!
+//![`Stepper`](struct.Stepper.html) polls a future one step at a time on the current thread,
+//! recording the wakeups it receives, while [`Trace`](struct.Trace.html) records the polls
+//! of the futures it wraps, so the exact interleaving of inner future and stopper can be checked
+//! without a runtime.
+//!
+//![`MockStopper`](struct.MockStopper.html) is a stopper fired synchronously from the test,
+//! in place of ad-hoc channels.
+//!
+//![`CancelSafety`](struct.CancelSafety.html) and
+//! [`assert_cancel_safe!`](../macro.assert_cancel_safe.html) check that a future
+//! can be cancelled at any of its polls without breaking an invariant.
+//!
+//![`explore`](fn.explore.html) runs a composition under every ordering of its inner futures
+//! and stoppers completing, to test races around near-simultaneous completion.
+//!
+//![`Instrumented`](struct.Instrumented.html) records the polls, wakes and time spent polling
+//! of the future it wraps, to catch busy polling.
+//!
+//![`Chaos`](struct.Chaos.html) cancels the futures it wraps at random polls, from a seed,
+//! and can be switched on for a whole suite from the environment.
+//!
+//! # Example
+//! ```rust
+//! # use futures::future::{pending, ready};
+//!  use kyansel::{
+//!      testing::{Event, Stepper, Trace},
+//!      FutureCancellable,
+//!  };
+//!
+//!  let trace = Trace::new();
+//!  let inner = trace.wrap("inner", pending::<()>());
+//!  let stopper = trace.wrap("stopper", ready(()));
+//!
+//!  let mut stepper = Stepper::new(inner.cancel_with(stopper));
+//!  assert!(stepper.step().is_ready());
+//!
+//!  assert_eq!(
+//!      trace.take(),
+//!      [Event::Poll("inner"), Event::Poll("stopper"), Event::Ready("stopper")]
+//!  );
+//!
+//!  //the inner future is dropped with the stepper
+//!  drop(stepper);
+//!  assert_eq!(trace.take(), [Event::Cancelled("inner")]);
+//! ```
+
+mod cancel_safe;
+pub use cancel_safe::CancelSafety;
+
+mod chaos;
+pub use chaos::{Chaos, ChaosStopper, CHAOS_ENV};
+
+#[cfg(feature = "futures-test")]
+pub mod futures_test;
+
+mod explore;
+pub use explore::{explore, Schedule, Step};
+
+mod instrument;
+pub use instrument::{Instrumented, Probe, Stats};
+
+mod mock;
+pub use mock::MockStopper;
+
+mod step;
+pub use step::Stepper;
+
+mod trace;
+pub use trace::{Event, Trace, Traced};
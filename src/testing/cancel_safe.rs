@@ -0,0 +1,205 @@
+use super::Stepper;
+use core::{fmt::Display, future::Future, task::Poll};
+use std::vec::Vec;
+
+///Harness checking that a future can be cancelled at any point without breaking invariants
+///
+///For every cut point `n`, the future created by the factory is polled `n` times and dropped,
+/// then a new one is created and driven to completion, and the invariant is checked.
+///A cut point of 0 drops the future before it's ever polled.
+///
+///The futures are polled without waiting to be woken, so they shouldn't depend on
+/// external events to make progress.
+///
+///See also [`assert_cancel_safe!`](../macro.assert_cancel_safe.html)
+///
+/// # Example
+/// ```rust,should_panic
+///  use kyansel::testing::CancelSafety;
+///  use std::sync::{Arc, Mutex};
+///
+///  let log = Arc::new(Mutex::new(Vec::new()));
+///
+///  CancelSafety::new().check(
+///      || {
+///          let log = log.clone();
+///          async move {
+///              //a record is written in two steps, cancelling in between leaves half of it
+///              log.lock().unwrap().push("header");
+///              futures::pending!();
+///              log.lock().unwrap().push("body");
+///          }
+///      },
+///      |_| match log.lock().unwrap().len() % 2 {
+///          0 => Ok(()),
+///          _ => Err("half-written record"),
+///      },
+///  );
+/// ```
+#[derive(Debug, Clone)]
+pub struct CancelSafety {
+    cuts: Cuts,
+    max_polls: usize,
+}
+
+#[derive(Debug, Clone)]
+enum Cuts {
+    All,
+    Given(Vec<usize>),
+    Random(usize),
+}
+
+impl Default for CancelSafety {
+    fn default() -> Self { Self { cuts: Cuts::All, max_polls: 1000 } }
+}
+
+impl CancelSafety {
+    ///Create a harness checking every cut point,
+    /// from before the first poll to right before completion
+    pub fn new() -> Self { Self::default() }
+
+    ///Only check the given cut points
+    pub fn cuts<I>(mut self, cuts: I) -> Self
+    where
+        I: IntoIterator<Item = usize>,
+    {
+        self.cuts = Cuts::Given(cuts.into_iter().collect());
+        self
+    }
+
+    ///Only check `count` cut points picked at random
+    ///
+    ///The picked cut points are printed when a check fails, to reproduce the failure
+    pub fn random_cuts(mut self, count: usize) -> Self {
+        self.cuts = Cuts::Random(count);
+        self
+    }
+
+    ///Set how many polls a future can take to complete before the check fails,
+    /// 1000 by default
+    pub fn max_polls(mut self, max_polls: usize) -> Self {
+        self.max_polls = max_polls;
+        self
+    }
+
+    ///Check `invariant` after cancelling the futures created by `make_fut` at every cut point
+    ///
+    ///The invariant receives the output of the future that ran to completion
+    ///
+    /// # Panics
+    ///Panics with the failing cut point if the invariant doesn't hold,
+    /// or if a future doesn't complete within the maximum number of polls
+    pub fn check<Mk, F, Inv, E>(&self, mut make_fut: Mk, mut invariant: Inv)
+    where
+        Mk: FnMut() -> F,
+        F: Future,
+        Inv: FnMut(F::Output) -> Result<(), E>,
+        E: Display,
+    {
+        let polls = self.complete(make_fut(), "the uninterrupted run");
+
+        let cuts = match &self.cuts {
+            Cuts::All => (0..polls).collect(),
+            Cuts::Given(cuts) => cuts.clone(),
+            Cuts::Random(count) => (0..*count).map(|_| random() % polls).collect::<Vec<_>>(),
+        };
+
+        for &cut in &cuts {
+            let mut stepper = Stepper::new(make_fut());
+            for _ in 0..cut {
+                if stepper.step().is_ready() {
+                    break;
+                }
+            }
+            drop(stepper);
+
+            let output = self.run(make_fut(), cut);
+            if let Err(err) = invariant(output) {
+                panic!(
+                    "invariant violated after cancelling at poll {} of {} (cut points: {:?}): {}",
+                    cut, polls, cuts, err
+                );
+            }
+        }
+    }
+
+    //run to completion, returning how many polls it took
+    fn complete<F>(&self, fut: F, what: &str) -> usize
+    where
+        F: Future,
+    {
+        let mut stepper = Stepper::new(fut);
+        while stepper.polls() < self.max_polls {
+            if stepper.step().is_ready() {
+                return stepper.polls();
+            }
+        }
+
+        panic!("{} didn't complete within {} polls", what, self.max_polls)
+    }
+
+    fn run<F>(&self, fut: F, cut: usize) -> F::Output
+    where
+        F: Future,
+    {
+        let mut stepper = Stepper::new(fut);
+        while stepper.polls() < self.max_polls {
+            if let Poll::Ready(output) = stepper.step() {
+                return output;
+            }
+        }
+
+        panic!(
+            "the run after cancelling at poll {} didn't complete within {} polls",
+            cut, self.max_polls
+        )
+    }
+}
+
+fn random() -> usize {
+    use std::{
+        collections::hash_map::RandomState,
+        hash::{BuildHasher, Hasher},
+    };
+
+    //every `RandomState` is seeded differently
+    RandomState::new().build_hasher().finish() as usize
+}
+
+///Assert that futures created by a factory can be cancelled at any point without breaking
+/// an invariant
+///
+///Shorthand for [`CancelSafety::check`](testing/struct.CancelSafety.html#method.check),
+/// optionally restricted to the given cut points
+///
+/// # Example
+/// ```rust
+///  use kyansel::assert_cancel_safe;
+///  use std::sync::{Arc, Mutex};
+///
+///  let log = Arc::new(Mutex::new(Vec::new()));
+///
+///  assert_cancel_safe!(
+///      || {
+///          let log = log.clone();
+///          async move {
+///              futures::pending!();
+///              //the record is written all at once
+///              log.lock().unwrap().extend(["header", "body"]);
+///          }
+///      },
+///      |()| match log.lock().unwrap().len() % 2 {
+///          0 => Ok(()),
+///          _ => Err("half-written record"),
+///      },
+///  );
+/// ```
+#[macro_export]
+macro_rules! assert_cancel_safe {
+    ($make_fut:expr, $invariant:expr $(,)?) => {
+        $crate::testing::CancelSafety::new().check($make_fut, $invariant)
+    };
+    ($make_fut:expr, $invariant:expr, cuts = $cuts:expr $(,)?) => {
+        $crate::testing::CancelSafety::new().cuts($cuts).check($make_fut, $invariant)
+    };
+}
@@ -0,0 +1,140 @@
+use crate::{rng::XorShift, Cancellable, FutureCancellable};
+use core::{
+    future::Future,
+    pin::Pin,
+    sync::atomic::{AtomicU64, Ordering},
+    task::{Context, Poll},
+};
+use std::{env, sync::Arc};
+
+///Environment variable read by [`Chaos::from_env`](struct.Chaos.html#method.from_env)
+pub const CHAOS_ENV: &str = "KYANSEL_CHAOS";
+
+///Cancellation injector, handing out stoppers that fire at random polls
+///
+///Futures can only be cancelled between polls, so firing at a random poll cancels them at a
+/// random await point, shaking out the ones that aren't cancellation safe.
+///The stoppers are seeded, each from the seed and the order it was handed out in,
+/// so a failing run can be replayed with the same seed.
+///
+///A disabled injector hands out stoppers that never fire, so the wrapping can stay in place
+/// and chaos be switched on from the environment with
+/// [`from_env`](struct.Chaos.html#method.from_env)
+///
+///Clones share the same sequence of stoppers
+///
+/// # Example
+/// ```rust
+/// # use futures::{executor::block_on, future::poll_fn};
+///  use kyansel::testing::Chaos;
+///  use std::task::Poll;
+///
+///  //`Chaos::from_env()` in a real suite, to run it with `KYANSEL_CHAOS=7:4`
+///  let chaos = Chaos::seeded(7, 4);
+///
+///  let mut polls = 0;
+///  let work = poll_fn(|cx| {
+///      polls += 1;
+///      cx.waker().wake_by_ref();
+///      if polls < 100 { Poll::Pending } else { Poll::Ready(polls) }
+///  });
+///
+///  //cancelled at one of its polls, one in 4 on average
+///  assert!(block_on(chaos.wrap(work)).is_cancelled());
+///
+///  //stoppers from a disabled injector never fire
+///  assert_eq!(block_on(Chaos::disabled().wrap(async { 42 })).finished(), Some(42));
+/// ```
+#[derive(Debug, Clone)]
+pub struct Chaos {
+    config: Option<(u64, u32)>,
+    handed_out: Arc<AtomicU64>,
+}
+
+impl Chaos {
+    ///Create an injector whose stoppers fire at one poll in `one_in` on average,
+    /// the same `seed` always firing at the same polls
+    ///
+    /// # Panics
+    ///Panics if `one_in` is 0
+    pub fn seeded(seed: u64, one_in: u32) -> Self {
+        assert!(one_in > 0, "Chaos can't fire at one poll in 0");
+        Self { config: Some((seed, one_in)), handed_out: Arc::default() }
+    }
+
+    ///Create an injector whose stoppers never fire
+    pub fn disabled() -> Self { Self { config: None, handed_out: Arc::default() } }
+
+    ///Create an injector from the [`KYANSEL_CHAOS`](constant.CHAOS_ENV.html) environment variable,
+    /// disabled if it's not set
+    ///
+    ///The variable holds the seed, optionally followed by `:` and how rarely the stoppers fire,
+    /// one poll in 8 by default: `KYANSEL_CHAOS=42` or `KYANSEL_CHAOS=42:100`
+    ///
+    /// # Panics
+    ///Panics if the variable is set but malformed, so a typo doesn't silently disable chaos
+    pub fn from_env() -> Self {
+        let value = match env::var(CHAOS_ENV) {
+            Ok(value) => value,
+            Err(_) => return Self::disabled(),
+        };
+
+        let (seed, one_in) = match value.split_once(':') {
+            Some((seed, one_in)) => (seed, one_in.parse().ok()),
+            None => (value.as_str(), Some(8)),
+        };
+
+        match (seed.parse(), one_in) {
+            (Ok(seed), Some(one_in)) if one_in > 0 => Self::seeded(seed, one_in),
+            _ => panic!("malformed {}: `{}`, expected `seed` or `seed:one_in`", CHAOS_ENV, value),
+        }
+    }
+
+    ///Check if the stoppers can fire
+    pub fn is_enabled(&self) -> bool { self.config.is_some() }
+
+    ///Retrieve the seed, to replay a failing run
+    pub fn seed(&self) -> Option<u64> { self.config.map(|(seed, _)| seed) }
+
+    ///Hand out the next stopper
+    pub fn stopper(&self) -> ChaosStopper {
+        let stream = self.handed_out.fetch_add(1, Ordering::Relaxed);
+
+        ChaosStopper {
+            rng: self.config.map(|(seed, one_in)| (XorShift::stream(seed, stream), one_in)),
+        }
+    }
+
+    ///Make `fut` cancellable by the next stopper
+    pub fn wrap<F>(&self, fut: F) -> Cancellable<F, ChaosStopper>
+    where
+        F: Future,
+    {
+        fut.cancel_with(self.stopper())
+    }
+}
+
+///Stopper handed out by [`Chaos`](struct.Chaos.html), firing at random polls
+///
+///It never registers a waker, it only fires when polled along with the future it cancels
+#[derive(Debug)]
+#[must_use = "futures do nothing unless you `.await` or poll them"]
+pub struct ChaosStopper {
+    rng: Option<(XorShift, u32)>,
+}
+
+impl Future for ChaosStopper {
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, _: &mut Context) -> Poll<Self::Output> {
+        let (rng, one_in) = match &mut self.get_mut().rng {
+            Some((rng, one_in)) => (rng, *one_in),
+            None => return Poll::Pending,
+        };
+
+        match rng.next_u64() % u64::from(one_in) {
+            0 => Poll::Ready(()),
+            _ => Poll::Pending,
+        }
+    }
+}
@@ -0,0 +1,162 @@
+use super::{MockStopper, Stepper};
+use core::{fmt, future::Future, task::Poll};
+use std::{string::String, vec::Vec};
+use std::panic::{self, AssertUnwindSafe};
+
+///Step of a [`Schedule`](struct.Schedule.html)
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Step {
+    ///The gate at this position was fired
+    Fire(usize),
+
+    ///The future was polled, after being woken unless it's the first poll
+    Poll,
+}
+
+///Ordering of gate firings and polls explored by [`explore`](fn.explore.html)
+#[derive(Debug, PartialEq, Eq, Clone, Default)]
+pub struct Schedule {
+    steps: Vec<Step>,
+}
+
+impl Schedule {
+    ///Retrieve the steps of the schedule, in order
+    pub fn steps(&self) -> &[Step] { &self.steps }
+
+    ///Retrieve how many polls happened before `gate` was fired,
+    /// gates fired before the same poll share the same round
+    pub fn round(&self, gate: usize) -> Option<usize> {
+        let fired = self.steps.iter().position(|step| *step == Step::Fire(gate))?;
+        Some(self.steps[..fired].iter().filter(|step| **step == Step::Poll).count())
+    }
+}
+
+impl fmt::Display for Schedule {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (i, step) in self.steps.iter().enumerate() {
+            if i > 0 {
+                f.write_str(", ")?;
+            }
+            match step {
+                Step::Fire(gate) => write!(f, "fire {}", gate)?,
+                Step::Poll => f.write_str("poll")?,
+            }
+        }
+
+        Ok(())
+    }
+}
+
+//every ordered partition of the gates in rounds fired before the same poll
+fn rounds(remaining: &[usize], prefix: &mut Vec<Vec<usize>>, out: &mut Vec<Vec<Vec<usize>>>) {
+    if remaining.is_empty() {
+        out.push(prefix.clone());
+        return;
+    }
+
+    for mask in 1..(1u32 << remaining.len()) {
+        let (round, rest): (Vec<_>, Vec<_>) =
+            remaining.iter().enumerate().partition(|(i, _)| mask & (1 << i) != 0);
+
+        prefix.push(round.into_iter().map(|(_, gate)| *gate).collect());
+        rounds(&rest.into_iter().map(|(_, gate)| *gate).collect::<Vec<_>>(), prefix, out);
+        prefix.pop();
+    }
+}
+
+///Run a composition under every ordering of its gates firing, checking the outcome of each
+///
+///`make_fut` receives `gates` [`MockStopper`](struct.MockStopper.html)s to build the composition
+/// from, for example using one as the inner future and one as the stopper.
+///Each schedule fires the gates in rounds, firing several gates before the same poll
+/// to simulate near-simultaneous completion, and optionally polling once before any fires.
+///
+///After the first poll, the future is only polled if it was woken,
+/// so a lost wakeup shows up as `None` passed to `check`, which otherwise receives the output.
+///
+///If `check` panics, the panic is raised again with the failing schedule in its message
+///
+/// # Panics
+///Panics if `gates` is more than 8, the number of schedules grows too quickly after that
+///
+/// # Example
+/// ```rust
+///  use futures::FutureExt;
+///  use kyansel::{testing::explore, CancellableResult, FutureCancellable};
+///
+///  explore(
+///      2,
+///      |mut gates| {
+///          let (stopper, inner) = (gates.pop().unwrap(), gates.pop().unwrap());
+///          inner.map(|()| "done").cancel_with(stopper)
+///      },
+///      |schedule, result| {
+///          //the inner future wins ties
+///          let expected = match schedule.round(0) <= schedule.round(1) {
+///              true => CancellableResult::Finished("done"),
+///              false => CancellableResult::Cancelled(()),
+///          };
+///          assert_eq!(result, Some(expected), "{}", schedule);
+///      },
+///  );
+/// ```
+pub fn explore<Mk, F, Chk>(gates: usize, mut make_fut: Mk, mut check: Chk)
+where
+    Mk: FnMut(Vec<MockStopper<()>>) -> F,
+    F: Future,
+    Chk: FnMut(&Schedule, Option<F::Output>),
+{
+    assert!(gates <= 8, "too many gates to explore");
+
+    let mut partitions = Vec::new();
+    rounds(&(0..gates).collect::<Vec<_>>(), &mut Vec::new(), &mut partitions);
+
+    for partition in partitions {
+        for poll_first in [true, false] {
+            let mocks: Vec<_> = (0..gates).map(|_| MockStopper::new()).collect();
+            let mut stepper = Stepper::new(make_fut(mocks.clone()));
+            let mut schedule = Schedule::default();
+            let mut output = None;
+
+            let mut poll = |stepper: &mut Stepper<F>, schedule: &mut Schedule, first: bool| {
+                if output.is_none() && !stepper.is_done() && (first || stepper.is_woken()) {
+                    schedule.steps.push(Step::Poll);
+                    if let Poll::Ready(out) = stepper.step() {
+                        output = Some(out);
+                    }
+                }
+            };
+
+            if poll_first {
+                poll(&mut stepper, &mut schedule, true);
+            }
+
+            for round in &partition {
+                for &gate in round {
+                    schedule.steps.push(Step::Fire(gate));
+                    mocks[gate].fire(());
+                }
+
+                let first = schedule.steps.iter().all(|step| *step != Step::Poll);
+                poll(&mut stepper, &mut schedule, first);
+            }
+
+            //keep going while the future wakes itself
+            while !stepper.is_done() && stepper.is_woken() {
+                poll(&mut stepper, &mut schedule, false);
+            }
+
+            let result = panic::catch_unwind(AssertUnwindSafe(|| check(&schedule, output)));
+            if let Err(panic) = result {
+                let message = match panic.downcast::<String>() {
+                    Ok(message) => *message,
+                    Err(panic) => match panic.downcast::<&str>() {
+                        Ok(message) => String::from(*message),
+                        Err(_) => String::from("non-string panic payload"),
+                    },
+                };
+                panic!("check failed on schedule {}: {}", schedule, message);
+            }
+        }
+    }
+}
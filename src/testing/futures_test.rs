@@ -0,0 +1,149 @@
+//! Interop with `futures-test`
+//!
+//!The adapters of `futures_test::future::FutureTestExt` wrap a whole
+//! [`Cancellable`](../../struct.Cancellable.html), while
+//! [`CancellableTestExt`](trait.CancellableTestExt.html) applies them to the inner future
+//! and the stopper separately.
+//![`poll_once`](fn.poll_once.html) polls a `Cancellable` with a no-op context,
+//! reporting what happened to each side.
+//!
+//! # Example
+//! ```rust
+//! # use futures::{future::{pending, ready}, pin_mut};
+//!  use kyansel::{
+//!      testing::futures_test::{poll_once, CancellableTestExt, Side},
+//!      FutureCancellable,
+//!  };
+//!
+//!  let cancellable = pending::<()>().cancel_with(ready(())).interleave_pending_sides();
+//!  pin_mut!(cancellable);
+//!
+//!  //the stopper returns `Pending` once before completing
+//!  let snapshot = poll_once(cancellable.as_mut());
+//!  assert_eq!((snapshot.inner, snapshot.stopper), (Side::Pending, Side::Pending));
+//!
+//!  let snapshot = poll_once(cancellable.as_mut());
+//!  assert_eq!((snapshot.inner, snapshot.stopper), (Side::Pending, Side::Ready));
+//!  assert!(snapshot.result.is_ready());
+//! ```
+
+use crate::{Cancellable, CancellableResult};
+use core::{future::Future, pin::Pin, task::Poll};
+use futures_test::{
+    future::{AssertUnmoved, FutureTestExt, InterleavePending, PendingOnce},
+    task::noop_context,
+};
+
+///What happened to one side of a [`Cancellable`](../../struct.Cancellable.html)
+/// during a poll
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Side {
+    ///The side wasn't polled
+    NotPolled,
+
+    ///The side was polled and returned `Pending`
+    Pending,
+
+    ///The side was polled and completed
+    Ready,
+}
+
+impl Side {
+    fn of<T>(poll: &Poll<T>) -> Self {
+        match poll {
+            Poll::Pending => Self::Pending,
+            Poll::Ready(_) => Self::Ready,
+        }
+    }
+}
+
+///Outcome of [`poll_once`](fn.poll_once.html)
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct Snapshot<T, S> {
+    ///What happened to the inner future
+    pub inner: Side,
+
+    ///What happened to the stopper
+    pub stopper: Side,
+
+    ///What the `Cancellable` returned
+    pub result: Poll<CancellableResult<T, S>>,
+}
+
+///Poll `cancellable` once with a no-op context, reporting what happened to each side
+///
+///The sides are polled in the same order as
+/// [`Cancellable::poll`](../../struct.Cancellable.html) does
+pub fn poll_once<F, S>(cancellable: Pin<&mut Cancellable<F, S>>) -> Snapshot<F::Output, S::Output>
+where
+    F: Future,
+    S: Future,
+{
+    let mut cx = noop_context();
+    let this = cancellable.project();
+
+    let inner = this.inner.poll(&mut cx);
+    if let Poll::Ready(t) = inner {
+        return Snapshot {
+            inner: Side::Ready,
+            stopper: Side::NotPolled,
+            result: Poll::Ready(CancellableResult::Finished(t)),
+        };
+    }
+
+    let stopper = this.stopper.poll(&mut cx);
+    Snapshot {
+        inner: Side::Pending,
+        stopper: Side::of(&stopper),
+        result: stopper.map(CancellableResult::Cancelled),
+    }
+}
+
+///An extension trait for [`Cancellable`](../../struct.Cancellable.html) applying the
+/// `futures-test` adapters to the inner future and the stopper separately
+///
+///All `Cancellable`s already implement `CancellableTestExt`
+pub trait CancellableTestExt<F, S>
+where
+    F: Future,
+    S: Future,
+{
+    ///Assert that neither side is moved after being pinned
+    fn assert_unmoved_sides(self) -> Cancellable<AssertUnmoved<F>, AssertUnmoved<S>>;
+
+    ///Make both sides return `Pending` once before being polled for real
+    fn pending_once_sides(self) -> Cancellable<PendingOnce<F>, PendingOnce<S>>;
+
+    ///Make both sides return `Pending` before every poll for real
+    fn interleave_pending_sides(self) -> Cancellable<InterleavePending<F>, InterleavePending<S>>;
+}
+
+impl<F, S> CancellableTestExt<F, S> for Cancellable<F, S>
+where
+    F: Future,
+    S: Future,
+{
+    fn assert_unmoved_sides(self) -> Cancellable<AssertUnmoved<F>, AssertUnmoved<S>> {
+        Cancellable {
+            inner: self.inner.assert_unmoved(),
+            stopper: self.stopper.assert_unmoved(),
+            name: self.name,
+        }
+    }
+
+    fn pending_once_sides(self) -> Cancellable<PendingOnce<F>, PendingOnce<S>> {
+        Cancellable {
+            inner: self.inner.pending_once(),
+            stopper: self.stopper.pending_once(),
+            name: self.name,
+        }
+    }
+
+    fn interleave_pending_sides(self) -> Cancellable<InterleavePending<F>, InterleavePending<S>> {
+        Cancellable {
+            inner: self.inner.interleave_pending(),
+            stopper: self.stopper.interleave_pending(),
+            name: self.name,
+        }
+    }
+}
@@ -0,0 +1,147 @@
+use core::{
+    future::Future,
+    pin::Pin,
+    sync::atomic::{AtomicU64, AtomicUsize, Ordering},
+    task::{Context, Poll, Waker},
+    time::Duration,
+};
+use pin_project_lite::pin_project;
+use std::{
+    sync::{Arc, Mutex},
+    task::Wake,
+    time::Instant,
+};
+
+///Activity recorded by an [`Instrumented`](struct.Instrumented.html) future
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+pub struct Stats {
+    ///How many times the future was polled
+    pub polls: usize,
+
+    ///How many times the future woke its task
+    pub wakes: usize,
+
+    ///Total time spent inside `poll`
+    pub busy: Duration,
+}
+
+impl Stats {
+    ///Retrieve how many polls weren't preceded by a wakeup, not counting the first one
+    ///
+    ///A high number usually means someone is busy polling
+    pub fn spurious_polls(&self) -> usize { self.polls.saturating_sub(self.wakes + 1) }
+}
+
+#[derive(Debug, Default)]
+struct Shared {
+    polls: AtomicUsize,
+    wakes: AtomicUsize,
+    busy: AtomicU64,
+    waker: Mutex<Option<Waker>>,
+}
+
+impl Wake for Shared {
+    fn wake(self: Arc<Self>) { self.wake_by_ref() }
+
+    fn wake_by_ref(self: &Arc<Self>) {
+        self.wakes.fetch_add(1, Ordering::AcqRel);
+        if let Some(waker) = &*self.waker.lock().unwrap() {
+            waker.wake_by_ref();
+        }
+    }
+}
+
+///Handle to the [`Stats`](struct.Stats.html) of an [`Instrumented`](struct.Instrumented.html)
+/// future, usable after the future is consumed
+#[derive(Debug, Clone)]
+pub struct Probe {
+    shared: Arc<Shared>,
+}
+
+impl Probe {
+    ///Retrieve the activity recorded so far
+    pub fn stats(&self) -> Stats {
+        Stats {
+            polls: self.shared.polls.load(Ordering::Acquire),
+            wakes: self.shared.wakes.load(Ordering::Acquire),
+            busy: Duration::from_nanos(self.shared.busy.load(Ordering::Acquire)),
+        }
+    }
+}
+
+pin_project! {
+    ///Future recording its polls, wakes and time spent in `poll`
+    ///
+    ///Wrap both the inner future and the stopper to see how a composition drives them
+    ///
+    /// # Example
+    /// ```rust
+    /// # use futures::{executor::block_on, future::{pending, ready}};
+    ///  use kyansel::{testing::Instrumented, FutureCancellable};
+    ///
+    ///  let inner = Instrumented::new(pending::<()>());
+    ///  let stopper = Instrumented::new(ready(()));
+    ///  let (inner_probe, stopper_probe) = (inner.probe(), stopper.probe());
+    ///
+    ///  block_on(inner.cancel_with(stopper));
+    ///
+    ///  assert_eq!(inner_probe.stats().polls, 1);
+    ///  assert_eq!(stopper_probe.stats().polls, 1);
+    ///  assert_eq!(inner_probe.stats().spurious_polls(), 0);
+    /// ```
+    #[must_use = "futures do nothing unless you `.await` or poll them"]
+    pub struct Instrumented<F> {
+        #[pin]
+        fut: F,
+        shared: Arc<Shared>,
+        waker: Waker,
+    }
+}
+
+impl<F> Instrumented<F> {
+    ///Wrap `fut`, recording its activity
+    pub fn new(fut: F) -> Self {
+        let shared = Arc::new(Shared::default());
+
+        Self { fut, waker: Waker::from(shared.clone()), shared }
+    }
+
+    ///Retrieve a handle to the recorded activity
+    pub fn probe(&self) -> Probe { Probe { shared: self.shared.clone() } }
+
+    ///Retrieve the activity recorded so far
+    pub fn stats(&self) -> Stats { self.probe().stats() }
+}
+
+impl<F> Future for Instrumented<F>
+where
+    F: Future,
+{
+    type Output = F::Output;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
+        let this = self.project();
+
+        {
+            let mut waker = this.shared.waker.lock().unwrap();
+            match &*waker {
+                Some(waker) if waker.will_wake(cx.waker()) => {}
+                _ => *waker = Some(cx.waker().clone()),
+            }
+        }
+
+        this.shared.polls.fetch_add(1, Ordering::AcqRel);
+        let start = Instant::now();
+        let poll = this.fut.poll(&mut Context::from_waker(this.waker));
+        let busy = start.elapsed().as_nanos().min(u64::MAX as u128) as u64;
+        this.shared.busy.fetch_add(busy, Ordering::AcqRel);
+
+        poll
+    }
+}
+
+impl<F> core::fmt::Debug for Instrumented<F> {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        f.debug_struct("Instrumented").field("stats", &self.stats()).finish()
+    }
+}
@@ -0,0 +1,110 @@
+use crate::fast_path::CancelSignal;
+use core::{
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll, Waker},
+};
+use std::sync::{Arc, Mutex};
+
+#[derive(Debug)]
+struct State<T> {
+    value: Option<T>,
+    fired: bool,
+    polls: usize,
+    waker: Option<Waker>,
+}
+
+///Stopper fired synchronously from test code
+///
+///Clones share the same state: pass one as the stopper,
+/// and keep another to [`fire`](struct.MockStopper.html#method.fire) it
+/// and inspect how it was polled
+///
+/// # Example
+/// ```rust
+/// # use futures::future::pending;
+///  use kyansel::{testing::{MockStopper, Stepper}, FutureCancellable};
+///
+///  let mock = MockStopper::new();
+///  let mut stepper = Stepper::new(pending::<()>().cancel_with(mock.clone()));
+///
+///  assert!(stepper.step().is_pending());
+///  assert_eq!(mock.polls(), 1);
+///  assert!(mock.has_waker());
+///
+///  mock.fire("stop");
+///  assert!(stepper.is_woken());
+///  assert_eq!(stepper.step().map(|r| r.cancelled()), std::task::Poll::Ready(Some("stop")));
+/// ```
+#[derive(Debug)]
+#[must_use = "futures do nothing unless you `.await` or poll them"]
+pub struct MockStopper<T> {
+    state: Arc<Mutex<State<T>>>,
+}
+
+impl<T> Clone for MockStopper<T> {
+    fn clone(&self) -> Self { Self { state: self.state.clone() } }
+}
+
+impl<T> Default for MockStopper<T> {
+    fn default() -> Self {
+        let state = State { value: None, fired: false, polls: 0, waker: None };
+        Self { state: Arc::new(Mutex::new(state)) }
+    }
+}
+
+impl<T> MockStopper<T> {
+    ///Create a stopper that is not fired
+    pub fn new() -> Self { Self::default() }
+
+    ///Fire the stopper, completing it with `value` and waking it if it was polled
+    ///
+    /// # Panics
+    ///Panics if the stopper was already fired
+    pub fn fire(&self, value: T) {
+        let waker = {
+            let mut state = self.state.lock().unwrap();
+            assert!(!state.fired, "MockStopper fired twice");
+            state.fired = true;
+            state.value = Some(value);
+            state.waker.take()
+        };
+
+        if let Some(waker) = waker {
+            waker.wake();
+        }
+    }
+
+    ///Check if the stopper was fired
+    pub fn is_fired(&self) -> bool { self.state.lock().unwrap().fired }
+
+    ///Retrieve how many times the stopper was polled
+    pub fn polls(&self) -> usize { self.state.lock().unwrap().polls }
+
+    ///Check if the stopper was polled at least once
+    pub fn was_polled(&self) -> bool { self.polls() > 0 }
+
+    ///Check if a waker is registered, waiting for the stopper to fire
+    pub fn has_waker(&self) -> bool { self.state.lock().unwrap().waker.is_some() }
+}
+
+impl<T> CancelSignal for MockStopper<T> {
+    fn is_cancelled(&self) -> bool { self.is_fired() }
+}
+
+impl<T> Future for MockStopper<T> {
+    type Output = T;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
+        let mut state = self.state.lock().unwrap();
+        state.polls += 1;
+
+        if state.fired {
+            let value = state.value.take().expect("MockStopper polled after completion");
+            return Poll::Ready(value);
+        }
+
+        state.waker = Some(cx.waker().clone());
+        Poll::Pending
+    }
+}
@@ -0,0 +1,121 @@
+use core::{
+    future::Future,
+    pin::Pin,
+    sync::atomic::{AtomicBool, AtomicUsize, Ordering},
+    task::{Context, Poll, Waker},
+};
+use std::boxed::Box;
+use std::{sync::Arc, task::Wake};
+
+#[derive(Debug, Default)]
+struct Wakes {
+    woken: AtomicBool,
+    count: AtomicUsize,
+}
+
+impl Wake for Wakes {
+    fn wake(self: Arc<Self>) { self.wake_by_ref() }
+
+    fn wake_by_ref(self: &Arc<Self>) {
+        self.woken.store(true, Ordering::Release);
+        self.count.fetch_add(1, Ordering::AcqRel);
+    }
+}
+
+///Single-threaded executor polling a future one step at a time
+///
+///Wakeups are recorded instead of triggering polls,
+/// so tests decide exactly when and whether the future is polled again
+#[must_use = "the future is only polled by `step`"]
+pub struct Stepper<F>
+where
+    F: Future,
+{
+    fut: Pin<Box<F>>,
+    wakes: Arc<Wakes>,
+    waker: Waker,
+    polls: usize,
+    done: bool,
+}
+
+impl<F> Stepper<F>
+where
+    F: Future,
+{
+    ///Create a stepper for `fut`, which is not polled yet
+    pub fn new(fut: F) -> Self {
+        let wakes = Arc::new(Wakes::default());
+
+        Self { fut: Box::pin(fut), waker: Waker::from(wakes.clone()), wakes, polls: 0, done: false }
+    }
+
+    ///Poll the future once, regardless of it being woken
+    ///
+    /// # Panics
+    ///Panics if the future already completed
+    pub fn step(&mut self) -> Poll<F::Output> {
+        assert!(!self.done, "Stepper stepped after the future completed");
+
+        self.wakes.woken.store(false, Ordering::Release);
+        self.polls += 1;
+
+        let poll = self.fut.as_mut().poll(&mut Context::from_waker(&self.waker));
+        self.done = poll.is_ready();
+        poll
+    }
+
+    ///Poll the future once if it was woken since the last poll
+    ///
+    ///Returns `None` if it wasn't woken
+    pub fn step_if_woken(&mut self) -> Option<Poll<F::Output>> {
+        if self.is_woken() {
+            Some(self.step())
+        } else {
+            None
+        }
+    }
+
+    ///Poll the future as long as it keeps waking itself
+    ///
+    ///Returns `Pending` once the future is waiting for something external
+    pub fn run_until_stalled(&mut self) -> Poll<F::Output> {
+        loop {
+            match self.step() {
+                Poll::Pending if self.is_woken() => continue,
+                poll => return poll,
+            }
+        }
+    }
+
+    ///Check if the future was woken since the last poll
+    pub fn is_woken(&self) -> bool { self.wakes.woken.load(Ordering::Acquire) }
+
+    ///Retrieve how many times the future was woken, in total
+    pub fn wake_count(&self) -> usize { self.wakes.count.load(Ordering::Acquire) }
+
+    ///Retrieve how many times the future was polled
+    pub fn polls(&self) -> usize { self.polls }
+
+    ///Check if the future completed
+    pub fn is_done(&self) -> bool { self.done }
+
+    ///Retrieve the waker handed to the future, to wake it from the test
+    pub fn waker(&self) -> &Waker { &self.waker }
+
+    ///Retrieve the future being stepped
+    pub fn get_mut(&mut self) -> Pin<&mut F> { self.fut.as_mut() }
+}
+
+impl<F> core::fmt::Debug for Stepper<F>
+where
+    F: Future,
+{
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        f.debug_struct("Stepper")
+            .field("polls", &self.polls)
+            .field("wakes", &self.wake_count())
+            .field("woken", &self.is_woken())
+            .field("done", &self.done)
+            .finish()
+    }
+}
@@ -0,0 +1,94 @@
+use core::{
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll},
+};
+use pin_project_lite::pin_project;
+use std::vec::Vec;
+use std::sync::{Arc, Mutex};
+
+///Event recorded by a [`Trace`](struct.Trace.html)
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Event {
+    ///The future with this label was polled
+    Poll(&'static str),
+
+    ///The future with this label completed
+    Ready(&'static str),
+
+    ///The future with this label was dropped before completing
+    Cancelled(&'static str),
+}
+
+///Shared log of the polls of the futures it wraps
+///
+///Clones record into the same log
+#[derive(Debug, Clone, Default)]
+pub struct Trace {
+    events: Arc<Mutex<Vec<Event>>>,
+}
+
+impl Trace {
+    ///Create an empty trace
+    pub fn new() -> Self { Self::default() }
+
+    ///Wrap `fut`, recording its polls with `label`
+    pub fn wrap<F>(&self, label: &'static str, fut: F) -> Traced<F>
+    where
+        F: Future,
+    {
+        Traced { fut, label, trace: self.clone(), done: false }
+    }
+
+    ///Take the events recorded so far, clearing the log
+    pub fn take(&self) -> Vec<Event> { std::mem::take(&mut *self.events.lock().unwrap()) }
+
+    fn record(&self, event: Event) { self.events.lock().unwrap().push(event) }
+}
+
+pin_project! {
+    ///Future wrapped by a [`Trace`](struct.Trace.html)
+    #[must_use = "futures do nothing unless you `.await` or poll them"]
+    pub struct Traced<F> {
+        #[pin]
+        fut: F,
+        label: &'static str,
+        trace: Trace,
+        done: bool,
+    }
+
+    impl<F> PinnedDrop for Traced<F> {
+        fn drop(this: Pin<&mut Self>) {
+            let this = this.project();
+            if !*this.done {
+                this.trace.record(Event::Cancelled(this.label));
+            }
+        }
+    }
+}
+
+impl<F> Future for Traced<F>
+where
+    F: Future,
+{
+    type Output = F::Output;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
+        let this = self.project();
+
+        this.trace.record(Event::Poll(this.label));
+        let poll = this.fut.poll(cx);
+        if poll.is_ready() {
+            *this.done = true;
+            this.trace.record(Event::Ready(this.label));
+        }
+
+        poll
+    }
+}
+
+impl<F> core::fmt::Debug for Traced<F> {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        f.debug_struct("Traced").field("label", &self.label).field("done", &self.done).finish()
+    }
+}
@@ -0,0 +1,223 @@
+//! Time-based cancellation over a pluggable timer
+//!
+//![`cancel_after`](fn.cancel_after.html) and [`cancel_at`](fn.cancel_at.html) work with any
+//! [`Timer`](trait.Timer.html), keeping code that needs deadlines runtime-neutral.
+//!
+//!Implementations are provided for tokio (`tokio::TokioTimer`), async-std
+//! (`async_std::AsyncStdTimer`) and futures-timer (`futures_timer::FuturesTimer`)
+//! behind their respective features.
+//!
+//![`Jittered`](struct.Jittered.html) randomizes the sleeps of another timer,
+//! to avoid thundering herds of retries and hedged requests.
+//!
+//![`ManualTimer`](struct.ManualTimer.html) only moves when told to,
+//! so deadlines can be tested deterministically without real sleeps.
+//!
+//! # Example
+//! ```rust
+//! # use futures::{executor::block_on, future::{pending, Ready, ready}};
+//!  use kyansel::timer::{cancel_after, Timer};
+//! #  use std::time::Duration;
+//!
+//!  //a timer that fires right away, for tests
+//!  struct Immediate;
+//!
+//!  impl Timer for Immediate {
+//!      type Instant = ();
+//!      type Sleep = Ready<()>;
+//!
+//!      fn sleep(&self, _: Duration) -> Self::Sleep { ready(()) }
+//!
+//!      fn sleep_until(&self, _: ()) -> Self::Sleep { ready(()) }
+//!  }
+//!
+//!  let result = block_on(cancel_after(pending::<()>(), &Immediate, Duration::from_secs(60)));
+//!
+//!  assert!(result.is_cancelled());
+//! ```
+
+use super::{Cancellable, FutureCancellable};
+use core::{
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll},
+    time::Duration,
+};
+use pin_project_lite::pin_project;
+
+mod jitter;
+pub use jitter::Jitter;
+#[cfg(feature = "std")]
+pub use jitter::Jittered;
+
+#[cfg(feature = "std")]
+mod manual;
+#[cfg(feature = "std")]
+pub use manual::{ManualSleep, ManualTimer};
+
+///Source of delays, abstracting over runtimes
+pub trait Timer {
+    ///Point in time understood by the timer
+    type Instant;
+
+    ///Future completing after a delay
+    type Sleep: Future<Output = ()>;
+
+    ///Create a future completing after `duration`
+    fn sleep(&self, duration: Duration) -> Self::Sleep;
+
+    ///Create a future completing at `deadline`, or right away if it's already passed
+    fn sleep_until(&self, deadline: Self::Instant) -> Self::Sleep;
+}
+
+impl<T: ?Sized> Timer for &T
+where
+    T: Timer,
+{
+    type Instant = T::Instant;
+    type Sleep = T::Sleep;
+
+    fn sleep(&self, duration: Duration) -> Self::Sleep { (**self).sleep(duration) }
+
+    fn sleep_until(&self, deadline: Self::Instant) -> Self::Sleep { (**self).sleep_until(deadline) }
+}
+
+///Cancel `fut` if it hasn't completed after `duration`
+pub fn cancel_after<F, T>(fut: F, timer: &T, duration: Duration) -> Cancellable<F, T::Sleep>
+where
+    F: Future,
+    T: Timer + ?Sized,
+{
+    fut.cancel_with(timer.sleep(duration))
+}
+
+///Cancel `fut` if it hasn't completed by `deadline`
+pub fn cancel_at<F, T>(fut: F, timer: &T, deadline: T::Instant) -> Cancellable<F, T::Sleep>
+where
+    F: Future,
+    T: Timer + ?Sized,
+{
+    fut.cancel_with(timer.sleep_until(deadline))
+}
+
+///Result returned by [`TimeoutOrCancel`](struct.TimeoutOrCancel.html)
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum TimeoutOrCancelled<T, S> {
+    ///If the inner future finished
+    Finished(T),
+
+    ///If the deadline passed
+    TimedOut,
+
+    ///If the stopper completed
+    Cancelled(S),
+}
+
+impl<T, S> TimeoutOrCancelled<T, S> {
+    ///Check if the deadline passed
+    pub fn is_timed_out(&self) -> bool { matches!(self, Self::TimedOut) }
+
+    ///Check if the stopper completed
+    pub fn is_cancelled(&self) -> bool { matches!(self, Self::Cancelled(_)) }
+
+    ///Retrieve the result of the future
+    /// if it finished
+    pub fn finished(self) -> Option<T> {
+        match self {
+            Self::Finished(t) => Some(t),
+            _ => None,
+        }
+    }
+
+    ///Retrieve the result of the stopper
+    /// if the future was cancelled
+    pub fn cancelled(self) -> Option<S> {
+        match self {
+            Self::Cancelled(s) => Some(s),
+            _ => None,
+        }
+    }
+}
+
+pin_project! {
+    ///Future cancelled either by a stopper or by a deadline, telling which one fired
+    ///
+    ///Created with [`timeout_or_cancel`](fn.timeout_or_cancel.html)
+    #[derive(Debug)]
+    #[must_use = "futures do nothing unless you `.await` or poll them"]
+    pub struct TimeoutOrCancel<F, S, D> {
+        #[pin]
+        inner: F,
+        #[pin]
+        stopper: S,
+        #[pin]
+        delay: D,
+    }
+}
+
+impl<F, S, D> Future for TimeoutOrCancel<F, S, D>
+where
+    F: Future,
+    S: Future,
+    D: Future<Output = ()>,
+{
+    type Output = TimeoutOrCancelled<F::Output, S::Output>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
+        let this = self.project();
+
+        //always poll inner future first
+        if let Poll::Ready(t) = this.inner.poll(cx) {
+            return Poll::Ready(TimeoutOrCancelled::Finished(t));
+        }
+
+        //an explicit cancellation wins over the deadline
+        if let Poll::Ready(s) = this.stopper.poll(cx) {
+            return Poll::Ready(TimeoutOrCancelled::Cancelled(s));
+        }
+
+        match this.delay.poll(cx) {
+            Poll::Ready(()) => Poll::Ready(TimeoutOrCancelled::TimedOut),
+            Poll::Pending => Poll::Pending,
+        }
+    }
+}
+
+///Cancel `fut` when either `stopper` or `delay` completes,
+/// reporting which one did
+///
+///`delay` can be any future, like one created by [`Timer::sleep`](trait.Timer.html#tymethod.sleep)
+///
+/// # Example
+/// ```rust
+/// # use futures::{executor::block_on, future::{pending, ready}};
+///  use kyansel::timer::{timeout_or_cancel, TimeoutOrCancelled};
+///
+///  let result = block_on(timeout_or_cancel(pending::<()>(), pending::<()>(), ready(())));
+///
+///  assert_eq!(result, TimeoutOrCancelled::TimedOut);
+/// ```
+pub const fn timeout_or_cancel<F, S, D>(fut: F, stopper: S, delay: D) -> TimeoutOrCancel<F, S, D>
+where
+    F: Future,
+    S: Future,
+    D: Future<Output = ()>,
+{
+    TimeoutOrCancel { inner: fut, stopper, delay }
+}
+
+///Cancel `fut` when either `stopper` completes or `duration` passes on `timer`,
+/// reporting which one did
+pub fn cancel_or_timeout<F, S, T>(
+    fut: F,
+    stopper: S,
+    timer: &T,
+    duration: Duration,
+) -> TimeoutOrCancel<F, S, T::Sleep>
+where
+    F: Future,
+    S: Future,
+    T: Timer + ?Sized,
+{
+    timeout_or_cancel(fut, stopper, timer.sleep(duration))
+}
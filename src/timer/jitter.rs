@@ -0,0 +1,139 @@
+#[cfg(feature = "std")]
+use super::Timer;
+use core::time::Duration;
+
+///Policy randomizing delays, so that many clients don't fire at the same time
+///
+///Applied with [`Jitter::apply`](enum.Jitter.html#method.apply), or to every sleep of a timer
+/// by wrapping it in [`Jittered`](struct.Jittered.html)
+#[derive(Debug, PartialEq, Clone, Copy, Default)]
+pub enum Jitter {
+    ///Keep the delay as is
+    #[default]
+    None,
+
+    ///Pick a delay between zero and the full delay
+    Full,
+
+    ///Pick a delay between half and the full delay
+    Equal,
+
+    ///Pick a delay within the given fraction of the delay, above or below it
+    ///
+    ///The fraction is clamped between 0 and 1, NaN counting as 0
+    Proportional(f64),
+}
+
+fn clamp_unit(x: f64) -> f64 {
+    match x.is_nan() {
+        true => 0.0,
+        false => x.clamp(0.0, 1.0),
+    }
+}
+
+//like `Duration::mul_f64`, saturating instead of panicking when the result doesn't fit
+fn scale(delay: Duration, factor: f64) -> Duration {
+    Duration::try_from_secs_f64(delay.as_secs_f64() * factor).unwrap_or(Duration::MAX)
+}
+
+impl Jitter {
+    ///Randomize `delay` using `unit`, a random number between 0 (inclusive) and 1 (exclusive)
+    ///
+    ///This is useful to bring your own source of randomness, or on `no_std` targets.
+    ///`unit` is clamped between 0 and 1, NaN counting as 0,
+    /// and the result saturates at `Duration::MAX` instead of overflowing
+    ///
+    /// # Example
+    /// ```rust
+    ///  use kyansel::timer::Jitter;
+    /// #  use std::time::Duration;
+    ///
+    ///  let delay = Duration::from_secs(10);
+    ///
+    ///  assert_eq!(Jitter::Equal.apply_with(delay, 0.0), Duration::from_secs(5));
+    ///  assert_eq!(Jitter::Proportional(0.1).apply_with(delay, 0.5), delay);
+    ///
+    ///  //a broken source of randomness doesn't panic
+    ///  assert_eq!(Jitter::Full.apply_with(delay, f64::NAN), Duration::ZERO);
+    ///  assert_eq!(Jitter::Proportional(0.5).apply_with(Duration::MAX, 1.0), Duration::MAX);
+    /// ```
+    pub fn apply_with(&self, delay: Duration, unit: f64) -> Duration {
+        let unit = clamp_unit(unit);
+
+        match *self {
+            Self::None => delay,
+            Self::Full => scale(delay, unit),
+            Self::Equal => (delay / 2).saturating_add(scale(delay / 2, unit)),
+            Self::Proportional(fraction) => {
+                let fraction = clamp_unit(fraction);
+                scale(delay, 1.0 - fraction + 2.0 * fraction * unit)
+            }
+        }
+    }
+
+    ///Randomize `delay`
+    ///
+    ///The randomness is good enough to spread delays, but it's not cryptographically secure
+    #[cfg(feature = "std")]
+    pub fn apply(&self, delay: Duration) -> Duration {
+        use std::{
+            collections::hash_map::RandomState,
+            hash::{BuildHasher, Hasher},
+        };
+
+        //every `RandomState` is seeded differently
+        let bits = RandomState::new().build_hasher().finish() >> 11;
+        self.apply_with(delay, bits as f64 / (1u64 << 53) as f64)
+    }
+}
+
+///[`Timer`](trait.Timer.html) applying a [`Jitter`](enum.Jitter.html) to every sleep
+///
+///Wrapping a timer adds jitter to all the features using it,
+/// from [`cancel_after`](fn.cancel_after.html) to
+/// [`hedge_after`](../race/fn.hedge_after.html).
+///Deadlines are left untouched
+///
+/// # Example
+/// ```rust
+///  use kyansel::timer::{Jitter, Jittered, ManualTimer, Timer};
+/// #  use std::time::Duration;
+///
+///  let timer = Jittered::new(ManualTimer::new(), Jitter::Equal);
+///  let _sleep = timer.sleep(Duration::from_secs(10)); //between 5 and 10 seconds
+/// ```
+#[cfg(feature = "std")]
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Jittered<T> {
+    timer: T,
+    jitter: Jitter,
+}
+
+#[cfg(feature = "std")]
+impl<T> Jittered<T> {
+    ///Wrap `timer`, applying `jitter` to its sleeps
+    pub fn new(timer: T, jitter: Jitter) -> Self { Self { timer, jitter } }
+
+    ///Retrieve the jitter applied to the sleeps
+    pub fn jitter(&self) -> Jitter { self.jitter }
+
+    ///Retrieve the wrapped timer
+    pub fn into_inner(self) -> T { self.timer }
+}
+
+#[cfg(feature = "std")]
+impl<T> Timer for Jittered<T>
+where
+    T: Timer,
+{
+    type Instant = T::Instant;
+    type Sleep = T::Sleep;
+
+    fn sleep(&self, duration: Duration) -> Self::Sleep {
+        self.timer.sleep(self.jitter.apply(duration))
+    }
+
+    fn sleep_until(&self, deadline: Self::Instant) -> Self::Sleep {
+        self.timer.sleep_until(deadline)
+    }
+}
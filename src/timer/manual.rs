@@ -0,0 +1,140 @@
+use super::Timer;
+use core::{
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll, Waker},
+    time::Duration,
+};
+use std::vec::Vec;
+use std::sync::{Arc, Mutex};
+
+#[derive(Debug, Default)]
+struct Clock {
+    now: Duration,
+    sleepers: Vec<Option<(Duration, Waker)>>,
+    free: Vec<usize>,
+}
+
+///[`Timer`](trait.Timer.html) driven by hand, for deterministic tests
+///
+///Time only moves when [`advance`](struct.ManualTimer.html#method.advance) is called,
+/// and instants are durations since the timer was created.
+///Clones share the same clock
+///
+/// # Example
+/// ```rust
+/// # use futures::{executor::block_on, future::pending, pin_mut, poll};
+///  use kyansel::timer::{cancel_after, ManualTimer};
+/// #  use std::time::Duration;
+///
+/// # block_on(async {
+///  let timer = ManualTimer::new();
+///  let cancellable = cancel_after(pending::<()>(), &timer, Duration::from_secs(60));
+///  pin_mut!(cancellable);
+///
+///  timer.advance(Duration::from_secs(59));
+///  assert!(poll!(cancellable.as_mut()).is_pending());
+///
+///  timer.advance(Duration::from_secs(1));
+///  assert!(cancellable.await.is_cancelled());
+/// # });
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct ManualTimer {
+    clock: Arc<Mutex<Clock>>,
+}
+
+impl ManualTimer {
+    ///Create a new timer, starting at zero
+    pub fn new() -> Self { Self::default() }
+
+    ///Retrieve the current time of the timer
+    pub fn now(&self) -> Duration { self.clock.lock().unwrap().now }
+
+    ///Move the clock forward by `duration`, waking the sleeps that are due
+    pub fn advance(&self, duration: Duration) {
+        let wakers: Vec<_> = {
+            let mut clock = self.clock.lock().unwrap();
+            clock.now += duration;
+
+            let now = clock.now;
+            let mut wakers = Vec::new();
+            for (slot, sleeper) in clock.sleepers.iter_mut().enumerate() {
+                if matches!(sleeper, Some((deadline, _)) if *deadline <= now) {
+                    wakers.push((slot, sleeper.take()));
+                }
+            }
+            clock.free.extend(wakers.iter().map(|(slot, _)| *slot));
+            wakers.into_iter().filter_map(|(_, sleeper)| sleeper).collect()
+        };
+
+        //wake outside of the lock, wakers might poll us again
+        wakers.into_iter().for_each(|(_, waker)| waker.wake());
+    }
+}
+
+impl Timer for ManualTimer {
+    type Instant = Duration;
+    type Sleep = ManualSleep;
+
+    fn sleep(&self, duration: Duration) -> Self::Sleep { self.sleep_until(self.now() + duration) }
+
+    fn sleep_until(&self, deadline: Self::Instant) -> Self::Sleep {
+        ManualSleep { clock: self.clock.clone(), deadline, slot: None }
+    }
+}
+
+///Future returned by [`ManualTimer`](struct.ManualTimer.html)
+#[derive(Debug)]
+#[must_use = "futures do nothing unless you `.await` or poll them"]
+pub struct ManualSleep {
+    clock: Arc<Mutex<Clock>>,
+    deadline: Duration,
+    slot: Option<usize>,
+}
+
+impl Future for ManualSleep {
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
+        let this = self.get_mut();
+        let mut clock = this.clock.lock().unwrap();
+
+        if clock.now >= this.deadline {
+            return Poll::Ready(());
+        }
+
+        let sleeper = Some((this.deadline, cx.waker().clone()));
+        match this.slot {
+            //slots are only freed once the deadline has passed, this one is still ours
+            Some(slot) => clock.sleepers[slot] = sleeper,
+            None => {
+                this.slot = Some(match clock.free.pop() {
+                    Some(slot) => {
+                        clock.sleepers[slot] = sleeper;
+                        slot
+                    }
+                    None => {
+                        clock.sleepers.push(sleeper);
+                        clock.sleepers.len() - 1
+                    }
+                });
+            }
+        }
+
+        Poll::Pending
+    }
+}
+
+impl Drop for ManualSleep {
+    fn drop(&mut self) {
+        if let Some(slot) = self.slot {
+            let mut clock = self.clock.lock().unwrap();
+            //the slot was already freed when the deadline passed
+            if clock.now < self.deadline {
+                clock.sleepers[slot] = None;
+                clock.free.push(slot);
+            }
+        }
+    }
+}
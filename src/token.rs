@@ -0,0 +1,379 @@
+//! Shared cancellation tokens
+//!
+//! A [`CancellationToken`](struct.CancellationToken.html) can be cloned and handed around freely,
+//! and [`cancelled`](struct.CancellationToken.html#method.cancelled) returns a future usable as a
+//! stopper for [`cancel_with`](../trait.FutureCancellable.html#method.cancel_with)
+//!
+//! # Example
+//! ```rust
+//! # use futures::{executor::block_on, future::pending};
+//!  use kyansel::{token::CancellationToken, FutureCancellable};
+//!
+//!  let token = CancellationToken::new();
+//!  let child = token.child_token();
+//!
+//!  let cancellable = pending::<()>().cancel_with(child.cancelled());
+//!
+//!  //cancelling the parent cancels all the children
+//!  token.cancel();
+//!
+//!  assert!(block_on(cancellable).is_cancelled());
+//! ```
+
+use super::fast_path::CancelSignal;
+use core::{
+    future::Future,
+    pin::Pin,
+    sync::atomic::{AtomicBool, Ordering},
+    task::{Context, Poll, Waker},
+};
+use pin_project_lite::pin_project;
+use std::vec::Vec;
+use std::sync::{Arc, Mutex, Weak};
+
+#[cfg(feature = "debug-cause")]
+use std::{backtrace::Backtrace, panic::Location, sync::OnceLock};
+
+///Cloneable handle to a shared cancellation state
+///
+///All clones observe the same state, cancelling one cancels them all
+#[derive(Debug, Clone, Default)]
+pub struct CancellationToken {
+    inner: Arc<Inner>,
+}
+
+#[derive(Debug, Default)]
+struct Inner {
+    cancelled: AtomicBool,
+    state: Mutex<State>,
+    #[cfg(feature = "debug-cause")]
+    cause: OnceLock<Cause>,
+}
+
+#[derive(Debug, Default)]
+struct State {
+    wakers: Vec<Option<Waker>>,
+    free: Vec<u32>,
+    children: Vec<Weak<Inner>>,
+}
+
+impl Inner {
+    fn cancel(&self) {
+        if self.cancelled.swap(true, Ordering::AcqRel) {
+            return;
+        }
+
+        let (wakers, children) = {
+            let mut state = self.state.lock().unwrap();
+            state.free.clear();
+            (std::mem::take(&mut state.wakers), std::mem::take(&mut state.children))
+        };
+
+        //wake outside of the lock, wakers might poll us again
+        wakers.into_iter().flatten().for_each(Waker::wake);
+        children.iter().filter_map(Weak::upgrade).for_each(|child| {
+            #[cfg(feature = "debug-cause")]
+            self.inherit_cause(&child);
+            child.cancel()
+        });
+    }
+
+    #[cfg(feature = "debug-cause")]
+    fn inherit_cause(&self, child: &Inner) {
+        if let Some(cause) = self.cause.get() {
+            let _ = child.cause.set(cause.clone());
+        }
+    }
+}
+
+///Where a token was cancelled from
+///
+///Recorded when the `debug-cause` feature is enabled,
+/// retrieved with [`CancellationToken::cause`](struct.CancellationToken.html#method.cause)
+/// or [`cancelled_with_cause`](struct.CancellationToken.html#method.cancelled_with_cause)
+#[cfg(feature = "debug-cause")]
+#[derive(Debug, Clone)]
+pub struct Cause {
+    location: &'static Location<'static>,
+    backtrace: Arc<Backtrace>,
+}
+
+#[cfg(feature = "debug-cause")]
+impl Cause {
+    #[track_caller]
+    fn capture() -> Self {
+        Self { location: Location::caller(), backtrace: Arc::new(Backtrace::capture()) }
+    }
+
+    ///Retrieve the location of the call to
+    /// [`cancel`](struct.CancellationToken.html#method.cancel)
+    ///
+    ///Children of the token report the location where the parent was cancelled
+    pub fn location(&self) -> &'static Location<'static> { self.location }
+
+    ///Retrieve the backtrace captured when the token was cancelled
+    ///
+    ///Like `Backtrace::capture`, it's only captured if the `RUST_BACKTRACE`
+    /// or `RUST_LIB_BACKTRACE` environment variables are set
+    pub fn backtrace(&self) -> &Backtrace { &self.backtrace }
+}
+
+#[cfg(feature = "debug-cause")]
+impl core::fmt::Display for Cause {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        write!(f, "cancelled at {}", self.location)
+    }
+}
+
+impl CancellationToken {
+    ///Create a new token that is not cancelled
+    pub fn new() -> Self { Self::default() }
+
+    ///Cancel this token, all its clones and all its children
+    ///
+    ///Cancelling an already cancelled token does nothing
+    #[cfg_attr(feature = "debug-cause", track_caller)]
+    pub fn cancel(&self) {
+        //recorded first, so that whoever sees the token cancelled also sees the cause
+        #[cfg(feature = "debug-cause")]
+        let _ = self.inner.cause.set(Cause::capture());
+
+        self.inner.cancel()
+    }
+
+    ///Retrieve a future completing right away, unless the token was cancelled
+    ///
+    ///Once the token is cancelled the future never completes, so awaiting it in a future
+    /// cancelled with [`cancelled`](struct.CancellationToken.html#method.cancelled) makes it yield,
+    /// and the cancellation take effect, even if everything else it awaits is always ready
+    pub fn checkpoint(&self) -> TokenCheckpoint<'_> { TokenCheckpoint { token: self } }
+
+    ///Retrieve where the token was cancelled from, if it was
+    #[cfg(feature = "debug-cause")]
+    pub fn cause(&self) -> Option<Cause> {
+        match self.is_cancelled() {
+            true => self.inner.cause.get().cloned(),
+            false => None,
+        }
+    }
+
+    ///Retrieve a future that completes with the cause of the cancellation
+    /// when the token is cancelled
+    ///
+    /// # Example
+    /// ```rust
+    /// # use futures::{executor::block_on, future::pending};
+    ///  use kyansel::{token::CancellationToken, FutureCancellable};
+    ///
+    ///  let token = CancellationToken::new();
+    ///  let cancellable = pending::<()>().cancel_with(token.cancelled_with_cause());
+    ///
+    ///  token.cancel();
+    ///
+    ///  let cause = block_on(cancellable).cancelled().unwrap();
+    ///  assert_eq!(cause.location().file(), file!());
+    /// ```
+    #[cfg(feature = "debug-cause")]
+    pub fn cancelled_with_cause(&self) -> WaitForCause {
+        WaitForCause { cancelled: self.cancelled() }
+    }
+
+    ///Check if the token was cancelled
+    pub fn is_cancelled(&self) -> bool { self.inner.cancelled.load(Ordering::Acquire) }
+
+    //check if both are clones of the same token
+    #[cfg(feature = "control")]
+    pub(crate) fn same_as(&self, other: &Self) -> bool { Arc::ptr_eq(&self.inner, &other.inner) }
+
+    ///Create a token that will be cancelled when this one is,
+    /// but that can also be cancelled on its own without affecting the parent
+    pub fn child_token(&self) -> Self {
+        let child = Self::new();
+
+        let mut state = self.inner.state.lock().unwrap();
+        if self.is_cancelled() {
+            drop(state);
+            #[cfg(feature = "debug-cause")]
+            self.inner.inherit_cause(&child.inner);
+            child.cancel();
+        } else {
+            state.children.retain(|c| c.strong_count() > 0);
+            state.children.push(Arc::downgrade(&child.inner));
+        }
+
+        child
+    }
+
+    ///Retrieve a future that completes when the token is cancelled
+    ///
+    ///The future owns a clone of the token so it can be used as a stopper freely
+    pub fn cancelled(&self) -> WaitForCancellation {
+        WaitForCancellation { token: self.clone(), slot: None }
+    }
+
+    ///Retrieve a future that cancels this token when `stopper` completes
+    ///
+    ///The future completes with the output of the stopper,
+    /// or with `None` if the token was cancelled some other way first
+    pub fn cancel_on<S>(&self, stopper: S) -> CancelOn<S>
+    where
+        S: Future,
+    {
+        CancelOn { token: self.clone(), cancelled: self.cancelled(), stopper }
+    }
+}
+
+pin_project! {
+    ///Future returned by
+    /// [`CancellationToken::cancel_on`](struct.CancellationToken.html#method.cancel_on)
+    #[derive(Debug)]
+    #[must_use = "futures do nothing unless you `.await` or poll them"]
+    pub struct CancelOn<S> {
+        token: CancellationToken,
+        cancelled: WaitForCancellation,
+        #[pin]
+        stopper: S,
+    }
+}
+
+impl<S> Future for CancelOn<S>
+where
+    S: Future,
+{
+    type Output = Option<S::Output>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
+        let this = self.project();
+
+        if let Poll::Ready(s) = this.stopper.poll(cx) {
+            this.token.cancel();
+            return Poll::Ready(Some(s));
+        }
+
+        Pin::new(this.cancelled).poll(cx).map(|_| None)
+    }
+}
+
+///Future returned by
+/// [`CancellationToken::checkpoint`](struct.CancellationToken.html#method.checkpoint)
+#[derive(Debug)]
+#[must_use = "futures do nothing unless you `.await` or poll them"]
+pub struct TokenCheckpoint<'a> {
+    token: &'a CancellationToken,
+}
+
+impl Future for TokenCheckpoint<'_> {
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, _: &mut Context) -> Poll<Self::Output> {
+        match self.token.is_cancelled() {
+            //whoever waits on the token was woken already
+            true => Poll::Pending,
+            false => Poll::Ready(()),
+        }
+    }
+}
+
+///Future returned by
+/// [`CancellationToken::cancelled`](struct.CancellationToken.html#method.cancelled)
+#[derive(Debug)]
+#[must_use = "futures do nothing unless you `.await` or poll them"]
+pub struct WaitForCancellation {
+    token: CancellationToken,
+    //a `u32` keeps the future two words large on 64-bit targets, with the token
+    slot: Option<u32>,
+}
+
+impl Future for WaitForCancellation {
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
+        let this = self.get_mut();
+
+        if this.token.is_cancelled() {
+            return Poll::Ready(());
+        }
+
+        let mut state = this.token.inner.state.lock().unwrap();
+
+        //check again now that we hold the lock, `cancel` might have run in between
+        if this.token.is_cancelled() {
+            return Poll::Ready(());
+        }
+
+        match this.slot {
+            Some(slot) => match &mut state.wakers[slot as usize] {
+                Some(waker) if waker.will_wake(cx.waker()) => {}
+                waker => *waker = Some(cx.waker().clone()),
+            },
+            None => {
+                let waker = Some(cx.waker().clone());
+                this.slot = Some(match state.free.pop() {
+                    Some(slot) => {
+                        state.wakers[slot as usize] = waker;
+                        slot
+                    }
+                    None => {
+                        state.wakers.push(waker);
+                        (state.wakers.len() - 1) as u32
+                    }
+                });
+            }
+        }
+
+        Poll::Pending
+    }
+}
+
+//a clone waits for the same token, but it has to register its own waker
+impl Clone for WaitForCancellation {
+    fn clone(&self) -> Self { self.token.cancelled() }
+}
+
+impl CancelSignal for WaitForCancellation {
+    fn is_cancelled(&self) -> bool { self.token.is_cancelled() }
+}
+
+impl Drop for WaitForCancellation {
+    fn drop(&mut self) {
+        if let Some(slot) = self.slot {
+            let mut state = self.token.inner.state.lock().unwrap();
+            //the slots are cleared on cancellation
+            if let Some(waker) = state.wakers.get_mut(slot as usize) {
+                *waker = None;
+                state.free.push(slot);
+            }
+        }
+    }
+}
+
+///Future returned by
+/// [`CancellationToken::cancelled_with_cause`](struct.CancellationToken.html#method.cancelled_with_cause)
+#[cfg(feature = "debug-cause")]
+#[derive(Debug)]
+#[must_use = "futures do nothing unless you `.await` or poll them"]
+pub struct WaitForCause {
+    cancelled: WaitForCancellation,
+}
+
+#[cfg(feature = "debug-cause")]
+impl CancelSignal for WaitForCause {
+    fn is_cancelled(&self) -> bool { self.cancelled.is_cancelled() }
+}
+
+#[cfg(feature = "debug-cause")]
+impl Future for WaitForCause {
+    type Output = Cause;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
+        let this = self.get_mut();
+
+        match Pin::new(&mut this.cancelled).poll(cx) {
+            Poll::Ready(()) => {
+                let cause = this.cancelled.token.inner.cause.get().cloned();
+                Poll::Ready(cause.expect("cancelled without a cause"))
+            }
+            Poll::Pending => Poll::Pending,
+        }
+    }
+}
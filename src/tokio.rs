@@ -0,0 +1,887 @@
+//! Integration with the tokio runtime
+//!
+//! The [`Cancellable`](../struct.Cancellable.html) combinator can only cancel a future
+//! while it's being polled. [`spawn_cancellable`](fn.spawn_cancellable.html) instead spawns the
+//! future as a task and aborts it with
+//! [`JoinHandle::abort`](https://docs.rs/tokio/1/tokio/task/struct.JoinHandle.html#method.abort)
+//! when the signal completes, even if nobody is awaiting the task.
+//!
+//! [`CancellableJoinSet`](struct.CancellableJoinSet.html) does the same for a whole
+//! `JoinSet` sharing one stopper.
+//!
+//!With the `tracing` feature,
+//! [`spawn_cancellable_instrumented`](fn.spawn_cancellable_instrumented.html) attaches a span
+//! to the task and its signal, so cancelled tasks can be told apart in `tokio-console`.
+//!
+//! Blocking work can't be aborted,
+//! [`spawn_blocking_cancellable`](fn.spawn_blocking_cancellable.html) hands the closure a token
+//! to check cooperatively instead.
+//!
+//!Cancellables awaited in a loop can keep a task busy for long when they're always ready,
+//! [`Cancellable::cooperative`](../struct.Cancellable.html#method.cooperative) makes them consume
+//! tokio's cooperative budget, so the task yields once it's spent.
+//!
+//![`timeout`](fn.timeout.html) is a drop-in replacement for `tokio::time::timeout`,
+//! with an [`Elapsed`](struct.Elapsed.html) error behaving like tokio's.
+//!
+//![`BroadcastStopper`](struct.BroadcastStopper.html) turns a `broadcast::Receiver`,
+//! the usual shutdown bus of a service, into a stopper firing on the next matching message.
+//!
+//! # Example
+//! ```rust
+//! # #[tokio::main]
+//! # async fn main() {
+//!  use kyansel::tokio::{spawn_cancellable, TaskResult};
+//! #  use std::time::Duration;
+//! #  use tokio::{sync::oneshot, time::sleep};
+//!
+//!  let (tx, rx) = oneshot::channel::<&str>();
+//!
+//!  let handle = spawn_cancellable(sleep(Duration::from_secs(60)), rx);
+//!  tx.send("shutdown").unwrap();
+//!
+//!  match handle.await {
+//!      TaskResult::Cancelled(reason) => assert_eq!(reason, Ok("shutdown")),
+//!      _ => unreachable!(),
+//!  }
+//! # }
+//! ```
+
+use super::{
+    fast_path::{CancelSignal, FastPath},
+    timer::Timer,
+    token::CancellationToken,
+    Cancellable, CancellableResult, FutureCancellable,
+};
+use core::{
+    any::Any,
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll},
+};
+use pin_project_lite::pin_project;
+use std::boxed::Box;
+use std::{
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+use tokio::{
+    sync::broadcast::{error::RecvError, Receiver},
+    task::{
+        coop::{cooperative, Coop},
+        AbortHandle, JoinError, JoinHandle, JoinSet,
+    },
+    time::{Instant, Sleep},
+};
+
+///Result returned by [`CancellableJoinHandle`](struct.CancellableJoinHandle.html)
+/// and [`CancellableJoinSet`](struct.CancellableJoinSet.html)
+#[derive(Debug)]
+pub enum TaskResult<T, S> {
+    ///If the task finished
+    Finished(T),
+
+    ///If the task was aborted because the signal completed
+    Cancelled(S),
+
+    ///If the task was aborted without the signal completing,
+    /// for example because the runtime shut down
+    Aborted,
+
+    ///If the task panicked, holding the panic payload
+    Panicked(Box<dyn Any + Send + 'static>),
+}
+
+impl<T, S> TaskResult<T, S> {
+    ///Check if the task was cancelled by the signal
+    pub fn is_cancelled(&self) -> bool { matches!(self, Self::Cancelled(_)) }
+
+    ///Check if the task panicked
+    pub fn is_panic(&self) -> bool { matches!(self, Self::Panicked(_)) }
+
+    ///Retrieve the result of the task
+    /// if it finished
+    pub fn finished(self) -> Option<T> {
+        match self {
+            Self::Finished(t) => Some(t),
+            _ => None,
+        }
+    }
+
+    ///Retrieve the result of the signal
+    /// if the task was cancelled
+    pub fn cancelled(self) -> Option<S> {
+        match self {
+            Self::Cancelled(s) => Some(s),
+            _ => None,
+        }
+    }
+}
+
+///Handle to a task spawned with [`spawn_cancellable`](fn.spawn_cancellable.html)
+///
+///Dropping the handle detaches the task, the signal will still abort it
+#[derive(Debug)]
+#[must_use = "dropping the handle detaches the task"]
+pub struct CancellableJoinHandle<T, S> {
+    task: JoinHandle<T>,
+    watcher: JoinHandle<CancellableResult<S, ()>>,
+}
+
+impl<T, S> CancellableJoinHandle<T, S> {
+    ///Abort the task right away, without waiting for the signal
+    ///
+    ///The handle will resolve to [`TaskResult::Aborted`](enum.TaskResult.html#variant.Aborted)
+    /// unless the task already finished
+    pub fn abort(&self) {
+        self.task.abort();
+        self.watcher.abort();
+    }
+
+    ///Check if the task has completed, either by finishing or by being aborted
+    pub fn is_finished(&self) -> bool { self.task.is_finished() }
+}
+
+impl<T, S> Future for CancellableJoinHandle<T, S> {
+    type Output = TaskResult<T, S>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
+        let this = self.get_mut();
+
+        let err = match Pin::new(&mut this.task).poll(cx) {
+            Poll::Pending => return Poll::Pending,
+            Poll::Ready(Ok(t)) => return Poll::Ready(TaskResult::Finished(t)),
+            Poll::Ready(Err(err)) => err,
+        };
+
+        if err.is_panic() {
+            return Poll::Ready(TaskResult::Panicked(err.into_panic()));
+        }
+
+        //the task was aborted, the watcher knows if it was because of the signal
+        match Pin::new(&mut this.watcher).poll(cx) {
+            Poll::Pending => Poll::Pending,
+            Poll::Ready(Ok(CancellableResult::Finished(s))) => {
+                Poll::Ready(TaskResult::Cancelled(s))
+            }
+            Poll::Ready(_) => Poll::Ready(TaskResult::Aborted),
+        }
+    }
+}
+
+//cancels the token when the task completes, is aborted or panics
+struct DoneGuard(CancellationToken);
+
+impl Drop for DoneGuard {
+    fn drop(&mut self) { self.0.cancel() }
+}
+
+///Spawn `fut` on the current tokio runtime, aborting it when `signal` completes
+///
+///Unlike [`cancel_with`](../trait.FutureCancellable.html#method.cancel_with) the task
+/// is aborted even if the returned handle is never polled.
+///
+/// # Panics
+///Panics if called outside of a tokio runtime
+pub fn spawn_cancellable<F, S>(fut: F, signal: S) -> CancellableJoinHandle<F::Output, S::Output>
+where
+    F: Future + Send + 'static,
+    F::Output: Send + 'static,
+    S: Future + Send + 'static,
+    S::Output: Send + 'static,
+{
+    let done = CancellationToken::new();
+
+    let guard = DoneGuard(done.clone());
+    let task = tokio::spawn(async move {
+        let _guard = guard;
+        fut.await
+    });
+
+    let abort = task.abort_handle();
+    let watcher = tokio::spawn(async move {
+        let result = signal.cancel_with(done.cancelled()).await;
+        if !result.is_cancelled() {
+            abort.abort();
+        }
+        result
+    });
+
+    CancellableJoinHandle { task, watcher }
+}
+
+///Spawn `fut` like [`spawn_cancellable`](fn.spawn_cancellable.html), instrumented with `span`
+///
+///The task is polled inside `span` and the signal inside a child span,
+/// so both show up under the same resource in `tokio-console` and other `tracing` layers.
+///When the signal aborts the task a `DEBUG` event is emitted in `span`,
+/// with the type of the signal that cancelled it.
+///
+/// # Example
+/// ```rust
+/// # #[tokio::main]
+/// # async fn main() {
+///  use kyansel::tokio::{spawn_cancellable_instrumented, TaskResult};
+/// #  use futures::future::{pending, ready};
+///
+///  let span = tracing::info_span!("sync job", id = 7);
+///  let handle = spawn_cancellable_instrumented(pending::<()>(), ready("shutdown"), span);
+///
+///  //emits `cancelled by signal` with `signal="futures_util::future::ready::Ready<&str>"`
+///  assert!(matches!(handle.await, TaskResult::Cancelled("shutdown")));
+/// # }
+/// ```
+///
+/// # Panics
+///Panics if called outside of a tokio runtime
+#[cfg(feature = "tracing")]
+pub fn spawn_cancellable_instrumented<F, S>(
+    fut: F,
+    signal: S,
+    span: tracing::Span,
+) -> CancellableJoinHandle<F::Output, S::Output>
+where
+    F: Future + Send + 'static,
+    F::Output: Send + 'static,
+    S: Future + Send + 'static,
+    S::Output: Send + 'static,
+{
+    use tracing::Instrument;
+
+    let signal_type = core::any::type_name::<S>();
+    let signal_span =
+        tracing::debug_span!(parent: &span, "cancellation signal", signal = signal_type);
+
+    let cancelled_in = span.clone();
+    let signal = async move {
+        let s = signal.await;
+        tracing::debug!(parent: &cancelled_in, signal = signal_type, "cancelled by signal");
+        s
+    };
+
+    spawn_cancellable(fut.instrument(span), signal.instrument(signal_span))
+}
+
+///Set of tasks aborted together when a shared stopper completes
+///
+///Wraps a `JoinSet`, yielding [`TaskResult`](enum.TaskResult.html)s so tasks aborted
+/// because of the stopper can be told apart from tasks aborted any other way, or that panicked.
+///
+///The stopper is driven by a task of its own, so the tasks are aborted when it completes
+/// even if nobody is joining them
+///
+/// # Example
+/// ```rust
+/// # #[tokio::main]
+/// # async fn main() {
+///  use kyansel::{token::CancellationToken, tokio::{CancellableJoinSet, TaskResult}};
+/// #  use futures::future::pending;
+///
+///  let shutdown = CancellationToken::new();
+///  let mut set = CancellableJoinSet::new(shutdown.cancelled());
+///
+///  set.spawn(async { 42 });
+///  set.spawn(pending());
+///
+///  assert!(matches!(set.join_next().await, Some(TaskResult::Finished(42))));
+///
+///  shutdown.cancel();
+///  assert!(matches!(set.join_next().await, Some(TaskResult::Cancelled(()))));
+///  assert!(set.join_next().await.is_none());
+/// # }
+/// ```
+pub struct CancellableJoinSet<T, S>
+where
+    S: Future,
+{
+    set: JoinSet<CancellableResult<T, ()>>,
+    //cancelled by the watcher once the stopper completed, with its output stored
+    token: CancellationToken,
+    cancelled: Arc<Mutex<Option<S::Output>>>,
+    watcher: AbortHandle,
+}
+
+impl<T, S> CancellableJoinSet<T, S>
+where
+    T: Send + 'static,
+    S: Future + Send + 'static,
+    S::Output: Clone + Send + 'static,
+{
+    ///Create an empty set, whose tasks will be aborted when `stopper` completes
+    ///
+    /// # Example
+    /// ```rust
+    /// # #[tokio::main]
+    /// # async fn main() {
+    ///  use kyansel::{token::CancellationToken, tokio::CancellableJoinSet};
+    /// #  use futures::future::pending;
+    ///  use std::{sync::Arc, time::Duration};
+    ///
+    ///  let shutdown = CancellationToken::new();
+    ///  let mut set = CancellableJoinSet::<(), _>::new(shutdown.cancelled());
+    ///
+    ///  let connection = Arc::new(());
+    ///  let held = connection.clone();
+    ///  set.spawn(async move {
+    ///      let _held = held;
+    ///      pending().await
+    ///  });
+    ///
+    ///  //the task is aborted without joining it
+    ///  shutdown.cancel();
+    ///  while Arc::strong_count(&connection) > 1 {
+    ///      tokio::time::sleep(Duration::from_millis(1)).await;
+    ///  }
+    /// #  assert!(set.is_cancelled());
+    /// # }
+    /// ```
+    ///
+    /// # Panics
+    ///Panics if called outside of a tokio runtime
+    pub fn new(stopper: S) -> Self {
+        let token = CancellationToken::new();
+        let cancelled = Arc::new(Mutex::new(None));
+
+        let watcher = {
+            let (token, cancelled) = (token.clone(), cancelled.clone());
+            tokio::spawn(async move {
+                let s = stopper.await;
+                *cancelled.lock().unwrap() = Some(s);
+                token.cancel();
+            })
+            .abort_handle()
+        };
+
+        Self { set: JoinSet::new(), token, cancelled, watcher }
+    }
+
+    ///Spawn `fut` on the current tokio runtime as part of the set
+    ///
+    ///If the stopper already completed the task is aborted right away
+    ///
+    /// # Panics
+    ///Panics if called outside of a tokio runtime
+    pub fn spawn<F>(&mut self, fut: F) -> AbortHandle
+    where
+        F: Future<Output = T> + Send + 'static,
+    {
+        self.set.spawn(fut.cancel_with(self.token.cancelled()))
+    }
+
+    ///Check if the stopper completed
+    pub fn is_cancelled(&self) -> bool { self.token.is_cancelled() }
+
+    ///Number of tasks in the set, including completed ones not yet joined
+    pub fn len(&self) -> usize { self.set.len() }
+
+    ///Check if the set has no tasks
+    pub fn is_empty(&self) -> bool { self.set.is_empty() }
+
+    ///Abort all the tasks without waiting for the stopper
+    ///
+    ///The aborted tasks will be joined as
+    /// [`TaskResult::Aborted`](enum.TaskResult.html#variant.Aborted)
+    pub fn abort_all(&mut self) { self.set.abort_all() }
+
+    ///Wait for the next task to complete, or `None` if the set is empty
+    pub async fn join_next(&mut self) -> Option<TaskResult<T, S::Output>> {
+        core::future::poll_fn(|cx| self.poll_join_next(cx)).await
+    }
+
+    ///Poll for the next task to complete, or `None` if the set is empty
+    pub fn poll_join_next(&mut self, cx: &mut Context) -> Poll<Option<TaskResult<T, S::Output>>> {
+        self.set.poll_join_next(cx).map(|next| next.map(|result| self.disambiguate(result)))
+    }
+
+    fn disambiguate(
+        &self,
+        result: Result<CancellableResult<T, ()>, JoinError>,
+    ) -> TaskResult<T, S::Output> {
+        match result {
+            Ok(CancellableResult::Finished(t)) => TaskResult::Finished(t),
+            Ok(CancellableResult::Cancelled(())) => {
+                //the output is stored before the token is cancelled
+                let s = self.cancelled.lock().unwrap().clone();
+                TaskResult::Cancelled(s.expect("token cancelled before the stopper completed"))
+            }
+            Err(err) if err.is_panic() => TaskResult::Panicked(err.into_panic()),
+            Err(_) => TaskResult::Aborted,
+        }
+    }
+}
+
+impl<T, S> Drop for CancellableJoinSet<T, S>
+where
+    S: Future,
+{
+    fn drop(&mut self) { self.watcher.abort() }
+}
+
+impl<T, S> core::fmt::Debug for CancellableJoinSet<T, S>
+where
+    S: Future,
+{
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        f.debug_struct("CancellableJoinSet")
+            .field("len", &self.set.len())
+            .field("cancelled", &self.token.is_cancelled())
+            .finish()
+    }
+}
+
+///Blocking task still running after being cancelled
+///
+///Returned by [`BlockingJoinHandle`](struct.BlockingJoinHandle.html) on cancellation,
+/// dropping it detaches the thread, which keeps running until it notices the cancellation
+#[derive(Debug)]
+#[must_use = "dropping the straggler detaches the thread"]
+pub struct Straggler<T> {
+    handle: JoinHandle<T>,
+}
+
+impl<T> Straggler<T> {
+    ///Wait for the thread to complete
+    pub async fn join(self) -> Result<T, JoinError> { self.handle.await }
+
+    ///Wait for the thread to complete, unless `stopper` completes first
+    ///
+    ///Pass a timer to join with a timeout, the thread is detached if the stopper wins
+    pub async fn join_with<S>(
+        self,
+        stopper: S,
+    ) -> CancellableResult<Result<T, JoinError>, S::Output>
+    where
+        S: Future,
+    {
+        self.handle.cancel_with(stopper).await
+    }
+
+    ///Check if the thread has completed
+    pub fn is_finished(&self) -> bool { self.handle.is_finished() }
+}
+
+pin_project! {
+    ///Handle to a blocking task spawned with
+    /// [`spawn_blocking_cancellable`](fn.spawn_blocking_cancellable.html)
+    ///
+    ///Dropping the handle detaches the thread, the signal will still cancel the token
+    #[must_use = "dropping the handle detaches the thread"]
+    pub struct BlockingJoinHandle<T, S> {
+        token: CancellationToken,
+        task: Option<JoinHandle<T>>,
+        #[pin]
+        signal: S,
+    }
+
+    impl<T, S> PinnedDrop for BlockingJoinHandle<T, S> {
+        fn drop(this: Pin<&mut Self>) {
+            let this = this.project();
+
+            //nobody will poll the signal anymore
+            if this.task.as_ref().is_some_and(|task| !task.is_finished()) {
+                this.token.cancel();
+            }
+        }
+    }
+}
+
+impl<T, S> BlockingJoinHandle<T, S> {
+    ///Cancel the token passed to the closure right away, without waiting for the signal
+    pub fn cancel(&self) { self.token.cancel() }
+}
+
+impl<T, S> Future for BlockingJoinHandle<T, S>
+where
+    S: Future,
+{
+    type Output = TaskResult<T, (S::Output, Straggler<T>)>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
+        let this = self.project();
+        let task = this.task.as_mut().expect("BlockingJoinHandle polled after completion");
+
+        //always poll the task first
+        match Pin::new(task).poll(cx) {
+            Poll::Ready(Ok(t)) => return Poll::Ready(TaskResult::Finished(t)),
+            Poll::Ready(Err(err)) if err.is_panic() => {
+                return Poll::Ready(TaskResult::Panicked(err.into_panic()))
+            }
+            Poll::Ready(Err(_)) => return Poll::Ready(TaskResult::Aborted),
+            Poll::Pending => {}
+        }
+
+        match this.signal.poll(cx) {
+            Poll::Ready(s) => {
+                this.token.cancel();
+                let handle = this.task.take().expect("BlockingJoinHandle polled after completion");
+                Poll::Ready(TaskResult::Cancelled((s, Straggler { handle })))
+            }
+            Poll::Pending => Poll::Pending,
+        }
+    }
+}
+
+impl<T, S> core::fmt::Debug for BlockingJoinHandle<T, S> {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        f.debug_struct("BlockingJoinHandle").field("token", &self.token).finish()
+    }
+}
+
+///Run `f` on tokio's blocking thread pool, cancelling the token it's given when `signal`
+/// completes
+///
+///The closure must check
+/// [`is_cancelled`](../token/struct.CancellationToken.html#method.is_cancelled) periodically
+/// and return early: the handle resolves as cancelled as soon as the signal completes,
+/// handing back a [`Straggler`](struct.Straggler.html) for the thread that is still running,
+/// to be joined or detached.
+///
+///Dropping the returned handle cancels the token as well
+///
+/// # Panics
+///Panics if called outside of a tokio runtime
+///
+/// # Example
+/// ```rust
+/// # #[tokio::main]
+/// # async fn main() {
+///  use kyansel::tokio::{spawn_blocking_cancellable, TaskResult};
+/// #  use futures::future::ready;
+///
+///  let handle = spawn_blocking_cancellable(
+///      |token| {
+///          let mut iterations = 0u64;
+///          while !token.is_cancelled() {
+///              iterations += 1;
+///          }
+///          iterations
+///      },
+///      ready("stop"),
+///  );
+///
+///  match handle.await {
+///      TaskResult::Cancelled((reason, straggler)) => {
+///          assert_eq!(reason, "stop");
+///          //the thread notices the cancellation and returns
+///          assert!(straggler.join().await.is_ok());
+///      }
+///      _ => unreachable!(),
+///  }
+/// # }
+/// ```
+pub fn spawn_blocking_cancellable<F, T, S>(f: F, signal: S) -> BlockingJoinHandle<T, S>
+where
+    F: FnOnce(CancellationToken) -> T + Send + 'static,
+    T: Send + 'static,
+    S: Future,
+{
+    let token = CancellationToken::new();
+
+    let flag = token.clone();
+    let task = tokio::task::spawn_blocking(move || f(flag));
+
+    BlockingJoinHandle { token, task: Some(task), signal }
+}
+
+///[`Timer`](../timer/trait.Timer.html) backed by `tokio::time`
+///
+///Deadlines are `tokio::time::Instant`s, so paused time is respected:
+/// with `tokio::time::pause` and `advance` deadlines can be tested without waiting for them.
+///
+///The returned futures panic if polled outside of a tokio runtime with the time driver enabled
+///
+/// # Example
+/// ```rust
+/// # #[tokio::main(flavor = "current_thread", start_paused = true)]
+/// # async fn main() {
+///  use kyansel::{timer::cancel_at, tokio::TokioTimer};
+/// #  use futures::future::pending;
+/// #  use std::time::Duration;
+///  use tokio::time::{advance, Instant};
+///
+///  let deadline = Instant::now() + Duration::from_secs(3600);
+///  let handle = tokio::spawn(cancel_at(pending::<()>(), &TokioTimer, deadline));
+///
+///  //an hour passes in no time
+///  advance(Duration::from_secs(3600)).await;
+///
+///  assert!(handle.await.unwrap().is_cancelled());
+/// # }
+/// ```
+#[derive(Debug, Default, Clone, Copy)]
+pub struct TokioTimer;
+
+impl Timer for TokioTimer {
+    type Instant = Instant;
+    type Sleep = Sleep;
+
+    fn sleep(&self, duration: Duration) -> Self::Sleep { tokio::time::sleep(duration) }
+
+    fn sleep_until(&self, deadline: Self::Instant) -> Self::Sleep {
+        tokio::time::sleep_until(deadline)
+    }
+}
+
+///Error returned by [`timeout`](fn.timeout.html) when the deadline elapses,
+/// the counterpart of `tokio::time::error::Elapsed`
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct Elapsed(());
+
+impl core::fmt::Display for Elapsed {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        f.write_str("deadline has elapsed")
+    }
+}
+
+impl std::error::Error for Elapsed {}
+
+impl From<Elapsed> for std::io::Error {
+    fn from(_: Elapsed) -> Self { std::io::ErrorKind::TimedOut.into() }
+}
+
+impl From<tokio::time::error::Elapsed> for Elapsed {
+    fn from(_: tokio::time::error::Elapsed) -> Self { Self(()) }
+}
+
+pin_project! {
+    ///Future returned by [`timeout`](fn.timeout.html) and [`timeout_at`](fn.timeout_at.html)
+    #[must_use = "futures do nothing unless you `.await` or poll them"]
+    #[derive(Debug)]
+    pub struct Timeout<F>
+    where
+        F: Future,
+    {
+        #[pin]
+        cancellable: Cancellable<F, Sleep>,
+    }
+}
+
+impl<F> Timeout<F>
+where
+    F: Future,
+{
+    ///Retrieve a reference to the inner future
+    pub fn get_ref(&self) -> &F { &self.cancellable.inner }
+
+    ///Retrieve a mutable reference to the inner future
+    pub fn get_mut(&mut self) -> &mut F { &mut self.cancellable.inner }
+
+    ///Retrieve the inner future, dropping the deadline
+    pub fn into_inner(self) -> F { self.cancellable.inner }
+}
+
+impl<F> Future for Timeout<F>
+where
+    F: Future,
+{
+    type Output = Result<F::Output, Elapsed>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
+        match self.project().cancellable.poll(cx) {
+            Poll::Ready(result) => Poll::Ready(result.ok_or(Elapsed(()))),
+            Poll::Pending => Poll::Pending,
+        }
+    }
+}
+
+///Drop-in replacement for `tokio::time::timeout`, failing with
+/// [`Elapsed`](struct.Elapsed.html) if `fut` doesn't complete within `duration`
+///
+///Like tokio's, the inner future is polled first, so it can still complete when the deadline
+/// has already elapsed
+///
+/// # Example
+/// ```rust
+/// # #[tokio::main(flavor = "current_thread", start_paused = true)]
+/// # async fn main() -> std::io::Result<()> {
+///  use kyansel::tokio::timeout;
+/// #  use futures::future::{pending, ready};
+///  use std::time::Duration;
+///
+///  assert_eq!(timeout(Duration::from_secs(1), ready(42)).await?, 42);
+///
+///  //converts into an `io::Error` like tokio's
+///  let elapsed = timeout(Duration::from_secs(1), pending::<()>()).await.unwrap_err();
+///  assert_eq!(std::io::Error::from(elapsed).kind(), std::io::ErrorKind::TimedOut);
+/// # Ok(())
+/// # }
+/// ```
+pub fn timeout<F>(duration: Duration, fut: F) -> Timeout<F::IntoFuture>
+where
+    F: core::future::IntoFuture,
+{
+    Timeout { cancellable: fut.into_future().cancel_with(tokio::time::sleep(duration)) }
+}
+
+///Drop-in replacement for `tokio::time::timeout_at`, failing with
+/// [`Elapsed`](struct.Elapsed.html) if `fut` doesn't complete by `deadline`
+pub fn timeout_at<F>(deadline: Instant, fut: F) -> Timeout<F::IntoFuture>
+where
+    F: core::future::IntoFuture,
+{
+    Timeout { cancellable: fut.into_future().cancel_with(tokio::time::sleep_until(deadline)) }
+}
+
+///Output of a [`BroadcastStopper`](struct.BroadcastStopper.html)
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Broadcasted<T> {
+    ///A message matching the filter was received
+    Message(T),
+
+    ///The receiver fell behind and this many messages were dropped,
+    /// any of which might have matched
+    Lagged(u64),
+
+    ///All the senders were dropped
+    Closed,
+}
+
+type Filter<T> = Box<dyn FnMut(&T) -> bool + Send>;
+
+///Stopper firing on the next message of a `broadcast` channel matching a filter
+///
+///A lagging receiver can't tell if the messages it missed matched,
+/// so by default it fires with [`Broadcasted::Lagged`](enum.Broadcasted.html#variant.Lagged),
+/// unless [`ignore_lag`](struct.BroadcastStopper.html#method.ignore_lag) is set.
+///It also fires once all the senders are dropped
+///
+/// # Example
+/// ```rust
+/// # #[tokio::main]
+/// # async fn main() {
+///  use kyansel::{tokio::{BroadcastStopper, Broadcasted}, FutureCancellable};
+/// #  use futures::future::pending;
+///  use tokio::sync::broadcast;
+///
+///  #[derive(Debug, Clone, PartialEq)]
+///  enum Event { Reload, Shutdown }
+///
+///  let (tx, rx) = broadcast::channel(16);
+///  let stopper = BroadcastStopper::new(rx).filter(|event| *event == Event::Shutdown);
+///  let server = tokio::spawn(pending::<()>().cancel_with(stopper));
+///
+///  tx.send(Event::Reload).unwrap();
+///  tx.send(Event::Shutdown).unwrap();
+///
+///  let result = server.await.unwrap();
+///  assert_eq!(result.cancelled(), Some(Broadcasted::Message(Event::Shutdown)));
+/// # }
+/// ```
+#[must_use = "futures do nothing unless you `.await` or poll them"]
+pub struct BroadcastStopper<T> {
+    rx: Option<Receiver<T>>,
+    filter: Option<Filter<T>>,
+    ignore_lag: bool,
+    //boxed, the future of an async fn can't be named
+    recv: Option<Pin<Box<dyn Future<Output = Broadcasted<T>> + Send>>>,
+}
+
+impl<T> BroadcastStopper<T>
+where
+    T: Clone + Send + 'static,
+{
+    ///Create a stopper firing on the next message received by `rx`
+    pub fn new(rx: Receiver<T>) -> Self {
+        Self { rx: Some(rx), filter: None, ignore_lag: false, recv: None }
+    }
+
+    ///Only fire on the messages matching `filter`
+    pub fn filter<P>(mut self, filter: P) -> Self
+    where
+        P: FnMut(&T) -> bool + Send + 'static,
+    {
+        self.filter = Some(Box::new(filter));
+        self
+    }
+
+    ///Keep receiving when the receiver lags, instead of firing
+    pub fn ignore_lag(mut self) -> Self {
+        self.ignore_lag = true;
+        self
+    }
+
+    async fn recv(
+        mut rx: Receiver<T>,
+        mut filter: Option<Filter<T>>,
+        ignore_lag: bool,
+    ) -> Broadcasted<T> {
+        loop {
+            match rx.recv().await {
+                Ok(msg) if filter.as_mut().is_none_or(|filter| filter(&msg)) => {
+                    return Broadcasted::Message(msg)
+                }
+                Ok(_) => {}
+                Err(RecvError::Lagged(_)) if ignore_lag => {}
+                Err(RecvError::Lagged(missed)) => return Broadcasted::Lagged(missed),
+                Err(RecvError::Closed) => return Broadcasted::Closed,
+            }
+        }
+    }
+}
+
+impl<T> Future for BroadcastStopper<T>
+where
+    T: Clone + Send + 'static,
+{
+    type Output = Broadcasted<T>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
+        let this = self.get_mut();
+
+        //the receiver is only moved into the future once it's polled, after it's configured
+        if let Some(rx) = this.rx.take() {
+            this.recv = Some(Box::pin(Self::recv(rx, this.filter.take(), this.ignore_lag)));
+        }
+
+        this.recv.as_mut().expect("BroadcastStopper polled after completion").as_mut().poll(cx)
+    }
+}
+
+impl<T> core::fmt::Debug for BroadcastStopper<T> {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        f.debug_struct("BroadcastStopper").field("ignore_lag", &self.ignore_lag).finish()
+    }
+}
+
+impl<F, S> Cancellable<F, S>
+where
+    F: Future,
+    S: Future,
+{
+    ///Consume tokio's cooperative budget when polled,
+    /// yielding to the scheduler instead of polling once the task spent it
+    ///
+    ///The budget is only consumed when the future completes, either way,
+    /// so a task waiting on it isn't penalized
+    ///
+    /// # Example
+    /// ```rust
+    /// # #[tokio::main]
+    /// # async fn main() {
+    ///  use kyansel::{token::CancellationToken, FutureCancellable};
+    /// #  use futures::future::ready;
+    ///  use tokio::task::coop::has_budget_remaining;
+    ///
+    ///  let token = CancellationToken::new();
+    ///
+    ///  //always ready, this would never yield to other tasks
+    ///  for _ in 0..128 {
+    ///      ready(()).cancel_with(token.cancelled()).cooperative().await;
+    ///  }
+    ///
+    ///  //the next one yields first
+    ///  assert!(!has_budget_remaining());
+    /// # }
+    /// ```
+    pub fn cooperative(self) -> Coop<Self> { cooperative(self) }
+}
+
+impl<F, S> FastPath<F, S>
+where
+    F: Future,
+    S: CancelSignal,
+{
+    ///Consume tokio's cooperative budget when polled, like
+    /// [`Cancellable::cooperative`](../struct.Cancellable.html#method.cooperative)
+    pub fn cooperative(self) -> Coop<Self> { cooperative(self) }
+}
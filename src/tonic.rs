@@ -0,0 +1,139 @@
+//! Deadlines for tonic gRPC servers
+//!
+//!gRPC clients send their deadline in the `grpc-timeout` header.
+//![`honor_deadline`](fn.honor_deadline.html) reads it from the request metadata,
+//! and races the handler against both the deadline and a stopper, like a shutdown token,
+//! failing with `Status::deadline_exceeded` or `Status::cancelled` respectively.
+//!
+//! # Example
+//! ```rust
+//! # use futures::{executor::block_on, future::pending};
+//!  use kyansel::{timer::ManualTimer, tonic::honor_deadline};
+//!  use tonic::{metadata::MetadataMap, Code, Status};
+//!
+//!  let mut metadata = MetadataMap::new();
+//!  metadata.insert("grpc-timeout", "100m".parse().unwrap());
+//!
+//!  let timer = ManualTimer::new();
+//!  let handler = pending::<Result<(), Status>>();
+//!  let response = honor_deadline(&metadata, handler, pending::<()>(), &timer);
+//!
+//!  timer.advance(std::time::Duration::from_millis(100));
+//!  assert_eq!(block_on(response).unwrap_err().code(), Code::DeadlineExceeded);
+//! ```
+
+use super::timer::{timeout_or_cancel, TimeoutOrCancel, TimeoutOrCancelled, Timer};
+use core::{
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll},
+    time::Duration,
+};
+use pin_project_lite::pin_project;
+use tonic::{metadata::MetadataMap, Status};
+
+///Read the deadline sent by the client in the `grpc-timeout` header, if any
+///
+///Malformed values are ignored, like tonic itself does
+pub fn grpc_timeout(metadata: &MetadataMap) -> Option<Duration> {
+    let value = metadata.get("grpc-timeout")?.to_str().ok()?;
+    if value.len() < 2 || value.len() > 9 {
+        return None;
+    }
+
+    let (amount, unit) = value.split_at(value.len() - 1);
+    let amount: u64 = amount.parse().ok()?;
+
+    Some(match unit {
+        "H" => Duration::from_secs(amount * 60 * 60),
+        "M" => Duration::from_secs(amount * 60),
+        "S" => Duration::from_secs(amount),
+        "m" => Duration::from_millis(amount),
+        "u" => Duration::from_micros(amount),
+        "n" => Duration::from_nanos(amount),
+        _ => return None,
+    })
+}
+
+///Map the result of [`TimeoutOrCancel`](../timer/struct.TimeoutOrCancel.html)
+/// around a handler into the response of the handler
+///
+///A timeout becomes `Status::deadline_exceeded` and a cancellation `Status::cancelled`
+pub fn into_status<T, S>(result: TimeoutOrCancelled<Result<T, Status>, S>) -> Result<T, Status> {
+    match result {
+        TimeoutOrCancelled::Finished(result) => result,
+        TimeoutOrCancelled::TimedOut => Err(Status::deadline_exceeded("deadline exceeded")),
+        TimeoutOrCancelled::Cancelled(_) => Err(Status::cancelled("request cancelled")),
+    }
+}
+
+pin_project! {
+    //the client deadline, if it sent one
+    #[derive(Debug)]
+    struct ClientDeadline<D> {
+        #[pin]
+        deadline: Option<D>,
+    }
+}
+
+impl<D> Future for ClientDeadline<D>
+where
+    D: Future<Output = ()>,
+{
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
+        match self.project().deadline.as_pin_mut() {
+            Some(deadline) => deadline.poll(cx),
+            None => Poll::Pending,
+        }
+    }
+}
+
+pin_project! {
+    ///Future for the [`honor_deadline`](fn.honor_deadline.html) combinator
+    #[must_use = "futures do nothing unless you `.await` or poll them"]
+    pub struct HonorDeadline<F, S, D> {
+        #[pin]
+        inner: TimeoutOrCancel<F, S, ClientDeadline<D>>,
+    }
+}
+
+impl<F, S, D, T> Future for HonorDeadline<F, S, D>
+where
+    F: Future<Output = Result<T, Status>>,
+    S: Future,
+    D: Future<Output = ()>,
+{
+    type Output = Result<T, Status>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
+        self.project().inner.poll(cx).map(into_status)
+    }
+}
+
+impl<F, S, D> core::fmt::Debug for HonorDeadline<F, S, D> {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        f.debug_struct("HonorDeadline").finish_non_exhaustive()
+    }
+}
+
+///Run the handler `fut` until it completes, the deadline in `metadata` passes on `timer`,
+/// or `stopper` completes
+///
+///Without a `grpc-timeout` header only `stopper` can cancel the handler
+pub fn honor_deadline<F, S, T, R>(
+    metadata: &MetadataMap,
+    fut: F,
+    stopper: S,
+    timer: &T,
+) -> HonorDeadline<F, S, T::Sleep>
+where
+    F: Future<Output = Result<R, Status>>,
+    S: Future,
+    T: Timer + ?Sized,
+{
+    let deadline = grpc_timeout(metadata).map(|timeout| timer.sleep(timeout));
+
+    HonorDeadline { inner: timeout_or_cancel(fut, stopper, ClientDeadline { deadline }) }
+}
@@ -0,0 +1,170 @@
+//! Tower middleware cancelling requests
+//!
+//![`CancelLayer`](struct.CancelLayer.html) wraps a `Service` so every call races against
+//! a cancellation signal, either shared by all the requests, like a
+//! [`CancellationToken`](../token/struct.CancellationToken.html) for shutdown,
+//! or created for each request, like a per-client disconnect notification.
+//!When the signal wins, the call fails with the error built by the configured function.
+//!
+//!This makes kyansel usable in hyper, axum and tonic middleware stacks
+//!
+//! # Example
+//! ```rust
+//! # #[tokio::main]
+//! # async fn main() {
+//!  use kyansel::{token::CancellationToken, tower::CancelLayer};
+//!  use tower::{service_fn, Layer, ServiceExt};
+//! #  use futures::future::pending;
+//!
+//!  let shutdown = CancellationToken::new();
+//!  let layer = CancelLayer::new(shutdown.clone(), |()| "shutting down");
+//!
+//!  let service = layer.layer(service_fn(|_req: ()| pending::<Result<(), &str>>()));
+//!
+//!  shutdown.cancel();
+//!  assert_eq!(service.oneshot(()).await, Err("shutting down"));
+//! # }
+//! ```
+
+use super::{token::CancellationToken, Cancellable, CancellableResult, FutureCancellable};
+use core::{
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll},
+};
+use pin_project_lite::pin_project;
+use tower_layer::Layer;
+use tower_service::Service;
+
+///Source of the cancellation signal of each request
+///
+///Implemented for [`CancellationToken`](../token/struct.CancellationToken.html),
+/// cancelling all the requests at once, and for closures creating a signal from the request
+pub trait MakeSignal<Req> {
+    ///Signal cancelling the request
+    type Signal: Future;
+
+    ///Create the signal cancelling `req`
+    fn make_signal(&mut self, req: &Req) -> Self::Signal;
+}
+
+impl<Req> MakeSignal<Req> for CancellationToken {
+    type Signal = super::token::WaitForCancellation;
+
+    fn make_signal(&mut self, _: &Req) -> Self::Signal { self.cancelled() }
+}
+
+impl<Req, Fun, S> MakeSignal<Req> for Fun
+where
+    Fun: FnMut(&Req) -> S,
+    S: Future,
+{
+    type Signal = S;
+
+    fn make_signal(&mut self, req: &Req) -> Self::Signal { self(req) }
+}
+
+///Layer applying [`Cancel`](struct.Cancel.html) to services
+#[derive(Debug, Clone)]
+pub struct CancelLayer<M, C> {
+    make_signal: M,
+    on_cancel: C,
+}
+
+impl<M, C> CancelLayer<M, C> {
+    ///Cancel the calls with the signals created by `make_signal`,
+    /// failing them with the error returned by `on_cancel`
+    pub fn new(make_signal: M, on_cancel: C) -> Self { Self { make_signal, on_cancel } }
+}
+
+impl<Svc, M, C> Layer<Svc> for CancelLayer<M, C>
+where
+    M: Clone,
+    C: Clone,
+{
+    type Service = Cancel<Svc, M, C>;
+
+    fn layer(&self, inner: Svc) -> Self::Service {
+        Cancel {
+            inner,
+            make_signal: self.make_signal.clone(),
+            on_cancel: self.on_cancel.clone(),
+        }
+    }
+}
+
+///Service cancelling the calls of the inner service when their signal completes
+///
+///Created by [`CancelLayer`](struct.CancelLayer.html)
+#[derive(Debug, Clone)]
+pub struct Cancel<Svc, M, C> {
+    inner: Svc,
+    make_signal: M,
+    on_cancel: C,
+}
+
+impl<Svc, M, C> Cancel<Svc, M, C> {
+    ///Retrieve the inner service
+    pub fn into_inner(self) -> Svc { self.inner }
+}
+
+impl<Svc, Req, M, C> Service<Req> for Cancel<Svc, M, C>
+where
+    Svc: Service<Req>,
+    M: MakeSignal<Req>,
+    C: FnOnce(<M::Signal as Future>::Output) -> Svc::Error + Clone,
+{
+    type Error = Svc::Error;
+    type Future = CancelFuture<Svc::Future, M::Signal, C>;
+    type Response = Svc::Response;
+
+    fn poll_ready(&mut self, cx: &mut Context) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: Req) -> Self::Future {
+        let signal = self.make_signal.make_signal(&req);
+
+        CancelFuture {
+            cancellable: self.inner.call(req).cancel_with(signal),
+            on_cancel: Some(self.on_cancel.clone()),
+        }
+    }
+}
+
+pin_project! {
+    ///Future returned by [`Cancel`](struct.Cancel.html)
+    #[derive(Debug)]
+    #[must_use = "futures do nothing unless you `.await` or poll them"]
+    pub struct CancelFuture<F, S, C>
+    where
+        F: Future,
+        S: Future,
+    {
+        #[pin]
+        cancellable: Cancellable<F, S>,
+        on_cancel: Option<C>,
+    }
+}
+
+impl<F, S, C, T, E> Future for CancelFuture<F, S, C>
+where
+    F: Future<Output = Result<T, E>>,
+    S: Future,
+    C: FnOnce(S::Output) -> E,
+{
+    type Output = Result<T, E>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
+        let this = self.project();
+
+        match this.cancellable.poll(cx) {
+            Poll::Ready(CancellableResult::Finished(result)) => Poll::Ready(result),
+            Poll::Ready(CancellableResult::Cancelled(s)) => {
+                let on_cancel = this.on_cancel.take().expect("polled after completion");
+                Poll::Ready(Err(on_cancel(s)))
+            }
+            Poll::Pending => Poll::Pending,
+        }
+    }
+}
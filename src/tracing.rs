@@ -0,0 +1,102 @@
+//! Observability with `tracing`
+//!
+//![`instrument_cancellable`](trait.CancellableInstrumentExt.html#method.instrument_cancellable)
+//! polls a cancellable future inside a span, and emits an event when it completes,
+//! telling whether it finished or was cancelled, why, and how long it ran.
+//!
+//!It works with any future resolving to a [`CancellableResult`](../enum.CancellableResult.html),
+//! like [`Cancellable`](../struct.Cancellable.html) or
+//! [`Grace`](../grace/struct.Grace.html)
+//!
+//! # Example
+//! ```rust
+//! # use futures::{executor::block_on, future::{pending, ready}};
+//!  use kyansel::{tracing::CancellableInstrumentExt, FutureCancellable};
+//!
+//!  let request = pending::<()>()
+//!      .cancel_with(ready("client went away"))
+//!      .instrument_cancellable(tracing::info_span!("request", id = 42));
+//!
+//!  //emits a `cancelled` event with `reason="client went away"` in the `request` span
+//!  assert!(block_on(request).is_cancelled());
+//! ```
+
+use super::CancellableResult;
+use core::{
+    fmt::Debug,
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll},
+};
+use pin_project_lite::pin_project;
+use std::time::Instant;
+use tracing::Span;
+
+pin_project! {
+    ///Future for the
+    /// [`instrument_cancellable`](trait.CancellableInstrumentExt.html#method.instrument_cancellable)
+    /// combinator
+    #[must_use = "futures do nothing unless you `.await` or poll them"]
+    pub struct InstrumentedCancellable<Fut> {
+        #[pin]
+        inner: Fut,
+        span: Span,
+        started: Option<Instant>,
+    }
+}
+
+impl<Fut, T, S> Future for InstrumentedCancellable<Fut>
+where
+    Fut: Future<Output = CancellableResult<T, S>>,
+    S: Debug,
+{
+    type Output = CancellableResult<T, S>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
+        let this = self.project();
+        let _entered = this.span.enter();
+        let started = *this.started.get_or_insert_with(Instant::now);
+
+        let result = match this.inner.poll(cx) {
+            Poll::Ready(result) => result,
+            Poll::Pending => return Poll::Pending,
+        };
+
+        let elapsed = started.elapsed();
+        match &result {
+            CancellableResult::Finished(_) => tracing::debug!(?elapsed, "finished"),
+            CancellableResult::Cancelled(reason) => tracing::debug!(?reason, ?elapsed, "cancelled"),
+        }
+
+        Poll::Ready(result)
+    }
+}
+
+impl<Fut> core::fmt::Debug for InstrumentedCancellable<Fut> {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        f.debug_struct("InstrumentedCancellable").field("span", &self.span).finish()
+    }
+}
+
+///An extension trait for cancellable futures that provides the
+/// [`InstrumentedCancellable`](struct.InstrumentedCancellable.html) combinator
+///
+///All futures resolving to a [`CancellableResult`](../enum.CancellableResult.html)
+/// already implement `CancellableInstrumentExt`
+pub trait CancellableInstrumentExt<T, S>: Future<Output = CancellableResult<T, S>> {
+    ///Poll this future inside `span`, emitting a `DEBUG` event when it completes
+    ///
+    ///The event tells whether the future finished or was cancelled, with the reason
+    /// of the cancellation, and the time elapsed since the first poll
+    fn instrument_cancellable(self, span: Span) -> InstrumentedCancellable<Self>
+    where
+        Self: Sized,
+    {
+        InstrumentedCancellable { inner: self, span, started: None }
+    }
+}
+
+impl<Fut, T, S> CancellableInstrumentExt<T, S> for Fut where
+    Fut: Future<Output = CancellableResult<T, S>>
+{
+}
@@ -0,0 +1,43 @@
+use core::{
+    sync::atomic::{AtomicBool, Ordering},
+    task::Waker,
+};
+use std::{
+    sync::{Arc, Mutex},
+    task::Wake,
+};
+
+//waker recording that it was woken, forwarding the wakeup to the task
+#[derive(Debug, Default)]
+pub struct WakeFlag {
+    woken: AtomicBool,
+    waker: Mutex<Option<Waker>>,
+}
+
+impl WakeFlag {
+    //store the waker of the task to forward wakeups to
+    pub fn register(&self, task: &Waker) {
+        let mut waker = self.waker.lock().unwrap();
+        match &*waker {
+            Some(waker) if waker.will_wake(task) => {}
+            _ => *waker = Some(task.clone()),
+        }
+    }
+
+    //check if we were woken since the last call
+    pub fn take(&self) -> bool { self.woken.swap(false, Ordering::AcqRel) }
+
+    //check if we were woken since the last call to `take`, without resetting it
+    pub fn is_woken(&self) -> bool { self.woken.load(Ordering::Acquire) }
+}
+
+impl Wake for WakeFlag {
+    fn wake(self: Arc<Self>) { self.wake_by_ref() }
+
+    fn wake_by_ref(self: &Arc<Self>) {
+        self.woken.store(true, Ordering::Release);
+        if let Some(waker) = &*self.waker.lock().unwrap() {
+            waker.wake_by_ref();
+        }
+    }
+}
@@ -0,0 +1,190 @@
+//! Interop with the browser's `AbortSignal` and `AbortController`
+//!
+//! [`AbortSignalStopper`](struct.AbortSignalStopper.html) turns an `AbortSignal` into a stopper,
+//! while [`abort_controller`](fn.abort_controller.html) creates an `AbortController` aborted
+//! when a [`CancellationToken`](../token/struct.CancellationToken.html) is cancelled,
+//! so `fetch` calls and kyansel-wrapped futures can share one cancellation source.
+//!
+//! There is no tokio timer on `wasm32-unknown-unknown`, [`cancel_after`](fn.cancel_after.html)
+//! and [`cancel_with_grace`](fn.cancel_with_grace.html) are backed by `setTimeout` instead.
+//!
+//! # Example
+//! ```rust,no_run
+//!  use kyansel::{token::CancellationToken, wasm::{abort_controller, AbortSignalStopper}};
+//!
+//!  let token = CancellationToken::new();
+//!  let (controller, bridge) = abort_controller(&token);
+//!  wasm_bindgen_futures::spawn_local(bridge);
+//!
+//!  //pass `controller.signal()` to `fetch`, cancelling the token aborts the request
+//!  let init = web_sys::RequestInit::new();
+//!  init.set_signal(Some(&controller.signal()));
+//!
+//!  //an abort coming from javascript can cancel the token too
+//!  # let signal_from_js = controller.signal();
+//!  wasm_bindgen_futures::spawn_local(async move {
+//!      token.cancel_on(AbortSignalStopper::new(signal_from_js)).await;
+//!  });
+//! ```
+
+use super::{
+    grace::{self, Grace},
+    token::{CancellationToken, WaitForCancellation},
+    Cancellable, FutureCancellable,
+};
+use core::{
+    cell::RefCell,
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll, Waker},
+};
+use gloo_timers::future::TimeoutFuture;
+use std::{rc::Rc, time::Duration};
+use wasm_bindgen::{closure::Closure, JsCast, JsValue};
+use web_sys::{AbortController, AbortSignal};
+
+#[derive(Default)]
+struct Shared {
+    aborted: bool,
+    waker: Option<Waker>,
+}
+
+///Stopper completing when an `AbortSignal` is aborted, with the abort reason
+#[must_use = "futures do nothing unless you `.await` or poll them"]
+pub struct AbortSignalStopper {
+    signal: AbortSignal,
+    shared: Rc<RefCell<Shared>>,
+    listener: Option<Closure<dyn FnMut()>>,
+}
+
+impl AbortSignalStopper {
+    ///Create a stopper completing when `signal` is aborted
+    pub fn new(signal: AbortSignal) -> Self {
+        Self { signal, shared: Rc::default(), listener: None }
+    }
+}
+
+impl Future for AbortSignalStopper {
+    type Output = JsValue;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
+        let this = self.get_mut();
+
+        if this.signal.aborted() || this.shared.borrow().aborted {
+            return Poll::Ready(this.signal.reason());
+        }
+
+        this.shared.borrow_mut().waker = Some(cx.waker().clone());
+
+        if this.listener.is_none() {
+            let shared = this.shared.clone();
+            let listener = Closure::<dyn FnMut()>::new(move || {
+                let mut shared = shared.borrow_mut();
+                shared.aborted = true;
+                if let Some(waker) = shared.waker.take() {
+                    waker.wake();
+                }
+            });
+
+            //this only fails if the listener is not a function
+            let _ = this
+                .signal
+                .add_event_listener_with_callback("abort", listener.as_ref().unchecked_ref());
+            this.listener = Some(listener);
+        }
+
+        Poll::Pending
+    }
+}
+
+impl Drop for AbortSignalStopper {
+    fn drop(&mut self) {
+        if let Some(listener) = &self.listener {
+            let _ = self
+                .signal
+                .remove_event_listener_with_callback("abort", listener.as_ref().unchecked_ref());
+        }
+    }
+}
+
+impl core::fmt::Debug for AbortSignalStopper {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        f.debug_struct("AbortSignalStopper").field("aborted", &self.signal.aborted()).finish()
+    }
+}
+
+///Future aborting an `AbortController` when a token is cancelled
+///
+///Returned by [`abort_controller`](fn.abort_controller.html), it must be spawned
+/// (with `wasm_bindgen_futures::spawn_local` for example) for the bridge to work
+#[must_use = "futures do nothing unless you `.await` or poll them"]
+pub struct AbortOnCancel {
+    controller: AbortController,
+    cancelled: WaitForCancellation,
+}
+
+impl Future for AbortOnCancel {
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
+        let this = self.get_mut();
+
+        match Pin::new(&mut this.cancelled).poll(cx) {
+            Poll::Ready(()) => {
+                this.controller.abort();
+                Poll::Ready(())
+            }
+            Poll::Pending => Poll::Pending,
+        }
+    }
+}
+
+impl core::fmt::Debug for AbortOnCancel {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        f.debug_struct("AbortOnCancel").field("cancelled", &self.cancelled).finish()
+    }
+}
+
+///Create an `AbortController` that will be aborted when `token` is cancelled
+///
+///The returned future performs the abort and must be spawned
+///
+/// # Panics
+///Panics if the `AbortController` can't be created, for example outside of a browser
+pub fn abort_controller(token: &CancellationToken) -> (AbortController, AbortOnCancel) {
+    let controller = AbortController::new().expect("unable to create an AbortController");
+    let bridge = AbortOnCancel { controller: controller.clone(), cancelled: token.cancelled() };
+
+    (controller, bridge)
+}
+
+//`setTimeout` fires right away for delays that don't fit in a signed 32 bit integer
+fn timeout(duration: Duration) -> TimeoutFuture {
+    TimeoutFuture::new(duration.as_millis().min(i32::MAX as u128) as u32)
+}
+
+///Cancel `fut` if it hasn't completed after `duration`, using a JS timer
+///
+///Durations are rounded down to milliseconds, and saturate at about 24 days
+pub fn cancel_after<F>(fut: F, duration: Duration) -> Cancellable<F, TimeoutFuture>
+where
+    F: Future,
+{
+    fut.cancel_with(timeout(duration))
+}
+
+///Cancel `fut` if it hasn't completed within `grace` after `stopper` completes,
+/// using a JS timer
+///
+///See [`grace::cancel_with_grace`](../grace/fn.cancel_with_grace.html)
+pub fn cancel_with_grace<F, S>(
+    fut: F,
+    stopper: S,
+    grace: Duration,
+) -> Grace<F, S, impl FnOnce() -> TimeoutFuture, TimeoutFuture>
+where
+    F: Future,
+    S: Future,
+{
+    grace::cancel_with_grace(fut, stopper, move || timeout(grace))
+}
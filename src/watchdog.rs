@@ -0,0 +1,155 @@
+//! Heartbeat-driven cancellation
+//!
+//![`watchdog`](fn.watchdog.html) wraps a future that gets cancelled
+//! unless [`Watchdog::feed`](struct.Watchdog.html#method.feed) is called within each interval,
+//! for example to stop a sync job when the leader stops pinging.
+//!
+//! # Example
+//! ```rust
+//! # use futures::{executor::block_on, future::pending, pin_mut, poll};
+//!  use kyansel::{timer::ManualTimer, watchdog::watchdog};
+//! #  use std::time::Duration;
+//!
+//! # block_on(async {
+//!  let timer = ManualTimer::new();
+//!  let (watchdog, job) = watchdog(pending::<()>(), timer.clone(), Duration::from_secs(5));
+//!  pin_mut!(job);
+//!  assert!(poll!(job.as_mut()).is_pending());
+//!
+//!  //a ping from the leader
+//!  timer.advance(Duration::from_secs(4));
+//!  watchdog.feed();
+//!  assert!(poll!(job.as_mut()).is_pending());
+//!
+//!  timer.advance(Duration::from_secs(4));
+//!  assert!(poll!(job.as_mut()).is_pending());
+//!
+//!  //the leader went silent
+//!  timer.advance(Duration::from_secs(1));
+//!  assert!(job.await.is_cancelled());
+//! # });
+//! ```
+
+use super::{timer::Timer, CancellableResult};
+use core::{
+    future::Future,
+    pin::Pin,
+    sync::atomic::{AtomicBool, Ordering},
+    task::{Context, Poll, Waker},
+    time::Duration,
+};
+use pin_project_lite::pin_project;
+use std::sync::{Arc, Mutex};
+
+#[derive(Debug, Default)]
+struct Shared {
+    fed: AtomicBool,
+    waker: Mutex<Option<Waker>>,
+}
+
+///Reason of the cancellation of a future whose watchdog wasn't fed in time
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct Starved {
+    ///The interval the watchdog had to be fed within
+    pub interval: Duration,
+}
+
+///Handle used to keep a [`Watched`](struct.Watched.html) future alive
+///
+///Created with [`watchdog`](fn.watchdog.html), it can be cloned and moved to other threads
+#[derive(Debug, Clone)]
+pub struct Watchdog {
+    shared: Arc<Shared>,
+}
+
+impl Watchdog {
+    ///Feed the watchdog, restarting the interval
+    ///
+    ///The watched future is woken, the interval restarts when it's polled next
+    pub fn feed(&self) {
+        self.shared.fed.store(true, Ordering::Release);
+        if let Some(waker) = self.shared.waker.lock().unwrap().take() {
+            waker.wake();
+        }
+    }
+}
+
+pin_project! {
+    ///Future cancelled unless its [`Watchdog`](struct.Watchdog.html) is fed within each interval
+    #[must_use = "futures do nothing unless you `.await` or poll them"]
+    pub struct Watched<F, T>
+    where
+        T: Timer,
+    {
+        #[pin]
+        inner: F,
+        timer: T,
+        interval: Duration,
+        #[pin]
+        sleep: Option<T::Sleep>,
+        shared: Arc<Shared>,
+    }
+}
+
+impl<F, T> Future for Watched<F, T>
+where
+    F: Future,
+    T: Timer,
+{
+    type Output = CancellableResult<F::Output, Starved>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
+        let mut this = self.project();
+
+        //always poll inner future first
+        if let Poll::Ready(t) = this.inner.poll(cx) {
+            return Poll::Ready(CancellableResult::Finished(t));
+        }
+
+        *this.shared.waker.lock().unwrap() = Some(cx.waker().clone());
+
+        if this.shared.fed.swap(false, Ordering::AcqRel) || this.sleep.is_none() {
+            this.sleep.set(Some(this.timer.sleep(*this.interval)));
+        }
+
+        match this.sleep.as_pin_mut().map(|sleep| sleep.poll(cx)) {
+            //fed right before the interval ended
+            Some(Poll::Ready(())) if this.shared.fed.load(Ordering::Acquire) => {
+                cx.waker().wake_by_ref();
+                Poll::Pending
+            }
+            Some(Poll::Ready(())) => {
+                Poll::Ready(CancellableResult::Cancelled(Starved { interval: *this.interval }))
+            }
+            _ => Poll::Pending,
+        }
+    }
+}
+
+impl<F, T> core::fmt::Debug for Watched<F, T>
+where
+    T: Timer + core::fmt::Debug,
+{
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        f.debug_struct("Watched")
+            .field("timer", &self.timer)
+            .field("interval", &self.interval)
+            .finish()
+    }
+}
+
+///Wrap `fut` so it's cancelled unless the returned [`Watchdog`](struct.Watchdog.html)
+/// is fed within each `interval`, measured with `timer`
+///
+///The first interval starts when the future is first polled
+pub fn watchdog<F, T>(fut: F, timer: T, interval: Duration) -> (Watchdog, Watched<F, T>)
+where
+    F: Future,
+    T: Timer,
+{
+    let shared = Arc::new(Shared::default());
+
+    let watched = Watched { inner: fut, timer, interval, sleep: None, shared: shared.clone() };
+
+    (Watchdog { shared }, watched)
+}
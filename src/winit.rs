@@ -0,0 +1,183 @@
+//! Cancelling background work when a `winit` app closes
+//!
+//!Desktop apps run async work next to their event loop, and it should stop when the window
+//! it's for closes or the app quits.
+//![`WindowTokens`](struct.WindowTokens.html) hands out a token per window, children of a root
+//! token, and wrapping the app in [`CancelOnExit`](struct.CancelOnExit.html) cancels them:
+//! a window's token when the window is destroyed, and the root token when the event loop exits.
+//!
+//!Apps not using `winit` can cancel a [`CancellationToken`](../token/struct.CancellationToken.html)
+//! from their own quit handler, it's safe to call from any thread.
+//!
+//! # Example
+//! ```rust
+//!  use kyansel::{token::CancellationToken, winit::WindowTokens};
+//!  use winit::window::WindowId;
+//!
+//!  let root = CancellationToken::new();
+//!  let tokens = WindowTokens::new(&root);
+//!
+//!  let (main, settings) = (WindowId::from(1), WindowId::from(2));
+//!  let download = tokens.for_window(main);
+//!  let preview = tokens.for_window(settings);
+//!
+//!  //what `CancelOnExit` does when the settings window is destroyed
+//!  tokens.close(settings);
+//!  assert!(preview.is_cancelled() && !download.is_cancelled());
+//!
+//!  //and when the app quits
+//!  root.cancel();
+//!  assert!(download.is_cancelled());
+//! ```
+
+use super::token::CancellationToken;
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+use winit::{
+    application::ApplicationHandler,
+    event::{DeviceEvent, DeviceId, StartCause, WindowEvent},
+    event_loop::ActiveEventLoop,
+    window::WindowId,
+};
+
+///Tokens of each window of an app, children of a root token
+///
+///The registry is cheap to clone, all clones share the same tokens
+#[derive(Debug, Clone)]
+pub struct WindowTokens {
+    root: CancellationToken,
+    windows: Arc<Mutex<HashMap<WindowId, CancellationToken>>>,
+}
+
+impl WindowTokens {
+    ///Create a registry whose window tokens are children of `root`
+    pub fn new(root: &CancellationToken) -> Self {
+        Self { root: root.clone(), windows: Arc::default() }
+    }
+
+    ///Retrieve the root token, cancelled when the app quits
+    pub fn root(&self) -> &CancellationToken { &self.root }
+
+    ///Retrieve the token of the window `id`, cancelled when the window closes or the app quits
+    pub fn for_window(&self, id: WindowId) -> CancellationToken {
+        let mut windows = self.windows.lock().unwrap();
+        windows.entry(id).or_insert_with(|| self.root.child_token()).clone()
+    }
+
+    ///Cancel the token of the window `id`, a new one is created if it's asked for again
+    pub fn close(&self, id: WindowId) {
+        if let Some(token) = self.windows.lock().unwrap().remove(&id) {
+            token.cancel();
+        }
+    }
+}
+
+///`winit` app cancelling the tokens of a [`WindowTokens`](struct.WindowTokens.html)
+///
+///The token of a window is cancelled when it's destroyed, and the root token when the event loop
+/// exits, right before the wrapped app handles the event
+///
+/// # Example
+/// ```rust,no_run
+///  use kyansel::{token::CancellationToken, winit::{CancelOnExit, WindowTokens}};
+///  use winit::{
+///      application::ApplicationHandler,
+///      event::WindowEvent,
+///      event_loop::{ActiveEventLoop, EventLoop},
+///      window::WindowId,
+///  };
+///
+///  struct App {
+///      tokens: WindowTokens,
+///  }
+///
+///  impl ApplicationHandler for App {
+///      fn resumed(&mut self, event_loop: &ActiveEventLoop) {
+///          let window = event_loop.create_window(Default::default()).unwrap();
+///          //spawn background work for the window, with `self.tokens.for_window(window.id())`
+///      }
+///
+///      fn window_event(&mut self, event_loop: &ActiveEventLoop, _: WindowId, e: WindowEvent) {
+///          if matches!(e, WindowEvent::CloseRequested) {
+///              event_loop.exit();
+///          }
+///      }
+///  }
+///
+///  let tokens = WindowTokens::new(&CancellationToken::new());
+///  let mut app = CancelOnExit::new(App { tokens: tokens.clone() }, &tokens);
+///
+///  EventLoop::new().unwrap().run_app(&mut app).unwrap();
+/// ```
+#[derive(Debug)]
+pub struct CancelOnExit<A> {
+    app: A,
+    tokens: WindowTokens,
+}
+
+impl<A> CancelOnExit<A> {
+    ///Wrap `app`, cancelling the tokens of `tokens`
+    pub fn new(app: A, tokens: &WindowTokens) -> Self { Self { app, tokens: tokens.clone() } }
+
+    ///Retrieve a reference to the wrapped app
+    pub fn get_ref(&self) -> &A { &self.app }
+
+    ///Retrieve a mutable reference to the wrapped app
+    pub fn get_mut(&mut self) -> &mut A { &mut self.app }
+
+    ///Retrieve the wrapped app
+    pub fn into_inner(self) -> A { self.app }
+}
+
+impl<A, T> ApplicationHandler<T> for CancelOnExit<A>
+where
+    A: ApplicationHandler<T>,
+    T: 'static,
+{
+    fn new_events(&mut self, event_loop: &ActiveEventLoop, cause: StartCause) {
+        self.app.new_events(event_loop, cause)
+    }
+
+    fn resumed(&mut self, event_loop: &ActiveEventLoop) { self.app.resumed(event_loop) }
+
+    fn user_event(&mut self, event_loop: &ActiveEventLoop, event: T) {
+        self.app.user_event(event_loop, event)
+    }
+
+    fn window_event(
+        &mut self,
+        event_loop: &ActiveEventLoop,
+        window_id: WindowId,
+        event: WindowEvent,
+    ) {
+        if matches!(event, WindowEvent::Destroyed) {
+            self.tokens.close(window_id);
+        }
+
+        self.app.window_event(event_loop, window_id, event)
+    }
+
+    fn device_event(
+        &mut self,
+        event_loop: &ActiveEventLoop,
+        device_id: DeviceId,
+        event: DeviceEvent,
+    ) {
+        self.app.device_event(event_loop, device_id, event)
+    }
+
+    fn about_to_wait(&mut self, event_loop: &ActiveEventLoop) { self.app.about_to_wait(event_loop) }
+
+    fn suspended(&mut self, event_loop: &ActiveEventLoop) { self.app.suspended(event_loop) }
+
+    fn exiting(&mut self, event_loop: &ActiveEventLoop) {
+        self.tokens.root.cancel();
+        self.app.exiting(event_loop)
+    }
+
+    fn memory_warning(&mut self, event_loop: &ActiveEventLoop) {
+        self.app.memory_warning(event_loop)
+    }
+}